@@ -0,0 +1,38 @@
+// Checks the C header for the `ffi` feature, and generates the gRPC service
+// for the `grpc` feature with the protoc that comes as a crate
+//
+// The header is checked in, so building never writes to the source tree: it
+// is generated into OUT_DIR and compared, with a warning if it differs.
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        println!("cargo:rerun-if-changed=include/vanity_address.h");
+
+        let generated = format!("{}/vanity_address.h", out_dir);
+        cbindgen::generate(&crate_dir)
+            .expect("Failed to generate the C header")
+            .write_to_file(&generated);
+        let checked_in = std::fs::read_to_string(format!("{}/include/vanity_address.h", crate_dir)).unwrap_or_default();
+        if std::fs::read_to_string(&generated).unwrap_or_default() != checked_in {
+            println!(
+                "cargo:warning=include/vanity_address.h is out of date; regenerate it with \
+                 `cbindgen --config cbindgen.toml --output include/vanity_address.h`"
+            );
+        }
+    }
+
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/vanity.proto");
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().expect("No protoc for this platform"));
+        tonic_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/vanity.proto"], &["proto"])
+            .expect("Failed to generate the gRPC service");
+    }
+}
@@ -0,0 +1,134 @@
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#ifndef VANITY_ADDRESS_H
+#define VANITY_ADDRESS_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Opaque handle to a running search
+ */
+typedef struct VanitySearchHandle VanitySearchHandle;
+
+/**
+ * Progress of a search
+ */
+typedef struct VanityStats {
+  /**
+   * Candidates checked so far
+   */
+  uint64_t attempts;
+  /**
+   * Seconds spent searching so far
+   */
+  double elapsed_secs;
+  /**
+   * Whether the search has ended, by finding a match or being cancelled
+   */
+  bool finished;
+} VanityStats;
+
+/**
+ * A matching address and its private key, as NUL terminated strings
+ */
+typedef struct VanityResult {
+  char address[128];
+  /**
+   * Hex encoded private key
+   */
+  char private_key[128];
+  /**
+   * Candidates checked before the match was found
+   */
+  uint64_t attempts;
+  /**
+   * Seconds spent searching
+   */
+  double elapsed_secs;
+} VanityResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Start searching on a background thread.
+ *
+ * `suffix` may be NULL. `threads` of 0 uses every available core. Returns
+ * NULL if the options are invalid; if `error` is not NULL it then receives
+ * a message that must be released with `vanity_string_free`.
+ *
+ * # Safety
+ *
+ * `pattern` and a non-NULL `suffix` must be valid NUL terminated strings,
+ * and a non-NULL `error` must be valid for writes.
+ */
+struct VanitySearchHandle *vanity_search_start(const char *pattern,
+                                               const char *suffix,
+                                               uint32_t threads,
+                                               bool incremental,
+                                               char **error);
+
+/**
+ * Fill `stats` with the current progress of the search.
+ *
+ * # Safety
+ *
+ * `handle` must come from `vanity_search_start` and not have been freed,
+ * and `stats` must be valid for writes.
+ */
+void vanity_search_poll_stats(const struct VanitySearchHandle *handle, struct VanityStats *stats);
+
+/**
+ * Ask the search to stop. Returns immediately; the search ends shortly after.
+ *
+ * # Safety
+ *
+ * `handle` must come from `vanity_search_start` and not have been freed.
+ */
+void vanity_search_cancel(const struct VanitySearchHandle *handle);
+
+/**
+ * Copy the match into `result` if the search found one. With `wait`, block
+ * until the search ends first. Returns false if there is no match (yet),
+ * which is also how a search whose threads couldn't be started ends.
+ *
+ * # Safety
+ *
+ * `handle` must come from `vanity_search_start` and not have been freed,
+ * and `result` must be valid for writes.
+ */
+bool vanity_search_result(const struct VanitySearchHandle *handle,
+                          bool wait,
+                          struct VanityResult *result);
+
+/**
+ * Cancel the search if it is still running, wait for it to end and
+ * release the handle. NULL is ignored.
+ *
+ * # Safety
+ *
+ * `handle` must be NULL or come from `vanity_search_start`, and must not be
+ * used again afterwards.
+ */
+void vanity_search_free(struct VanitySearchHandle *handle);
+
+/**
+ * Release a string returned by this library. NULL is ignored.
+ *
+ * # Safety
+ *
+ * `string` must be NULL or a string returned by this library, and must not
+ * be used again afterwards.
+ */
+void vanity_string_free(char *string);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* VANITY_ADDRESS_H */
@@ -0,0 +1,30 @@
+;; Example matcher plugin: addresses with many digits
+;;
+;; Scores an address by the number of digits in it, and matches the ones
+;; with at least $min_digits of them. Compile with WABT's wat2wasm:
+;;
+;;   wat2wasm digits.wat -o digits.wasm
+;;   vanity-address-rust --plugin digits.wasm
+
+(module
+  (memory (export "memory") 1)
+
+  ;; Where the address, then the witness program, are written
+  (func (export "buffer") (result i32)
+    (i32.const 0))
+
+  (func (export "score") (param $address_len i32) (param $program_len i32) (result i32)
+    (local $i i32)
+    (local $digits i32)
+    (local $c i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $address_len)))
+        (local.set $c (i32.load8_u (local.get $i)))
+        ;; '0' to '9'
+        (if (i32.lt_u (i32.sub (local.get $c) (i32.const 48)) (i32.const 10))
+          (then (local.set $digits (i32.add (local.get $digits) (i32.const 1)))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    ;; $min_digits: the "1" of bc1q counts too
+    (select (local.get $digits) (i32.const 0) (i32.ge_u (local.get $digits) (i32.const 21)))))
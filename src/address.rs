@@ -0,0 +1,207 @@
+// Address types the search can produce
+//
+// Besides addresses, a search can grind Lightning node IDs: the hex encoded
+// compressed public key a node is known by, whose private key is the node's
+// identity key.
+//
+// A silent payment address (BIP 352) encodes two public keys, so it can't be
+// derived from one key on its own: the search pairs every candidate scan key
+// with a spend key of its thread.
+
+use bitcoin::bech32::{self, FromBase32, ToBase32, Variant};
+use bitcoin::key::TweakedPublicKey;
+use bitcoin::secp256k1::{self, Secp256k1, SecretKey};
+use bitcoin::{Address, Network, PrivateKey};
+use clap::ValueEnum;
+
+use crate::Multisig;
+
+/// The kind of address to search for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+#[non_exhaustive]
+pub enum AddressType {
+    /// Native segwit v0 pay-to-witness-pubkey-hash (bc1q...)
+    #[default]
+    P2wpkh,
+    /// Taproot key path (bc1p...) whose output key is the private key's own
+    /// public key, without a script commitment: a `rawtr()` descriptor
+    P2tr,
+    /// Native segwit v0 pay-to-witness-script-hash (bc1q...) of a sorted
+    /// multisig wallet whose other keys are fixed (see [`Multisig`])
+    P2wsh,
+    /// Lightning node ID: the compressed public key as 66 hex digits
+    /// (02... or 03...), the same on every network
+    NodeId,
+    /// Silent payment address (sp1q...) of a ground scan key and a fixed
+    /// spend key, both of which a match reports
+    SilentPayment,
+}
+
+impl AddressType {
+    /// Every supported address type
+    pub const ALL: &'static [AddressType] = &[
+        AddressType::P2wpkh,
+        AddressType::P2tr,
+        AddressType::P2wsh,
+        AddressType::NodeId,
+        AddressType::SilentPayment,
+    ];
+
+    /// What every mainnet address of this type starts with; patterns of
+    /// node IDs start at their first digit
+    pub fn prefix(self) -> &'static str {
+        match self {
+            AddressType::P2wpkh | AddressType::P2wsh => "bc1q",
+            AddressType::P2tr => "bc1p",
+            AddressType::NodeId => "",
+            AddressType::SilentPayment => "sp1q",
+        }
+    }
+
+    /// The address of this type for `private_key` on `network`, or `None` if
+    /// the key can't have one on its own: segwit requires a compressed key,
+    /// a P2WSH address also depends on the cosigners, and a silent payment
+    /// address on the spend key
+    pub fn address(self, private_key: &PrivateKey, network: Network) -> Option<String> {
+        if !private_key.compressed {
+            return None;
+        }
+        self.encode(&private_key.inner.public_key(&Secp256k1::new()), None, network)
+    }
+
+    // The address of this type for a public key, and for P2WSH the multisig
+    // wallet it belongs to
+    pub(crate) fn encode(
+        self,
+        public_key: &secp256k1::PublicKey,
+        multisig: Option<&Multisig>,
+        network: Network,
+    ) -> Option<String> {
+        let address = match self {
+            AddressType::P2wpkh => Address::p2wpkh(&bitcoin::PublicKey::new(*public_key), network).ok()?,
+            AddressType::P2tr => {
+                let output_key = TweakedPublicKey::dangerous_assume_tweaked(public_key.x_only_public_key().0);
+                Address::p2tr_tweaked(output_key, network)
+            }
+            AddressType::P2wsh => return Some(multisig?.address(public_key, network)),
+            AddressType::NodeId => return Some(public_key.to_string()),
+            AddressType::SilentPayment => return None,
+        };
+        Some(address.to_string())
+    }
+}
+
+impl std::fmt::Display for AddressType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AddressType::P2wpkh => "p2wpkh",
+            AddressType::P2tr => "p2tr",
+            AddressType::P2wsh => "p2wsh",
+            AddressType::NodeId => "node-id",
+            AddressType::SilentPayment => "silent-payment",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The mainnet silent payment address of a scan and a spend key
+pub(crate) fn silent_payment_address(scan_key: &secp256k1::PublicKey, spend_key: &secp256k1::PublicKey) -> String {
+    let mut program = scan_key.serialize().to_vec();
+    program.extend_from_slice(&spend_key.serialize());
+    let mut data = vec![bech32::u5::try_from_u8(0).expect("version 0")];
+    data.extend(program.to_base32());
+    bech32::encode("sp", data, Variant::Bech32m).expect("valid HRP")
+}
+
+/// Parse a private key given as WIF, or as 64 hex digits (taken to be a
+/// compressed mainnet key, as printed by the search)
+pub fn parse_private_key(key: &str) -> Result<PrivateKey, String> {
+    if key.len() == 64 && key.bytes().all(|b| b.is_ascii_hexdigit()) {
+        let secret_key: SecretKey = key.parse().map_err(|e| format!("Invalid private key: {}", e))?;
+        return Ok(PrivateKey::new(secret_key, Network::Bitcoin));
+    }
+    PrivateKey::from_wif(key).map_err(|e| format!("Invalid WIF private key: {}", e))
+}
+
+/// Find the address type and network under which `private_key` controls
+/// `address`, trying every supported address type and every network the
+/// key is valid for. For a silent payment address, that is the network
+/// under which `private_key` is its scan key.
+pub fn identify_address(private_key: &PrivateKey, address: &str) -> Option<(AddressType, Network)> {
+    // WIF only tells mainnet keys apart from all the test networks
+    let networks: &[Network] = match private_key.network {
+        Network::Bitcoin => &[Network::Bitcoin],
+        _ => &[Network::Testnet, Network::Signet, Network::Regtest],
+    };
+
+    AddressType::ALL.iter().find_map(|&address_type| {
+        if address_type == AddressType::SilentPayment {
+            let network = silent_payment_network(private_key, address)?;
+            return networks.contains(&network).then_some((address_type, network));
+        }
+        networks.iter().find_map(|&network| {
+            let derived = address_type.address(private_key, network)?;
+            derived.eq_ignore_ascii_case(address).then_some((address_type, network))
+        })
+    })
+}
+
+// The network of a silent payment address whose scan key is the public key
+// of `private_key`. Signet shares the testnet HRP, so it's reported as
+// testnet.
+fn silent_payment_network(private_key: &PrivateKey, address: &str) -> Option<Network> {
+    let (hrp, data, variant) = bech32::decode(address).ok()?;
+    let network = match hrp.as_str() {
+        "sp" => Network::Bitcoin,
+        "tsp" => Network::Testnet,
+        "sprt" => Network::Regtest,
+        _ => return None,
+    };
+    let (version, program) = data.split_first()?;
+    let program = Vec::<u8>::from_base32(program).ok()?;
+    if variant != Variant::Bech32m || version.to_u8() != 0 || program.len() != 66 {
+        return None;
+    }
+    let scan_key = private_key.inner.public_key(&Secp256k1::new()).serialize();
+    (private_key.compressed && program[..33] == scan_key).then_some(network)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The receiving keys and address of BIP 352's test vectors
+    const SCAN_KEY: &str = "0f694e068028a717f8af6b9411f9a133dd3565258714cc226594b34db90c1f2c";
+    const SPEND_KEY: &str = "9d6ad855ce3417ef84e836892e5a56392bfba05fa5d97ccea30e266f540e08b3";
+    const ADDRESS: &str = concat!(
+        "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usf",
+        "pxumr70xc9pkqwv"
+    );
+
+    fn public_key(private_key: &str) -> secp256k1::PublicKey {
+        parse_private_key(private_key).unwrap().inner.public_key(&Secp256k1::new())
+    }
+
+    #[test]
+    fn silent_payment_addresses_match_bip352() {
+        assert_eq!(silent_payment_address(&public_key(SCAN_KEY), &public_key(SPEND_KEY)), ADDRESS);
+    }
+
+    #[test]
+    fn silent_payment_addresses_are_identified_by_their_scan_key() {
+        let scan_key = parse_private_key(SCAN_KEY).unwrap();
+        let mainnet = Some((AddressType::SilentPayment, Network::Bitcoin));
+        assert_eq!(identify_address(&scan_key, ADDRESS), mainnet);
+        assert_eq!(identify_address(&scan_key, &ADDRESS.to_uppercase()), mainnet);
+        assert_eq!(identify_address(&parse_private_key(SPEND_KEY).unwrap(), ADDRESS), None);
+
+        // The same keys on testnet, for a testnet WIF only
+        let program = [public_key(SCAN_KEY).serialize(), public_key(SPEND_KEY).serialize()].concat();
+        let mut data = vec![bech32::u5::try_from_u8(0).unwrap()];
+        data.extend(program.to_base32());
+        let testnet = bech32::encode("tsp", data, Variant::Bech32m).unwrap();
+        assert_eq!(identify_address(&scan_key, &testnet), None);
+        let testnet_key = PrivateKey::new(scan_key.inner, Network::Testnet);
+        assert_eq!(identify_address(&testnet_key, &testnet), Some((AddressType::SilentPayment, Network::Testnet)));
+    }
+}
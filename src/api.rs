@@ -0,0 +1,172 @@
+// REST API server mode
+//
+// Jobs are submitted over HTTP and run one at a time, in submission order
+// (see `jobs`), and can be polled and cancelled independently.
+//
+//   POST   /jobs             submit {"pattern", "suffix", "incremental"}
+//   GET    /jobs             list all jobs
+//   GET    /jobs/{id}        status and stats of a job
+//   GET    /jobs/{id}/result the match, once found
+//   DELETE /jobs/{id}        cancel a queued or running job
+//   GET    /jobs/{id}/events WebSocket pushing stats snapshots and the match
+
+use serde_json::json;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+use tracing::info;
+use tungstenite::protocol::Role;
+use tungstenite::WebSocket;
+
+use vanity_address_rust::HashBackend;
+
+use crate::jobs::{self, Job, JobRequest, Jobs, Status};
+use crate::keys::KeyFormat;
+use crate::scheduling::Threads;
+
+fn summary(job: &Job) -> serde_json::Value {
+    json!({
+        "id": job.id,
+        "pattern": job.pattern,
+        "suffix": job.suffix,
+        "incremental": job.incremental,
+        "status": job.status,
+        "attempts": job.attempts(),
+        "elapsed_secs": job.elapsed().as_secs_f64(),
+        "rate": job.rate(),
+        "error": job.error,
+    })
+}
+
+/// Serve the REST API on `listen` until the process is killed. Results hold
+/// their keys in `key_format`. Only loopback addresses are accepted unless
+/// `allow_remote` is set.
+pub fn serve(
+    listen: &str,
+    hash_backend: HashBackend,
+    threads: Threads,
+    stats_interval: Duration,
+    key_format: KeyFormat,
+    allow_remote: bool,
+) -> Result<(), String> {
+    jobs::check_listen(listen, allow_remote)?;
+    let server = Server::http(listen).map_err(|e| format!("Failed to listen on {}: {}", listen, e))?;
+    let jobs = Jobs::start(hash_backend, threads, stats_interval);
+
+    info!("API server listening on http://{}", listen);
+    info!("Using hash backend: {}", hash_backend);
+
+    for request in server.incoming_requests() {
+        handle(&jobs, key_format, request);
+    }
+    Ok(())
+}
+
+fn handle(jobs: &Arc<Jobs>, key_format: KeyFormat, mut request: Request) {
+    let path: Vec<String> = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or("")
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect();
+    let path: Vec<&str> = path.iter().map(String::as_str).collect();
+    let id = path.get(1).and_then(|id| id.parse::<u64>().ok());
+
+    let (code, body) = match (request.method(), path.as_slice(), id) {
+        (Method::Post, ["jobs"], _) => {
+            let mut body = String::new();
+            let _ = request.as_reader().read_to_string(&mut body);
+            submit(jobs, &body)
+        }
+        (Method::Get, ["jobs"], _) => (200, json!(jobs.all(summary))),
+        (Method::Get, ["jobs", _], Some(id)) => jobs.get(id, |job| (200, summary(job))).unwrap_or_else(not_found),
+        (Method::Get, ["jobs", _, "events"], Some(id)) => {
+            if jobs.get(id, |_| ()).is_some() {
+                subscribe(jobs, request, id);
+                return;
+            }
+            not_found()
+        }
+        (Method::Get, ["jobs", _, "result"], Some(id)) => jobs
+            .get(id, |job| match &job.result {
+                Some(found) => {
+                    let mut result = key_format.json_fields(found);
+                    result.insert("address".to_string(), json!(found.address));
+                    (200, result.into())
+                }
+                None => (409, json!({ "error": "job has no result", "status": job.status })),
+            })
+            .unwrap_or_else(not_found),
+        (Method::Delete, ["jobs", _], Some(id)) => jobs.cancel(id, |job| (200, summary(job))).unwrap_or_else(not_found),
+        _ => not_found(),
+    };
+
+    let response = Response::from_string(body.to_string())
+        .with_status_code(code)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+    let _ = request.respond(response);
+}
+
+fn submit(jobs: &Jobs, body: &str) -> (u16, serde_json::Value) {
+    let request: JobRequest = match serde_json::from_str(body) {
+        Ok(request) => request,
+        Err(e) => return (400, json!({ "error": format!("Invalid job: {}", e) })),
+    };
+    match jobs.submit(request) {
+        Ok(id) => jobs.get(id, |job| (201, summary(job))).unwrap_or_else(not_found),
+        Err(e) => (400, json!({ "error": e })),
+    }
+}
+
+fn not_found() -> (u16, serde_json::Value) {
+    (404, json!({ "error": "not found" }))
+}
+
+// Upgrade the request to a WebSocket and push job events from a new thread:
+// a stats snapshot every stats interval, and a final event the moment the
+// job is found or cancelled. The match event carries the address only; the
+// private key is available from the result endpoint.
+fn subscribe(jobs: &Arc<Jobs>, request: Request, id: u64) {
+    let key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+        .map(|h| h.value.as_str().to_string());
+    let Some(key) = key else {
+        let response = Response::from_string("expected a WebSocket upgrade").with_status_code(400);
+        let _ = request.respond(response);
+        return;
+    };
+
+    let accept = tungstenite::handshake::derive_accept_key(key.as_bytes());
+    let response = Response::empty(StatusCode(101))
+        .with_header(Header::from_bytes("Upgrade", "websocket").unwrap())
+        .with_header(Header::from_bytes("Connection", "Upgrade").unwrap())
+        .with_header(Header::from_bytes("Sec-WebSocket-Accept", accept).unwrap());
+    let stream = request.upgrade("websocket", response);
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+    let jobs = Arc::clone(jobs);
+    thread::spawn(move || {
+        jobs.watch(id, event, |event| socket.send(tungstenite::Message::Text(event.to_string())).is_ok());
+        let _ = socket.close(None);
+        let _ = socket.flush();
+    });
+}
+
+fn event(job: &Job) -> serde_json::Value {
+    match job.status {
+        Status::Queued | Status::Running => json!({ "type": "stats", "job": summary(job) }),
+        Status::Found => json!({
+            "type": "match",
+            "job": summary(job),
+            "address": job.result.as_ref().map(|found| found.address.clone()),
+        }),
+        Status::Cancelled => json!({ "type": "cancelled", "job": summary(job) }),
+        Status::Failed => json!({ "type": "failed", "job": summary(job) }),
+    }
+}
@@ -0,0 +1,457 @@
+// Batch runs for several patterns at once
+//
+// A job lists patterns with the number of matches wanted of each, e.g.
+// "cafe:3, dead:1, 777:10", given with --job or one or more entries per line
+// in a --job-file. All patterns share one search, and every pattern is dropped
+// from it once it has its matches, so the rest get all the attempts. With
+// --output, the matches of every pattern go to a file of their own.
+//
+// Entries can also have a priority, 1 being the highest and the default:
+// "cafe@1, 777:10@2". The job ends once the patterns of its highest priority
+// have their matches. The others are checked against every candidate all the
+// same, and their matches reported as they come, but no one waits for them.
+// Targets are kept in order of priority, which is also the order the search
+// gets their patterns in, so an address matching several patterns counts
+// towards the most urgent one that still needs matches. There is no weighting:
+// every pattern is checked against every candidate, so there are no attempts
+// to share out between them.
+//
+// With --watch-job-file, a job takes in edits to its file while it runs:
+// patterns can be added, removed or given other counts, and the search swaps
+// to the new set of patterns at once, keeping its stats and the matches found
+// so far. An edit is only read once the file has stopped changing for a
+// moment, so a half-written file never drops patterns; one that doesn't parse
+// leaves the job as it was.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use vanity_address_rust::Match;
+
+use crate::color::highlight_address;
+use crate::output::Output;
+
+/// A pattern of a job and the number of matches wanted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Target {
+    pub pattern: String,
+    pub suffix: Option<String>,
+    pub count: usize,
+    pub priority: u32,
+}
+
+impl Target {
+    /// "cafe", "cafe...dead" or "...dead", as entries are written
+    pub fn label(&self) -> String {
+        label(&self.pattern, self.suffix.as_deref())
+    }
+
+    fn is(&self, found: &Match) -> bool {
+        self.pattern == found.pattern && self.suffix == found.suffix
+    }
+}
+
+/// "cafe", "cafe...dead" or "...dead": a pattern and suffix as they are
+/// written in jobs, fed patterns and logs
+pub fn label(pattern: &str, suffix: Option<&str>) -> String {
+    match suffix {
+        Some(suffix) => format!("{}...{}", pattern, suffix),
+        None => pattern.to_string(),
+    }
+}
+
+/// Parse `PATTERN[...SUFFIX]`, lowercased; one of the two may be left out,
+/// not both
+pub fn parse_pattern(text: &str) -> Result<(String, Option<String>), String> {
+    let (pattern, suffix) = match text.split_once("...") {
+        Some((pattern, suffix)) => (pattern.trim(), Some(suffix.trim().to_lowercase())),
+        None => (text.trim(), None),
+    };
+    if pattern.is_empty() && suffix.as_deref().unwrap_or_default().is_empty() {
+        return Err(format!("'{}' has no pattern", text));
+    }
+    Ok((pattern.to_lowercase(), suffix))
+}
+
+/// Parse comma separated entries of `PATTERN[...SUFFIX][:COUNT][@PRIORITY]`,
+/// where the count and priority default to 1. Everything after a `#` is a
+/// comment.
+pub fn parse_targets(spec: &str) -> Result<Vec<Target>, String> {
+    let mut targets = Vec::new();
+    for line in spec.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        for entry in line.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            targets.push(parse_target(entry)?);
+        }
+    }
+    Ok(targets)
+}
+
+fn parse_target(entry: &str) -> Result<Target, String> {
+    let (rest, priority) = match entry.rsplit_once('@') {
+        Some((rest, priority)) => {
+            let priority = priority
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&priority| priority > 0)
+                .ok_or_else(|| format!("Invalid priority in job entry '{}': expected a positive number", entry))?;
+            (rest.trim(), priority)
+        }
+        None => (entry, 1),
+    };
+    let (patterns, count) = match rest.rsplit_once(':') {
+        Some((patterns, count)) => {
+            let count = count
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&count| count > 0)
+                .ok_or_else(|| format!("Invalid count in job entry '{}': expected a positive number", entry))?;
+            (patterns.trim(), count)
+        }
+        None => (rest, 1),
+    };
+    let (pattern, suffix) = parse_pattern(patterns).map_err(|_| format!("Job entry '{}' has no pattern", entry))?;
+    Ok(Target {
+        pattern,
+        suffix,
+        count,
+        priority,
+    })
+}
+
+/// The targets of a running job and the matches found for each, highest
+/// priority first
+pub struct Batch {
+    targets: Vec<Target>,
+    // Addresses found for each target
+    found: Vec<Vec<String>>,
+    // Output file of each target
+    outputs: Vec<Output>,
+    // The path the output files are named after, if any
+    output_path: Option<PathBuf>,
+}
+
+impl Batch {
+    pub fn new(mut targets: Vec<Target>) -> Result<Self, String> {
+        if targets.is_empty() {
+            return Err("The job has no patterns".to_string());
+        }
+        for (i, target) in targets.iter().enumerate() {
+            if targets[..i].iter().any(|t| t.pattern == target.pattern && t.suffix == target.suffix) {
+                return Err(format!("'{}' is in the job more than once", target.label()));
+            }
+        }
+        // Entries of the same priority keep their order
+        targets.sort_by_key(|target| target.priority);
+        let found = vec![Vec::new(); targets.len()];
+        Ok(Batch {
+            targets,
+            found,
+            outputs: Vec::new(),
+            output_path: None,
+        })
+    }
+
+    pub fn targets(&self) -> &[Target] {
+        &self.targets
+    }
+
+    /// Write the matches of every target to a file next to `path`, named
+    /// after the target: keys.txt becomes keys-cafe.txt, keys-cafe-dead.txt
+    /// and so on. `open` opens each one.
+    pub fn open_outputs(&mut self, path: &Path, open: impl Fn(&Path) -> Output) {
+        self.outputs = self.targets.iter().map(|target| open(&output_path(path, target))).collect();
+        self.output_path = Some(path.to_path_buf());
+    }
+
+    fn position(&self, target: &Target) -> Option<usize> {
+        self.targets.iter().position(|t| t.pattern == target.pattern && t.suffix == target.suffix)
+    }
+
+    /// The (pattern, suffix) pairs of targets that still need matches, in
+    /// the order the search should check them
+    pub fn pending(&self) -> Vec<(String, Option<String>)> {
+        self.targets
+            .iter()
+            .zip(&self.found)
+            .filter(|(target, found)| found.len() < target.count)
+            .map(|(target, _)| (target.pattern.clone(), target.suffix.clone()))
+            .collect()
+    }
+
+    /// Take on the targets of `job`, an edited version of this one, keeping
+    /// the matches of targets in both. `search` is given the patterns that
+    /// still need matches, unless there are none; `open` opens the output
+    /// files of new targets. Nothing changes if either fails. Returns what
+    /// changed, e.g. "added 'cafe' (3 matches)".
+    pub fn reload(
+        &mut self,
+        job: Batch,
+        search: impl Fn(&[(String, Option<String>)]) -> Result<(), String>,
+        open: impl Fn(&Path) -> Result<Output, String>,
+    ) -> Result<Vec<String>, String> {
+        let mut reloaded = job;
+        for (target, found) in reloaded.targets.iter().zip(&mut reloaded.found) {
+            if let Some(index) = self.position(target) {
+                found.clone_from(&self.found[index]);
+            }
+        }
+
+        let pending = reloaded.pending();
+        if !pending.is_empty() {
+            search(&pending)?;
+        }
+        if let Some(path) = self.output_path.clone() {
+            let mut opened = Vec::new();
+            for target in reloaded.targets.iter().filter(|target| self.position(target).is_none()) {
+                match open(&output_path(&path, target)) {
+                    Ok(output) => opened.push(output),
+                    Err(e) => {
+                        // Back to the patterns of the job as it was
+                        let _ = search(&self.pending());
+                        return Err(e);
+                    }
+                }
+            }
+            let mut opened = opened.into_iter();
+            let mut outputs: Vec<Option<Output>> = std::mem::take(&mut self.outputs).into_iter().map(Some).collect();
+            for target in &reloaded.targets {
+                let output = match self.position(target) {
+                    Some(index) => outputs[index].take(),
+                    None => opened.next(),
+                };
+                reloaded.outputs.extend(output);
+            }
+            reloaded.output_path = Some(path);
+        }
+
+        let mut changes = Vec::new();
+        for target in &self.targets {
+            if reloaded.position(target).is_none() {
+                changes.push(format!("removed '{}'", target.label()));
+            }
+        }
+        for target in &reloaded.targets {
+            match self.position(target).map(|index| &self.targets[index]) {
+                None => changes.push(format!("added '{}' ({})", target.label(), matches(target.count))),
+                Some(old) => {
+                    if old.count != target.count {
+                        changes.push(format!("'{}' now wants {}", target.label(), matches(target.count)));
+                    }
+                    if old.priority != target.priority {
+                        changes.push(format!("'{}' now has priority {}", target.label(), target.priority));
+                    }
+                }
+            }
+        }
+        *self = reloaded;
+        Ok(changes)
+    }
+
+    /// The output file for a match, if any
+    pub fn output(&mut self, found: &Match) -> Option<&mut Output> {
+        let index = self.targets.iter().position(|target| target.is(found))?;
+        self.outputs.get_mut(index)
+    }
+
+    /// Whether a match counts towards a target that still needs matches
+    pub fn wants(&self, found: &Match) -> bool {
+        let index = self.targets.iter().position(|target| target.is(found));
+        index.is_some_and(|index| self.found[index].len() < self.targets[index].count)
+    }
+
+    /// Count a match towards its target. Returns the target if the match
+    /// completed it.
+    pub fn record(&mut self, found: &Match) -> Option<&Target> {
+        let index = self.targets.iter().position(|target| target.is(found))?;
+        self.found[index].push(found.address.clone());
+        let target = &self.targets[index];
+        (self.found[index].len() == target.count).then_some(target)
+    }
+
+    /// The highest priority of any target, the lowest number
+    pub fn top_priority(&self) -> u32 {
+        self.targets.iter().map(|target| target.priority).min().unwrap_or(1)
+    }
+
+    /// Whether the targets differ in priority
+    pub fn is_prioritized(&self) -> bool {
+        let top = self.top_priority();
+        self.targets.iter().any(|target| target.priority != top)
+    }
+
+    /// Whether every target of the highest priority has its matches, which
+    /// ends the job
+    pub fn is_complete(&self) -> bool {
+        let top = self.top_priority();
+        self.targets
+            .iter()
+            .zip(&self.found)
+            .all(|(target, found)| target.priority > top || found.len() >= target.count)
+    }
+
+    /// Whether every target has its matches, whatever its priority
+    pub fn has_everything(&self) -> bool {
+        self.targets.iter().zip(&self.found).all(|(target, found)| found.len() >= target.count)
+    }
+
+    /// "cafe 2/3, dead 1/1, 777 0/10"
+    pub fn progress(&self) -> String {
+        let parts: Vec<String> = self
+            .targets
+            .iter()
+            .zip(&self.found)
+            .map(|(target, found)| format!("{} {}/{}", target.label(), found.len(), target.count))
+            .collect();
+        parts.join(", ")
+    }
+
+    /// The addresses found, grouped by target
+    pub fn print_summary(&self) {
+        for (target, found) in self.targets.iter().zip(&self.found) {
+            println!("{} ({}/{}):", target.label(), found.len(), target.count);
+            for address in found {
+                println!("  {}", highlight_address(address, &target.pattern, target.suffix.as_deref()));
+            }
+        }
+    }
+}
+
+// "1 match", "3 matches"
+fn matches(count: usize) -> String {
+    if count == 1 {
+        "1 match".to_string()
+    } else {
+        format!("{} matches", count)
+    }
+}
+
+/// Notices edits to a job file, by its modification time and size
+pub struct Watch {
+    path: PathBuf,
+    // As of the last edit taken in, and as of the last check
+    taken: Option<(SystemTime, u64)>,
+    seen: Option<(SystemTime, u64)>,
+}
+
+impl Watch {
+    pub fn new(path: &Path) -> Self {
+        let stamp = stamp(path);
+        Watch {
+            path: path.to_path_buf(),
+            taken: stamp,
+            seen: stamp,
+        }
+    }
+
+    /// Whether the file was edited since the last edit taken in, and has
+    /// stayed the same since the previous check. A file that is missing for
+    /// a moment, e.g. while an editor replaces it, hasn't changed.
+    pub fn changed(&mut self) -> bool {
+        let Some(stamp) = stamp(&self.path) else { return false };
+        let settled = self.seen == Some(stamp);
+        self.seen = Some(stamp);
+        if settled && self.taken != Some(stamp) {
+            self.taken = Some(stamp);
+            return true;
+        }
+        false
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn output_path(path: &Path, target: &Target) -> PathBuf {
+    let mut label = target.pattern.clone();
+    if let Some(suffix) = &target.suffix {
+        label = format!("{}-{}", label, suffix);
+    }
+    let stem = path.file_stem().map_or_else(Default::default, |stem| stem.to_string_lossy());
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, label, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, label),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::ControlFlow;
+    use std::time::Duration;
+    use vanity_address_rust::{Event, SearchConfig, VanitySearch};
+
+    fn target(pattern: &str, suffix: Option<&str>, count: usize, priority: u32) -> Target {
+        Target {
+            pattern: pattern.to_string(),
+            suffix: suffix.map(str::to_string),
+            count,
+            priority,
+        }
+    }
+
+    #[test]
+    fn entries_take_a_priority() {
+        let targets = parse_targets("cafe, dead@2, 777:10@3, c0...ff:2@1 # comment\n beef @ 4").unwrap();
+        assert_eq!(
+            targets,
+            [
+                target("cafe", None, 1, 1),
+                target("dead", None, 1, 2),
+                target("777", None, 10, 3),
+                target("c0", Some("ff"), 2, 1),
+                target("beef", None, 1, 4),
+            ]
+        );
+        for entry in ["cafe@0", "cafe@", "cafe@x", "cafe@-1", "cafe:2@1.5"] {
+            assert!(parse_targets(entry).is_err(), "{}", entry);
+        }
+    }
+
+    #[test]
+    fn targets_are_kept_most_urgent_first() {
+        let batch = Batch::new(parse_targets("ca@2, 777:3@3, cafe@1, dead@2").unwrap()).unwrap();
+        let labels: Vec<String> = batch.targets().iter().map(Target::label).collect();
+        assert_eq!(labels, ["cafe", "ca", "dead", "777"]);
+        assert_eq!(batch.pending()[0], ("cafe".to_string(), None));
+        assert_eq!(batch.top_priority(), 1);
+    }
+
+    // Every address starting with "cq" also starts with "c", and is found
+    // about once in 1024 candidates, so this takes a moment even unoptimized
+    #[test]
+    fn overlapping_patterns_count_towards_the_most_urgent() {
+        let mut batch = Batch::new(parse_targets("c:3@2, cq@1").unwrap()).unwrap();
+        let pending = batch.pending();
+        let search = VanitySearch::new(SearchConfig {
+            pattern: pending[0].0.clone(),
+            threads: Some(1),
+            ..Default::default()
+        })
+        .unwrap();
+        search.set_patterns(&pending).unwrap();
+
+        search
+            .run_with(Duration::MAX, |event| {
+                if let Event::Match(found) = event {
+                    let expected = if found.address.starts_with("bc1qcq") { "cq" } else { "c" };
+                    assert_eq!(found.pattern, expected, "{}", found.address);
+                    if batch.wants(&found) {
+                        batch.record(&found);
+                    }
+                }
+                if batch.is_complete() {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .unwrap();
+        assert!(batch.progress().starts_with("cq 1/1, c "), "{}", batch.progress());
+    }
+}
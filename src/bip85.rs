@@ -0,0 +1,274 @@
+// Candidate keys derived from an existing wallet with BIP85
+//
+// BIP85 derives independent child secrets from a master key, so they can be
+// recovered from the master's backup instead of being backed up on their
+// own. The WIF application turns the hardened child m/83696968'/2'/{index}'
+// into a private key: the first 32 bytes of HMAC-SHA512 with the key
+// "bip-entropy-from-k" over the child's private key. A BIP85 search grinds
+// the index, so every match is the master backup plus a number.
+//
+// Instead of BIP85, a search can also grind the last index of a path in the
+// wallet itself, e.g. m/84'/0'/0'/0/i for the receive addresses of its first
+// native segwit account, so that a match is one of the wallet's own addresses
+// and shows up in it once it looks that far ahead.
+//
+// Indices are handed out to the search threads a batch at a time from a
+// shared counter. There are 2^31 hardened or normal indices, so a search ends
+// once it has tried them all.
+
+use bitcoin::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey, Fingerprint};
+use bitcoin::hashes::{hmac, sha512, Hash, HashEngine};
+use bitcoin::secp256k1::{self, Secp256k1, SecretKey};
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use zeroize::Zeroize;
+
+use crate::master::{erase, master_key_from_mnemonic, master_key_from_xprv};
+
+// Purpose and application numbers of the WIF application
+const WIF_PATH: &str = "m/83696968'/2'";
+
+// Number of hardened child indices
+const INDICES: u32 = 1 << 31;
+
+/// A master key to derive candidate keys from with BIP85, or at a path of
+/// the wallet itself
+///
+/// Holds secret key material, which is wiped when dropped; `Debug` output is
+/// redacted.
+#[derive(Clone)]
+pub struct Bip85 {
+    // The master key at m/83696968'/2', or at `chain`
+    parent: ExtendedPrivKey,
+    fingerprint: Fingerprint,
+    start: u32,
+    chain: Option<DerivationPath>,
+}
+
+/// Where the key of a match sits in its master wallet
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bip85Child {
+    /// Fingerprint of the master key
+    pub fingerprint: Fingerprint,
+    /// Index of the BIP85 WIF child, or of the child of `chain`
+    pub index: u32,
+    /// For a key of the wallet itself rather than a BIP85 key, the path of
+    /// its parent, e.g. m/84'/0'/0'/0
+    pub chain: Option<DerivationPath>,
+}
+
+impl Bip85Child {
+    /// The child at `path` of the master key with `fingerprint`: a BIP85 WIF
+    /// child, or a normal child of the wallet
+    pub fn at(fingerprint: Fingerprint, path: &DerivationPath) -> Result<Self, String> {
+        let wif = DerivationPath::from_str(WIF_PATH).expect("valid path");
+        let (chain, index) = split_path(path)?;
+        match index {
+            ChildNumber::Hardened { index } if chain == wif => Ok(Bip85Child { fingerprint, index, chain: None }),
+            ChildNumber::Normal { index } => Ok(Bip85Child { fingerprint, index, chain: Some(chain) }),
+            ChildNumber::Hardened { .. } => Err(format!("{} is neither a BIP85 key nor a normal child", path)),
+        }
+    }
+
+    /// Derivation path of the child, e.g. m/83696968'/2'/42' or
+    /// m/84'/0'/0'/0/42
+    pub fn path(&self) -> String {
+        match &self.chain {
+            Some(chain) => format!("{}/{}", chain, self.index),
+            None => format!("{}/{}'", WIF_PATH, self.index),
+        }
+    }
+}
+
+// A path's parent and last step
+fn split_path(path: &DerivationPath) -> Result<(DerivationPath, ChildNumber), String> {
+    let steps: &[ChildNumber] = path.as_ref();
+    match steps.split_last() {
+        Some((last, parent)) => Ok((DerivationPath::from(parent), *last)),
+        None => Err("The path names the master key itself, not a child".to_string()),
+    }
+}
+
+impl Bip85 {
+    /// The master key of a BIP39 mnemonic and optional passphrase
+    pub fn from_mnemonic(mnemonic: &str, passphrase: &str) -> Result<Self, String> {
+        Self::from_master(master_key_from_mnemonic(mnemonic, passphrase)?)
+    }
+
+    /// A master key given as an xprv
+    pub fn from_xprv(xprv: &str) -> Result<Self, String> {
+        Self::from_master(master_key_from_xprv(xprv)?)
+    }
+
+    /// A master key, which is erased once the BIP85 parent is derived
+    pub fn from_master(mut master: ExtendedPrivKey) -> Result<Self, String> {
+        if master.depth != 0 {
+            erase(&mut master);
+            return Err("BIP85 derives from a master key, but this is a child key".to_string());
+        }
+        let secp = Secp256k1::new();
+        let path = DerivationPath::from_str(WIF_PATH).expect("valid path");
+        let parent = master.derive_priv(&secp, &path).map_err(|e| e.to_string());
+        let fingerprint = master.fingerprint(&secp);
+        erase(&mut master);
+        Ok(Bip85 {
+            parent: parent?,
+            fingerprint,
+            start: 0,
+            chain: None,
+        })
+    }
+
+    /// A master key whose own keys at `path` are searched instead of BIP85
+    /// keys: the last step of `path`, which must be a normal child, is ground
+    /// from its index on. The master key is erased once the parent of the
+    /// path is derived.
+    pub fn at_path(mut master: ExtendedPrivKey, path: &DerivationPath) -> Result<Self, String> {
+        let checked = match master.depth {
+            0 => split_path(path).and_then(|(chain, last)| match last {
+                ChildNumber::Normal { index } => Ok((chain, index)),
+                ChildNumber::Hardened { .. } => {
+                    Err(format!("The last step of {} must not be hardened, to grind wallet addresses", path))
+                }
+            }),
+            _ => Err("Paths start at a master key, but this is a child key".to_string()),
+        };
+        let (chain, start) = match checked {
+            Ok(checked) => checked,
+            Err(e) => {
+                erase(&mut master);
+                return Err(e);
+            }
+        };
+        let secp = Secp256k1::new();
+        let parent = master.derive_priv(&secp, &chain).map_err(|e| e.to_string());
+        let fingerprint = master.fingerprint(&secp);
+        erase(&mut master);
+        Ok(Bip85 {
+            parent: parent?,
+            fingerprint,
+            start,
+            chain: Some(chain),
+        })
+    }
+
+    /// Start grinding at `index` instead of 0, e.g. to continue where an
+    /// earlier search with the same master key stopped
+    pub fn starting_at(mut self, index: u32) -> Result<Self, String> {
+        if index >= INDICES {
+            return Err(format!("BIP85 indices go up to {}", INDICES - 1));
+        }
+        self.start = index;
+        Ok(self)
+    }
+
+    /// The index the search starts at
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// Fingerprint of the master key, which tells wallets apart without
+    /// revealing anything
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    /// The path of the parent of the keys searched, for a search of the
+    /// wallet's own keys
+    pub fn chain(&self) -> Option<&DerivationPath> {
+        self.chain.as_ref()
+    }
+
+    /// The private key of the child at `index`, which must be below
+    /// `INDICES`
+    pub fn key(&self, secp: &Secp256k1<secp256k1::All>, index: u32) -> SecretKey {
+        if self.chain.is_some() {
+            let index = ChildNumber::from_normal_idx(index).expect("index below 2^31");
+            // Only fails for one in about 2^127 indices
+            let mut child = self.parent.ckd_priv(secp, index).expect("valid child key");
+            let key = child.private_key;
+            erase(&mut child);
+            return key;
+        }
+        let index = ChildNumber::from_hardened_idx(index).expect("index below 2^31");
+        // Both steps only fail for one in about 2^127 indices
+        let mut child = self.parent.ckd_priv(secp, index).expect("valid child key");
+        let mut engine = hmac::HmacEngine::<sha512::Hash>::new(b"bip-entropy-from-k");
+        engine.input(&child.private_key.secret_bytes());
+        erase(&mut child);
+        let mut entropy = hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+        let key = SecretKey::from_slice(&entropy[..32]).expect("valid private key");
+        entropy.zeroize();
+        key
+    }
+
+    /// The child at `index`, to report with a match
+    pub fn child(&self, index: u32) -> Bip85Child {
+        Bip85Child {
+            fingerprint: self.fingerprint,
+            index,
+            chain: self.chain.clone(),
+        }
+    }
+}
+
+/// The indices of a running BIP85 search, shared by its threads
+pub struct Bip85Indices {
+    pub bip85: Bip85,
+    next: AtomicU64,
+}
+
+impl Bip85Indices {
+    pub fn new(bip85: Bip85) -> Self {
+        let next = AtomicU64::new(bip85.start.into());
+        Bip85Indices { bip85, next }
+    }
+
+    /// The next `count` indices not handed out yet, fewer once they run out
+    pub fn take(&self, count: usize) -> Range<u32> {
+        let start = self.next.fetch_add(count as u64, Ordering::Relaxed).min(INDICES.into());
+        let end = (start + count as u64).min(INDICES.into());
+        start as u32..end as u32
+    }
+
+    /// Whether every index has been handed out
+    pub fn exhausted(&self) -> bool {
+        self.next.load(Ordering::Relaxed) >= INDICES.into()
+    }
+}
+
+impl std::fmt::Debug for Bip85 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bip85")
+            .field("fingerprint", &self.fingerprint)
+            .field("start", &self.start)
+            .field("chain", &self.chain)
+            .finish()
+    }
+}
+
+impl Drop for Bip85 {
+    fn drop(&mut self) {
+        erase(&mut self.parent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{Network, PrivateKey};
+
+    // The test vector of the WIF application in BIP85
+    const MASTER: &str =
+        "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+
+    #[test]
+    fn wif_application_matches_the_spec() {
+        let bip85 = Bip85::from_xprv(MASTER).unwrap();
+        let key = bip85.key(&Secp256k1::new(), 0);
+        assert_eq!(hex::encode(key.secret_bytes()), "7040bb53104f27367f317558e78a994ada7296c6fde36a364e5baf206e502bb1");
+        assert_eq!(PrivateKey::new(key, Network::Bitcoin).to_wif(), "Kzyv4uF39d4Jrw2W7UryTHwZr1zQVNk4dAFyqE6BuMrMh1Za7uhp");
+        assert_eq!(bip85.child(0).path(), "m/83696968'/2'/0'");
+    }
+}
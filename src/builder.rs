@@ -0,0 +1,104 @@
+// Builder for configuring a search in code
+
+use std::sync::Arc;
+
+use crate::{AddressType, Bip85, CustomMatcher, Error, HashBackend, Multisig, SearchConfig, Seed, VanitySearch};
+
+/// Step-by-step construction of a [`VanitySearch`], validated by `build`
+///
+/// ```no_run
+/// use vanity_address_rust::{AddressType, VanitySearch};
+///
+/// let search = VanitySearch::builder()
+///     .pattern("cafe")
+///     .suffix("777")
+///     .threads(8)
+///     .address_type(AddressType::P2wpkh)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct VanitySearchBuilder {
+    config: SearchConfig,
+}
+
+impl VanitySearchBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pattern the address must start with, after the address type's prefix
+    pub fn pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.config.pattern = pattern.into();
+        self
+    }
+
+    /// Pattern the address must end with
+    pub fn suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.config.suffix = Some(suffix.into());
+        self
+    }
+
+    /// Run on this many threads instead of one per CPU thread
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.config.threads = Some(threads);
+        self
+    }
+
+    /// Step through consecutive keys from a random start
+    pub fn incremental(mut self, incremental: bool) -> Self {
+        self.config.incremental = incremental;
+        self
+    }
+
+    /// Hash160 implementation to use
+    pub fn hash_backend(mut self, hash_backend: HashBackend) -> Self {
+        self.config.hash_backend = hash_backend;
+        self
+    }
+
+    /// Kind of address to search for
+    pub fn address_type(mut self, address_type: AddressType) -> Self {
+        self.config.address_type = address_type;
+        self
+    }
+
+    /// Search for P2WSH addresses of `multisig`, with our key ground; the
+    /// address type must be [`AddressType::P2wsh`]
+    pub fn multisig(mut self, multisig: Multisig) -> Self {
+        self.config.multisig = Some(multisig);
+        self
+    }
+
+    /// Derive the candidates from a master key with BIP85, so matches can be
+    /// recovered from its backup
+    pub fn bip85(mut self, bip85: Bip85) -> Self {
+        self.config.bip85 = Some(bip85);
+        self
+    }
+
+    /// Let every search thread work only `percent` of the time, resting in
+    /// between, e.g. to keep a laptop cool during a long search
+    pub fn max_cpu(mut self, percent: u8) -> Self {
+        self.config.max_cpu = Some(percent);
+        self
+    }
+
+    /// Only report candidates that `matcher` also scores as a match
+    pub fn custom_matcher(mut self, matcher: impl CustomMatcher + 'static) -> Self {
+        self.config.custom_matcher = Some(Arc::new(matcher));
+        self
+    }
+
+    /// Derive every candidate from `seed`, for reproducible tests. Never
+    /// use keys from a seeded search for real funds.
+    pub fn seed(mut self, seed: Seed) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    /// Validate the options and prepare the search
+    pub fn build(self) -> Result<VanitySearch, Error> {
+        VanitySearch::new(self.config)
+    }
+}
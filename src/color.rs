@@ -0,0 +1,76 @@
+// Colored terminal output
+//
+// Matches are printed with the part of the address that matched highlighted,
+// so it's plain to see why an address was picked, most of all when several
+// patterns are searched for at once. Colors are on for terminals unless
+// NO_COLOR is set (https://no-color.org), and --color overrides both. Files
+// and logs never get colors.
+
+use clap::ValueEnum;
+use colored::Colorize;
+use std::io::IsTerminal;
+
+/// When to color the output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// When stdout is a terminal and NO_COLOR is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Turn colors on or off for the rest of the process
+pub fn configure(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && std::io::stdout().is_terminal()
+        }
+    };
+    colored::control::set_override(enabled);
+}
+
+/// `address` with the parts matching `pattern` and `suffix` highlighted. The
+/// pattern follows the prefix of the address (bc1q, bc1p, sp1q: everything up
+/// to the separator and the witness version), or starts right away for a hex
+/// node ID.
+pub fn highlight_address(address: &str, pattern: &str, suffix: Option<&str>) -> String {
+    let start = match address.find('1') {
+        Some(separator) if !address.bytes().all(|b| b.is_ascii_hexdigit()) => separator + 2,
+        _ => 0,
+    };
+    let end = address.len().saturating_sub(suffix.map_or(0, str::len));
+    let matched = address[start..].starts_with(pattern) && address.ends_with(suffix.unwrap_or(""));
+    if !matched || start + pattern.len() > end {
+        return address.to_string();
+    }
+    let pattern_end = start + pattern.len();
+    format!(
+        "{}{}{}{}",
+        &address[..start],
+        emphasize(&address[start..pattern_end]),
+        &address[pattern_end..end],
+        emphasize(&address[end..])
+    )
+}
+
+/// `key` with the first occurrence of `pattern` highlighted
+pub fn highlight_within(key: &str, pattern: &str) -> String {
+    match key.find(pattern) {
+        Some(start) => {
+            let end = start + pattern.len();
+            format!("{}{}{}", &key[..start], emphasize(&key[start..end]), &key[end..])
+        }
+        None => key.to_string(),
+    }
+}
+
+// Without escape codes around nothing
+fn emphasize(text: &str) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    text.green().bold().to_string()
+}
@@ -0,0 +1,148 @@
+// Control socket for a running search
+//
+// `--control-socket PATH` listens on a Unix socket for one command per line
+// and answers each with one line of JSON, so scripts and other programs can
+// manage a search without killing it:
+//
+//   status                       attempts, rate, patterns and whether paused
+//   pause, resume                like SIGUSR1 and SIGUSR2
+//   add-pattern PATTERN[...SUFFIX]  also look for these patterns
+//   stop                         end the search as if it ran out of time
+//
+// Answers are {"ok": true, ...} or {"ok": false, "error": "..."}. Only the
+// owner may connect; nothing sent over the socket includes a private key.
+
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use vanity_address_rust::VanitySearch;
+
+/// The listening socket, removed again when dropped
+pub struct ControlSocket {
+    path: PathBuf,
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Listen on `path` and serve commands for `search` on background threads
+#[cfg(unix)]
+pub fn listen(path: &Path, search: Arc<VanitySearch>) -> Result<ControlSocket, String> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    // A socket left behind by a crashed run can be replaced, one that still
+    // answers belongs to a running search
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("{} is in use by another search", path.display()));
+        }
+        std::fs::remove_file(path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    }
+    let listener = UnixListener::bind(path).map_err(|e| format!("Failed to listen on {}: {}", path.display(), e))?;
+    let socket = ControlSocket { path: path.to_path_buf() };
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("Failed to restrict access to {}: {}", path.display(), e))?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let search = Arc::clone(&search);
+            std::thread::spawn(move || {
+                let Ok(mut writer) = stream.try_clone() else { return };
+                for line in BufReader::new(stream).lines() {
+                    let Ok(line) = line else { return };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    tracing::debug!("Control socket command: {}", line.trim());
+                    let reply = match handle(&search, &line) {
+                        Ok(Value::Object(mut reply)) => {
+                            reply.insert("ok".to_string(), json!(true));
+                            Value::Object(reply)
+                        }
+                        Ok(_) => json!({ "ok": true }),
+                        Err(error) => json!({ "ok": false, "error": error }),
+                    };
+                    if writeln!(writer, "{}", reply).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    Ok(socket)
+}
+
+#[cfg(not(unix))]
+pub fn listen(_path: &Path, _search: Arc<VanitySearch>) -> Result<ControlSocket, String> {
+    Err("Control sockets are not supported on this platform".to_string())
+}
+
+// Run one command, returning the fields of its answer
+fn handle(search: &VanitySearch, line: &str) -> Result<Value, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let argument = words.next();
+    if words.next().is_some() {
+        return Err(format!("Too many arguments for {}", command));
+    }
+
+    match (command, argument) {
+        ("status", None) => Ok(status(search)),
+        ("pause", None) => {
+            search.pause();
+            Ok(json!({ "paused": true }))
+        }
+        ("resume", None) => {
+            search.resume();
+            Ok(json!({ "paused": false }))
+        }
+        ("add-pattern", Some(target)) => {
+            let (pattern, suffix) = match target.split_once("...") {
+                Some((pattern, suffix)) => (pattern, Some(suffix).filter(|s| !s.is_empty())),
+                None => (target, None),
+            };
+            if pattern.is_empty() && suffix.is_none() {
+                return Err("The pattern is empty".to_string());
+            }
+            search.add_pattern(pattern, suffix).map_err(|e| e.to_string())?;
+            Ok(json!({ "patterns": patterns(search) }))
+        }
+        ("add-pattern", None) => Err("Usage: add-pattern PATTERN[...SUFFIX]".to_string()),
+        ("stop", None) => {
+            search.stop();
+            Ok(json!({}))
+        }
+        ("status" | "pause" | "resume" | "stop", Some(_)) => Err(format!("{} takes no arguments", command)),
+        _ => Err(format!(
+            "Unknown command '{}', expected status, pause, resume, add-pattern or stop",
+            command
+        )),
+    }
+}
+
+fn status(search: &VanitySearch) -> Value {
+    let progress = search.progress();
+    let secs = progress.elapsed.as_secs_f64();
+    json!({
+        "attempts": progress.attempts,
+        "elapsed_secs": secs,
+        "rate": if secs > 0.0 { progress.attempts as f64 / secs } else { 0.0 },
+        "expected_attempts": search.expected_attempts(),
+        "threads": search.threads(),
+        "paused": search.is_paused(),
+        "patterns": patterns(search),
+    })
+}
+
+fn patterns(search: &VanitySearch) -> Vec<Value> {
+    search
+        .patterns()
+        .into_iter()
+        .map(|(pattern, suffix)| json!({ "pattern": pattern, "suffix": suffix }))
+        .collect()
+}
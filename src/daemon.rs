@@ -0,0 +1,160 @@
+// Running as a long-lived service
+//
+// `--daemon` detaches from the terminal the classic way: fork, start a new
+// session, fork again so the daemon can never acquire a terminal, and point
+// stdin at /dev/null and stdout and stderr at the log file. It has to happen
+// before any thread starts, as only the forking thread survives a fork. The
+// working directory is kept, so relative paths keep working.
+//
+// Under systemd, the service manager is told about readiness, status and
+// liveness through $NOTIFY_SOCKET (the sd_notify protocol, one datagram per
+// message). Without --daemon, stdout goes to the journal as usual.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::warn;
+
+use vanity_address_rust::VanitySearch;
+
+use crate::reporter::spawn_reporter;
+
+/// Detach from the terminal, logging to `log_file` from now on
+#[cfg(unix)]
+pub fn daemonize(log_file: &Path) -> Result<(), String> {
+    use std::fs::{File, OpenOptions};
+    use std::os::unix::io::AsRawFd;
+
+    // Opened up front, so that a bad path is still reported on the terminal
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .map_err(|e| format!("Failed to open {}: {}", log_file.display(), e))?;
+    let null = File::open("/dev/null").map_err(|e| format!("Failed to open /dev/null: {}", e))?;
+    tracing::info!("Running in the background, logging to {}", log_file.display());
+
+    fork()?;
+    // SAFETY: setsid has no memory safety preconditions
+    if unsafe { libc::setsid() } == -1 {
+        return Err(format!("Failed to start a new session: {}", std::io::Error::last_os_error()));
+    }
+    fork()?;
+
+    for (from, to) in [(null.as_raw_fd(), 0), (log.as_raw_fd(), 1), (log.as_raw_fd(), 2)] {
+        // SAFETY: both descriptors are open
+        if unsafe { libc::dup2(from, to) } == -1 {
+            return Err(format!("Failed to redirect output: {}", std::io::Error::last_os_error()));
+        }
+    }
+    Ok(())
+}
+
+// Continue in a child process, the parent exits
+#[cfg(unix)]
+fn fork() -> Result<(), String> {
+    // SAFETY: called before any other thread exists
+    match unsafe { libc::fork() } {
+        -1 => Err(format!("Failed to fork: {}", std::io::Error::last_os_error())),
+        0 => Ok(()),
+        _ => std::process::exit(crate::exit::SUCCESS),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn daemonize(_log_file: &Path) -> Result<(), String> {
+    Err("--daemon is not supported on this platform".to_string())
+}
+
+/// A file holding the PID of this process, removed again when dropped
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(PidFile { path: path.to_path_buf() })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Send a message such as "READY=1" to the service manager, if this process
+/// runs under one
+pub fn sd_notify(state: &str) {
+    if let Err(e) = send(state) {
+        warn!("failed to notify the service manager: {}", e);
+    }
+}
+
+#[cfg(unix)]
+fn send(state: &str) -> std::io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+    let socket = UnixDatagram::unbound()?;
+    // Names starting with '@' are in Linux's abstract socket namespace
+    #[cfg(target_os = "linux")]
+    if let Some(name) = path.as_encoded_bytes().strip_prefix(b"@") {
+        use std::os::linux::net::SocketAddrExt;
+        let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        return socket.send_to_addr(state.as_bytes(), &address).map(|_| ());
+    }
+    socket.send_to(state.as_bytes(), path).map(|_| ())
+}
+
+#[cfg(not(unix))]
+fn send(_state: &str) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Keep the service manager's status line up to date every
+/// `stats_interval`, and answer its watchdog as long as the search makes
+/// progress, so a stuck search gets restarted. `None` when not running under
+/// a service manager.
+pub fn spawn_notifier(
+    search: Arc<VanitySearch>,
+    stop: Arc<AtomicBool>,
+    stats_interval: Duration,
+) -> Option<JoinHandle<()>> {
+    std::env::var_os("NOTIFY_SOCKET")?;
+    let watchdog = watchdog_interval();
+    // Ping the watchdog twice per timeout, as systemd recommends
+    let interval = watchdog.map_or(stats_interval, |timeout| stats_interval.min(timeout / 2));
+
+    let mut last_attempts = 0;
+    Some(spawn_reporter(stop, interval, move || {
+        let progress = search.progress();
+        let rate = progress.attempts as f64 / progress.elapsed.as_secs_f64().max(1e-9);
+        let mut state = if search.is_paused() {
+            format!("STATUS=Paused after {} attempts", progress.attempts)
+        } else {
+            format!("STATUS=Searching: {} attempts, {:.0} addr/s", progress.attempts, rate)
+        };
+        if watchdog.is_some() && (progress.attempts > last_attempts || search.is_paused()) {
+            state.push_str("\nWATCHDOG=1");
+        }
+        last_attempts = progress.attempts;
+        sd_notify(&state);
+    }))
+}
+
+// $WATCHDOG_USEC, if the watchdog is enabled for this process
+fn watchdog_interval() -> Option<Duration> {
+    let pid = std::env::var("WATCHDOG_PID").ok();
+    if pid.is_some_and(|pid| pid != std::process::id().to_string()) {
+        return None;
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
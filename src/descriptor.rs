@@ -0,0 +1,70 @@
+// Output descriptor checksums (BIP 380)
+
+// Characters allowed in descriptors, in the order that defines the checksum
+const DESCRIPTOR_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// The 8 character checksum that follows a `#` after `descriptor`, or `None`
+/// if it contains characters descriptors can't have
+pub fn descriptor_checksum(descriptor: &str) -> Option<String> {
+    // A BCH code over the descriptor's characters, split into their position
+    // within a group of 32 and the group number
+    let mut c = 1;
+    let mut groups = 0;
+    let mut group_count = 0;
+    for ch in descriptor.chars() {
+        let position = DESCRIPTOR_CHARSET.find(ch)? as u64;
+        c = polymod(c, position & 31);
+        groups = groups * 3 + (position >> 5);
+        group_count += 1;
+        if group_count == 3 {
+            c = polymod(c, groups);
+            groups = 0;
+            group_count = 0;
+        }
+    }
+    if group_count > 0 {
+        c = polymod(c, groups);
+    }
+    for _ in 0..8 {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+
+    Some((0..8).map(|i| CHECKSUM_CHARSET[((c >> (5 * (7 - i))) & 31) as usize] as char).collect())
+}
+
+fn polymod(c: u64, value: u64) -> u64 {
+    const GENERATOR: [u64; 5] = [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd];
+    let top = c >> 35;
+    let mut c = ((c & 0x7ffffffff) << 5) ^ value;
+    for (bit, generator) in GENERATOR.iter().enumerate() {
+        if top >> bit & 1 == 1 {
+            c ^= generator;
+        }
+    }
+    c
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_known_descriptors() {
+        for (descriptor, checksum) in [
+            ("raw(deadbeef)", "89f8spxm"),
+            ("addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)", "02wpgw69"),
+            ("wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)", "8zl0zxma"),
+        ] {
+            assert_eq!(descriptor_checksum(descriptor).as_deref(), Some(checksum), "{}", descriptor);
+        }
+    }
+
+    #[test]
+    fn other_characters_have_no_checksum() {
+        assert_eq!(descriptor_checksum("raw(deadbeef)\n"), None);
+        assert_eq!(descriptor_checksum("wpkh(é)"), None);
+    }
+}
@@ -0,0 +1,299 @@
+// Coordinator/worker mode for spreading one search across several machines
+//
+// The coordinator listens for workers, hands each of them the job and adds
+// up the attempt counts they report. Workers run an ordinary random (or
+// incremental from a random start) search, so their key spaces never overlap
+// in practice and no range bookkeeping is needed. When a worker reports a
+// hit, the coordinator re-derives the address from the private key and, if
+// it checks out, tells every worker to stop.
+//
+// Messages are JSON objects, one per line, over plain TCP. Private keys are
+// sent unencrypted, so only use this on a trusted network or over a tunnel.
+
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use clap::ValueEnum;
+use vanity_address_rust::{AddressType, HashBackend, Match, Progress, SearchConfig, SecretString, VanitySearch};
+
+use crate::exit::CliError;
+use crate::reporter::{format_rates, spawn_reporter, stop_reporter, RateWindow, LONG_WINDOW};
+use crate::scheduling::Threads;
+
+// How often workers report their progress to the coordinator
+const WORKER_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    Hello { threads: usize },
+    Job { pattern: String, suffix: Option<String>, incremental: bool, address_type: String },
+    Stats { attempts: u64 },
+    Found { address: String, private_key: SecretString },
+    Stop,
+}
+
+fn send(stream: &mut TcpStream, message: &Message) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(message).expect("messages always serialize");
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+}
+
+/// Job description handed to every worker
+pub struct Job {
+    pub pattern: String,
+    pub suffix: Option<String>,
+    pub incremental: bool,
+    /// Only types that need no keys besides the ground one: p2wpkh, p2tr
+    /// and node-id
+    pub address_type: AddressType,
+}
+
+// Shared state of the coordinator
+struct Coordinator {
+    job: Job,
+    // Never run, only used to verify hits reported by workers
+    search: VanitySearch,
+    attempts: AtomicU64,
+    workers: AtomicUsize,
+    threads: AtomicUsize,
+    // Write halves of all connected workers, for broadcasting the stop
+    streams: Mutex<Vec<TcpStream>>,
+    found_tx: crossbeam_channel::Sender<(String, SecretString)>,
+}
+
+/// How a coordinated search ended
+pub enum Finish {
+    Found(Box<Match>),
+    TimedOut(Progress),
+}
+
+/// Run the coordinator until a worker finds a verified match, or `timeout`
+/// passes. The attempts reported are the combined attempts of all workers.
+pub fn serve(listen: &str, job: Job, stats_interval: Duration, timeout: Option<Duration>) -> Result<Finish, CliError> {
+    // Workers get no multisig wallet or spend key, so they can only grind
+    // addresses of the ground key alone
+    if matches!(job.address_type, AddressType::P2wsh | AddressType::SilentPayment) {
+        return Err(CliError::InvalidOptions(format!(
+            "Distributed searches support p2wpkh, p2tr and node-id addresses, not {}",
+            job.address_type
+        )));
+    }
+    let search = VanitySearch::new(SearchConfig {
+        pattern: job.pattern.clone(),
+        suffix: job.suffix.clone(),
+        address_type: job.address_type,
+        ..Default::default()
+    })?;
+    let listener = TcpListener::bind(listen).map_err(|e| format!("Failed to listen on {}: {}", listen, e))?;
+    let (found_tx, found_rx) = crossbeam_channel::unbounded();
+
+    let coordinator = Arc::new(Coordinator {
+        job,
+        search,
+        attempts: AtomicU64::new(0),
+        workers: AtomicUsize::new(0),
+        threads: AtomicUsize::new(0),
+        streams: Mutex::new(Vec::new()),
+        found_tx,
+    });
+
+    info!("Coordinator listening on {}", listen);
+
+    {
+        let coordinator = Arc::clone(&coordinator);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let coordinator = Arc::clone(&coordinator);
+                thread::spawn(move || handle_worker(&coordinator, stream));
+            }
+        });
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let started_at = Instant::now();
+    let reporter = {
+        let coordinator = Arc::clone(&coordinator);
+        let mut rates = RateWindow::new(LONG_WINDOW);
+        spawn_reporter(Arc::clone(&stop), stats_interval, move || {
+            let elapsed = started_at.elapsed();
+            let attempts = coordinator.attempts.load(Ordering::Relaxed);
+            rates.record(elapsed, attempts);
+            if elapsed.as_secs() > 0 {
+                info!(
+                    "Workers: {}, Threads: {}, Attempts: {}, Time: {}s, Rate: {}",
+                    coordinator.workers.load(Ordering::Relaxed),
+                    coordinator.threads.load(Ordering::Relaxed),
+                    attempts,
+                    elapsed.as_secs(),
+                    format_rates(&rates, attempts, elapsed)
+                );
+            }
+        })
+    };
+
+    // The coordinator keeps a sender alive, so this only fails on timeout
+    let found = match timeout {
+        Some(timeout) => found_rx.recv_timeout(timeout).ok(),
+        None => found_rx.recv().ok(),
+    };
+    stop_reporter(&stop, reporter);
+
+    for stream in coordinator.streams.lock().unwrap_or_else(PoisonError::into_inner).iter_mut() {
+        let _ = send(stream, &Message::Stop);
+    }
+
+    let attempts = coordinator.attempts.load(Ordering::Relaxed);
+    let elapsed = started_at.elapsed();
+    Ok(match found {
+        Some((address, private_key)) => Finish::Found(Box::new(Match {
+            address,
+            pattern: coordinator.job.pattern.clone(),
+            suffix: coordinator.job.suffix.clone(),
+            private_key,
+            attempts,
+            elapsed,
+            taproot: None,
+            multisig: None,
+            bip85: None,
+            spend_key: None,
+            score: None,
+        })),
+        None => Finish::TimedOut(Progress { attempts, elapsed }),
+    })
+}
+
+fn handle_worker(coordinator: &Coordinator, mut stream: TcpStream) {
+    let peer = stream
+        .peer_addr()
+        .map(|a| a.to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+
+    let job = Message::Job {
+        pattern: coordinator.job.pattern.clone(),
+        suffix: coordinator.job.suffix.clone(),
+        incremental: coordinator.job.incremental,
+        address_type: coordinator.job.address_type.to_string(),
+    };
+    if send(&mut stream, &job).is_err() {
+        return;
+    }
+    if let Ok(writer) = stream.try_clone() {
+        coordinator.streams.lock().unwrap_or_else(PoisonError::into_inner).push(writer);
+    }
+
+    let mut threads = 0;
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { break };
+        match serde_json::from_str::<Message>(&line) {
+            Ok(Message::Hello { threads: n }) => {
+                threads = n;
+                coordinator.workers.fetch_add(1, Ordering::Relaxed);
+                coordinator.threads.fetch_add(n, Ordering::Relaxed);
+                info!("Worker {} joined with {} threads", peer, n);
+            }
+            Ok(Message::Stats { attempts }) => {
+                coordinator.attempts.fetch_add(attempts, Ordering::Relaxed);
+            }
+            Ok(Message::Found { address, private_key }) => {
+                // Never trust a remote hit without re-deriving it ourselves
+                if coordinator.search.verify(&address, &private_key) {
+                    let _ = coordinator.found_tx.send((address, private_key));
+                } else {
+                    warn!("Worker {} reported an invalid match for {}, ignoring it", peer, address);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Worker {} sent a malformed message: {}", peer, e),
+        }
+    }
+
+    if threads > 0 {
+        coordinator.workers.fetch_sub(1, Ordering::Relaxed);
+        coordinator.threads.fetch_sub(threads, Ordering::Relaxed);
+        info!("Worker {} disconnected", peer);
+    }
+}
+
+/// Join a coordinator and search on its behalf until told to stop.
+/// Returns the match if this worker found it.
+pub fn work(connect: &str, hash_backend: HashBackend, threads: Threads) -> Result<Option<Match>, String> {
+    let mut stream = TcpStream::connect(connect).map_err(|e| format!("Failed to connect to {}: {}", connect, e))?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+
+    send(&mut stream, &Message::Hello { threads: threads.count }).map_err(|e| e.to_string())?;
+
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let (pattern, suffix, incremental, address_type) = match serde_json::from_str::<Message>(&line) {
+        Ok(Message::Job { pattern, suffix, incremental, address_type }) => (pattern, suffix, incremental, address_type),
+        _ => return Err("Coordinator did not send a job".to_string()),
+    };
+    let address_type = AddressType::from_str(&address_type, false)
+        .map_err(|_| format!("Coordinator sent an unknown address type '{}'", address_type))?;
+
+    info!("Connected to coordinator {}", connect);
+    crate::print_target(&pattern, suffix.as_deref(), address_type.prefix());
+
+    let search = Arc::new(VanitySearch::new(SearchConfig {
+        pattern,
+        suffix,
+        incremental,
+        hash_backend,
+        address_type,
+        threads: Some(threads.count),
+        thread_start: threads.start,
+        ..Default::default()
+    })?);
+    let stop = Arc::new(AtomicBool::new(false));
+    let writer = Arc::new(Mutex::new(stream));
+
+    // Stop when the coordinator says so or goes away
+    {
+        let search = Arc::clone(&search);
+        thread::spawn(move || {
+            for line in reader.lines() {
+                match line.ok().and_then(|l| serde_json::from_str::<Message>(&l).ok()) {
+                    Some(Message::Stop) | None => break,
+                    Some(_) => {}
+                }
+            }
+            search.stop();
+        });
+    }
+
+    // Report only the attempts made since the previous report
+    let reported = Arc::new(AtomicU64::new(0));
+    let report = {
+        let search = Arc::clone(&search);
+        let writer = Arc::clone(&writer);
+        let reported = Arc::clone(&reported);
+        move || {
+            let attempts = search.attempts();
+            let delta = attempts - reported.swap(attempts, Ordering::Relaxed);
+            let _ = send(&mut writer.lock().unwrap_or_else(PoisonError::into_inner), &Message::Stats { attempts: delta });
+        }
+    };
+    let reporter = spawn_reporter(Arc::clone(&stop), WORKER_REPORT_INTERVAL, report.clone());
+
+    let found = search.run();
+    stop_reporter(&stop, reporter);
+    report();
+    let found = found?;
+
+    if let Some(found) = &found {
+        let message = Message::Found {
+            address: found.address.clone(),
+            private_key: found.private_key.clone(),
+        };
+        send(&mut writer.lock().unwrap_or_else(PoisonError::into_inner), &message).map_err(|e| e.to_string())?;
+    }
+
+    Ok(found)
+}
@@ -0,0 +1,205 @@
+// Entropy sources for the seed of a search
+//
+// Whatever the source, its bytes and any extra entropy from the user are
+// hashed with SHA-256 into the 32 byte seed, which keys the per-thread
+// ChaCha20 generators (see `rng`). Every source is mixed with OS randomness,
+// so a source can only add entropy: even a file that reads the same every
+// time never gives two searches the same keys. The seed's fingerprint
+// identifies it in logs, so the provenance of a key can be audited without
+// revealing it.
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::rand::rngs::OsRng;
+use bitcoin::secp256k1::rand::RngCore;
+use std::fmt;
+use std::io::Read;
+use std::path::PathBuf;
+use std::str::FromStr;
+use zeroize::Zeroize;
+
+use crate::Seed;
+
+// A file source must provide at least a full seed's worth of bytes, and is
+// read up to this many (e.g. from an endless device like /dev/hwrng)
+const MIN_FILE_BYTES: usize = 32;
+const MAX_FILE_BYTES: u64 = 64 * 1024;
+
+// RDSEED reports failure while the CPU's entropy conditioner refills
+#[cfg(target_arch = "x86_64")]
+const RDSEED_RETRIES: usize = 1024;
+
+/// Where the seed of a search comes from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EntropySource {
+    /// The operating system's generator (getrandom)
+    Os,
+    /// OS randomness mixed with the CPU's RDSEED instruction (x86_64 only)
+    Rdseed,
+    /// OS randomness mixed with bytes read from a file or device, such as a
+    /// hardware RNG
+    File(PathBuf),
+}
+
+impl EntropySource {
+    /// Draw a fresh seed from this source, mixing in `extra` entropy, e.g.
+    /// dice rolls, if given
+    pub fn seed(&self, extra: Option<&[u8]>) -> Result<Seed, String> {
+        let mut bytes = match self {
+            EntropySource::Os => os_random()?.to_vec(),
+            EntropySource::Rdseed => {
+                let mut bytes = os_random()?.to_vec();
+                bytes.extend_from_slice(&rdseed()?);
+                bytes
+            }
+            EntropySource::File(path) => {
+                let mut bytes = os_random()?.to_vec();
+                bytes.append(&mut read_file(path)?);
+                bytes
+            }
+        };
+
+        let mut engine = sha256::Hash::engine();
+        engine.input(&bytes);
+        engine.input(extra.unwrap_or_default());
+        bytes.zeroize();
+        Ok(Seed::new(sha256::Hash::from_engine(engine).to_byte_array()))
+    }
+}
+
+impl fmt::Display for EntropySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntropySource::Os => write!(f, "os"),
+            EntropySource::Rdseed => write!(f, "rdseed"),
+            EntropySource::File(path) => write!(f, "file:{}", path.display()),
+        }
+    }
+}
+
+impl FromStr for EntropySource {
+    type Err = String;
+
+    /// Parse `os`, `rdseed` or `file:PATH`
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "os" => Ok(EntropySource::Os),
+            "rdseed" => Ok(EntropySource::Rdseed),
+            _ => match s.strip_prefix("file:") {
+                Some(path) if !path.is_empty() => Ok(EntropySource::File(PathBuf::from(path))),
+                _ => Err(format!("Unknown entropy source '{}', expected os, rdseed or file:PATH", s)),
+            },
+        }
+    }
+}
+
+fn os_random() -> Result<[u8; 32], String> {
+    let mut bytes = [0u8; 32];
+    OsRng
+        .try_fill_bytes(&mut bytes)
+        .map_err(|e| format!("Failed to read OS randomness: {}", e))?;
+    Ok(bytes)
+}
+
+fn read_file(path: &PathBuf) -> Result<Vec<u8>, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut bytes = Vec::new();
+    file.take(MAX_FILE_BYTES)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    if bytes.len() < MIN_FILE_BYTES {
+        let read = bytes.len();
+        bytes.zeroize();
+        return Err(format!(
+            "{} holds only {} bytes, an entropy file needs at least {}",
+            path.display(),
+            read,
+            MIN_FILE_BYTES
+        ));
+    }
+    Ok(bytes)
+}
+
+#[cfg(target_arch = "x86_64")]
+fn rdseed() -> Result<[u8; 32], String> {
+    if !std::is_x86_feature_detected!("rdseed") {
+        return Err("This CPU doesn't support RDSEED".to_string());
+    }
+
+    let mut bytes = [0u8; 32];
+    for chunk in bytes.chunks_exact_mut(8) {
+        // SAFETY: RDSEED support was checked above
+        let value = unsafe { rdseed64() }.ok_or("RDSEED kept failing to deliver entropy")?;
+        chunk.copy_from_slice(&value.to_le_bytes());
+    }
+    Ok(bytes)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "rdseed")]
+unsafe fn rdseed64() -> Option<u64> {
+    for _ in 0..RDSEED_RETRIES {
+        let mut value = 0;
+        if std::arch::x86_64::_rdseed64_step(&mut value) == 1 {
+            return Some(value);
+        }
+        std::hint::spin_loop();
+    }
+    None
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn rdseed() -> Result<[u8; 32], String> {
+    Err("RDSEED is only available on x86_64".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A file in the temporary directory holding `bytes`, removed on drop
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, bytes: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("vanity-entropy-{}-{}", std::process::id(), name));
+            std::fs::write(&path, bytes).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn sources_parse() {
+        assert_eq!("os".parse(), Ok(EntropySource::Os));
+        assert_eq!("rdseed".parse(), Ok(EntropySource::Rdseed));
+        assert_eq!("file:/dev/hwrng".parse(), Ok(EntropySource::File(PathBuf::from("/dev/hwrng"))));
+        for source in ["file:", "file", "OS", "", "hwrng"] {
+            assert!(source.parse::<EntropySource>().is_err(), "{}", source);
+        }
+        let file = EntropySource::File(PathBuf::from("seed.bin"));
+        assert_eq!(file.to_string().parse(), Ok(file));
+    }
+
+    #[test]
+    fn short_files_are_refused() {
+        let file = TempFile::new("short", &[7; MIN_FILE_BYTES - 1]);
+        let error = EntropySource::File(file.0.clone()).seed(None).unwrap_err();
+        assert!(error.contains(&format!("only {} bytes", MIN_FILE_BYTES - 1)), "{}", error);
+        assert!(EntropySource::File(file.0.with_extension("missing")).seed(None).is_err());
+    }
+
+    #[test]
+    fn files_add_to_os_randomness() {
+        let file = TempFile::new("seed", &[7; MIN_FILE_BYTES]);
+        let source = EntropySource::File(file.0.clone());
+        let first = source.seed(None).unwrap();
+        let second = source.seed(None).unwrap();
+        assert_ne!(first.fingerprint(), second.fingerprint());
+    }
+}
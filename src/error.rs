@@ -0,0 +1,36 @@
+// Errors setting up a search
+
+use std::fmt;
+
+/// Why a search could not be set up
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// A pattern has characters that never appear in an address, or is
+    /// longer than the address itself
+    InvalidPattern(String),
+    /// The options contradict each other or what this build and CPU
+    /// support, e.g. zero threads or a missing hash backend
+    InvalidConfig(String),
+    /// The search threads could not be started
+    ThreadPool(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidPattern(message) | Error::InvalidConfig(message) | Error::ThreadPool(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Lets functions that report errors as messages use `?` on search setup
+impl From<Error> for String {
+    fn from(error: Error) -> String {
+        error.to_string()
+    }
+}
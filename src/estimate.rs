@@ -0,0 +1,99 @@
+// Time, energy and cost estimates of a search
+//
+// Every candidate matches with the same small probability, so with E
+// expected attempts per match, a search has found one after n attempts with
+// probability 1 - (1 - 1/E)^n, about 1 - e^(-n/E). Reaching probability p
+// takes E·ln(1/(1 - p)) attempts: 0.69 E for even odds, 3.0 E for 95%.
+//
+// The energy is the power draw given with --watts over that time, which
+// should be measured at the wall: the CPU alone leaves out the rest of the
+// machine.
+
+use clap::Args;
+
+/// Power draw and price of electricity, to turn search times into energy
+/// and cost
+#[derive(Args, Debug, Clone)]
+pub struct Energy {
+    /// Power the machine draws while searching, in watts, to estimate the
+    /// energy a search takes
+    #[clap(long, value_name = "WATTS", value_parser = parse_positive)]
+    pub watts: Option<f64>,
+
+    /// Price of a kWh of electricity, to estimate what that energy costs
+    #[clap(long, value_name = "PRICE", value_parser = parse_positive, requires = "watts")]
+    pub cost_per_kwh: Option<f64>,
+}
+
+/// Chances of a match the estimates are given for
+const PROBABILITIES: [f64; 2] = [0.5, 0.95];
+
+impl Energy {
+    /// Energy, and its cost if the price is known, of `secs` of searching
+    fn describe(&self, secs: f64) -> Option<String> {
+        let kwh = self.watts? * secs / 3.6e6;
+        Some(match self.cost_per_kwh {
+            Some(price) => format!("{}, costing {:.2}", format_energy(kwh), kwh * price),
+            None => format_energy(kwh),
+        })
+    }
+
+    /// Energy and cost of `secs` of searching so far, if --watts was given
+    pub fn used(&self, secs: f64) -> Option<String> {
+        self.describe(secs).map(|energy| format!("Energy used: {}", energy))
+    }
+
+    /// The time, energy and cost to find a match with `expected` attempts
+    /// per match at `rate` attempts per second, for even odds and for 95%
+    pub fn estimates(&self, expected: f64, rate: f64) -> Vec<String> {
+        PROBABILITIES
+            .iter()
+            .map(|&p| {
+                let secs = expected * -(1.0 - p).ln() / rate;
+                let line = format!("{:.0}% chance within {}", p * 100.0, format_time(secs));
+                match self.describe(secs) {
+                    Some(energy) => format!("{} ({})", line, energy),
+                    None => line,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A positive number, for the options above and --rate
+pub fn parse_positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if number > 0.0 && number.is_finite() => Ok(number),
+        _ => Err(format!("'{}' is not a positive number", value)),
+    }
+}
+
+// Seconds, rounded to the largest unit that fits
+pub fn format_time(secs: f64) -> String {
+    const UNITS: &[(f64, &str)] = &[
+        (365.25 * 86400.0, "years"),
+        (86400.0, "days"),
+        (3600.0, "hours"),
+        (60.0, "minutes"),
+    ];
+    for &(unit, name) in UNITS {
+        if secs >= unit {
+            return format!("{:.1} {}", secs / unit, name);
+        }
+    }
+    if secs < 1.0 {
+        return "under a second".to_string();
+    }
+    format!("{:.1} seconds", secs)
+}
+
+// kWh, in Wh below one and in MWh from a thousand
+fn format_energy(kwh: f64) -> String {
+    if kwh < 1.0 {
+        format!("{:.1} Wh", kwh * 1000.0)
+    } else if kwh < 1000.0 {
+        format!("{:.2} kWh", kwh)
+    } else {
+        format!("{:.2} MWh", kwh / 1000.0)
+    }
+}
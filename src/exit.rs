@@ -0,0 +1,74 @@
+// Exit statuses
+//
+// Scripts branch on how a run ended, so the statuses are a documented
+// contract (see "Exit Codes" in the README): keep existing values stable
+// and only ever add new ones.
+
+use std::fmt;
+
+/// A match was found, or a subcommand succeeded
+pub const SUCCESS: i32 = 0;
+/// A runtime error, see `CliError::Failed`
+pub const FAILED: i32 = 1;
+/// The search ran out of --timeout without a match
+pub const TIMED_OUT: i32 = 2;
+pub const INVALID_PATTERN: i32 = 3;
+pub const INVALID_OPTIONS: i32 = 4;
+pub const KEY_MISMATCH: i32 = 5;
+/// The search was stopped through the control socket, or ran out of BIP85
+/// indices or wallet accounts, without a match
+pub const STOPPED: i32 = 6;
+
+/// Why a run failed, which decides its exit status
+#[derive(Debug)]
+pub enum CliError {
+    /// A pattern can never match an address
+    InvalidPattern(String),
+    /// Invalid or contradicting options, or ones this machine doesn't support
+    InvalidOptions(String),
+    /// A key does not control the address it was checked against
+    KeyMismatch(String),
+    /// Anything else that went wrong at runtime: files, network, threads
+    Failed(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::InvalidPattern(_) => INVALID_PATTERN,
+            CliError::InvalidOptions(_) => INVALID_OPTIONS,
+            CliError::KeyMismatch(_) => KEY_MISMATCH,
+            CliError::Failed(_) => FAILED,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::InvalidPattern(message)
+            | CliError::InvalidOptions(message)
+            | CliError::KeyMismatch(message)
+            | CliError::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::Failed(message)
+    }
+}
+
+impl From<vanity_address_rust::Error> for CliError {
+    fn from(error: vanity_address_rust::Error) -> Self {
+        use vanity_address_rust::Error;
+
+        match error {
+            Error::InvalidPattern(message) => CliError::InvalidPattern(message),
+            Error::InvalidConfig(message) => CliError::InvalidOptions(message),
+            Error::ThreadPool(message) => CliError::Failed(message),
+            other => CliError::Failed(other.to_string()),
+        }
+    }
+}
@@ -0,0 +1,375 @@
+// Export found keys in formats that wallets can import
+//
+// Reads the JSON lines written by --log-matches, re-derives every address
+// from its key (and for P2WSH the logged cosigners) so a corrupted record can
+// never be exported, and prints the keys on stdout in the chosen format. The
+// Sparrow format writes a directory per key instead, with everything needed
+// to import it in one go.
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::json;
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use vanity_address_rust::bitcoin::bip32::{DerivationPath, Fingerprint};
+use vanity_address_rust::bitcoin::secp256k1::Secp256k1;
+use vanity_address_rust::{
+    identify_address, parse_cosigner, parse_private_key, AddressType, Bip85Child, Multisig, SecretString,
+};
+use zeroize::Zeroize;
+
+use crate::batch;
+use crate::keys;
+
+/// Wallet format to export keys in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// One `p2wpkh:<WIF>` line per key, for Electrum's "Import Bitcoin
+    /// addresses or private keys"
+    Electrum,
+    /// A JSON array of requests for Bitcoin Core's `importdescriptors`
+    Core,
+    /// A directory per key (see --dir) with a descriptor file for Sparrow's
+    /// "Import Wallet" and the label as BIP329
+    Sparrow,
+}
+
+// The fields of a match log record that exports need
+#[derive(Deserialize)]
+struct Record {
+    address: String,
+    private_key: SecretString,
+    pattern: String,
+    suffix: Option<String>,
+    // Where a BIP85 key, or a key at a path of the wallet, sits in its
+    // master wallet. Logs from before --path have no path.
+    bip85_fingerprint: Option<String>,
+    bip85_index: Option<u32>,
+    bip85_path: Option<String>,
+    // The wallet of a P2WSH match
+    threshold: Option<usize>,
+    cosigners: Option<Vec<String>>,
+}
+
+impl Record {
+    fn multisig(&self) -> Result<Option<Multisig>, String> {
+        let (Some(threshold), Some(cosigners)) = (self.threshold, &self.cosigners) else {
+            return Ok(None);
+        };
+        let cosigners = cosigners.iter().map(|key| parse_cosigner(key)).collect::<Result<Vec<_>, _>>()?;
+        Multisig::new(threshold, &cosigners).map(Some)
+    }
+
+    fn bip85(&self) -> Result<Option<Bip85Child>, String> {
+        let Some(fingerprint) = &self.bip85_fingerprint else {
+            return Ok(None);
+        };
+        let fingerprint =
+            Fingerprint::from_str(fingerprint).map_err(|_| format!("invalid master fingerprint '{}'", fingerprint))?;
+        match (&self.bip85_path, self.bip85_index) {
+            (Some(path), _) => {
+                let path = DerivationPath::from_str(path).map_err(|_| format!("invalid derivation path '{}'", path))?;
+                Bip85Child::at(fingerprint, &path).map(Some)
+            }
+            (None, Some(index)) => Ok(Some(Bip85Child { fingerprint, index, chain: None })),
+            (None, None) => Ok(None),
+        }
+    }
+
+    // "cafe" or "cafe...dead", as the search was given
+    fn label(&self) -> String {
+        batch::label(&self.pattern, self.suffix.as_deref())
+    }
+}
+
+// A key from the log that was checked against its address
+struct Key {
+    address_type: AddressType,
+    multisig: Option<Multisig>,
+    wif: SecretString,
+}
+
+/// Print every key in the match log at `path` in `format`, or write them to
+/// `dir` for formats that need files
+pub fn export(path: &Path, format: ExportFormat, dir: Option<&Path>) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let records = read_records(BufReader::new(file), path)?;
+    if records.is_empty() {
+        return Err(format!("No matches in {}", path.display()));
+    }
+    write_export(&records, path, format, dir, &mut std::io::stdout().lock())
+}
+
+// Write the keys of `records`, read from `path`, to `stdout` in `format`
+fn write_export(
+    records: &[(usize, Record)],
+    path: &Path,
+    format: ExportFormat,
+    dir: Option<&Path>,
+    stdout: &mut impl Write,
+) -> Result<(), String> {
+    let mut requests = Vec::new();
+    for (line, record) in records {
+        let key = key(record).map_err(|e| format!("{}, line {}: {}", path.display(), line, e))?;
+        match format {
+            ExportFormat::Electrum => {
+                let mut entry = format!("{}:{}", electrum_script_type(key.address_type)?, key.wif.as_str());
+                let written = writeln!(stdout, "{}", entry);
+                entry.zeroize();
+                written.map_err(write_error)?;
+            }
+            ExportFormat::Core => {
+                let descriptor = SecretString::from(descriptor(&key)?);
+                requests.push(json!({
+                    "desc": descriptor.as_str(),
+                    "timestamp": "now",
+                    "label": record.pattern,
+                }));
+            }
+            ExportFormat::Sparrow => {
+                let dir = dir.ok_or("The sparrow format needs --dir")?;
+                let bundle = write_bundle(dir, record, &key)
+                    .map_err(|e| format!("{}, line {}: {}", path.display(), line, e))?;
+                writeln!(stdout, "{}", bundle.display()).map_err(write_error)?;
+            }
+        }
+    }
+
+    if format == ExportFormat::Core {
+        let mut output = serde_json::to_string_pretty(&requests).expect("JSON values always serialize");
+        let written = writeln!(stdout, "{}", output);
+        output.zeroize();
+        written.map_err(write_error)?;
+    }
+    Ok(())
+}
+
+// Write the bundle of a key to a new directory in `dir`, named after the
+// address, and return its path:
+//
+// - descriptor.txt: the checksummed descriptor, with the address, label and
+//   (for BIP85 keys) derivation path in comments, for Sparrow's File > Import
+//   Wallet > Output Descriptor
+// - labels.jsonl: the label of the address as BIP329, for Sparrow's Import
+//   Labels
+//
+// Only the owner may read them.
+fn write_bundle(dir: &Path, record: &Record, key: &Key) -> Result<std::path::PathBuf, String> {
+    let bundle = dir.join(&record.address);
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&bundle).map_err(|e| format!("Failed to create {}: {}", bundle.display(), e))?;
+
+    let label = record.label();
+    let mut contents = format!("# Vanity key for '{}'\n", label);
+    contents.push_str(&format!("# First receive address: {}\n", record.address));
+    match record.bip85()? {
+        Some(child) if child.chain.is_some() => contents.push_str(&format!(
+            "# Key {} of master {}, one of the wallet's own addresses\n",
+            child.path(),
+            child.fingerprint
+        )),
+        Some(child) => contents.push_str(&format!(
+            "# BIP85 key {} of master {}, recoverable from its seed words\n",
+            child.path(),
+            child.fingerprint
+        )),
+        None => {}
+    }
+    let descriptor = SecretString::from(descriptor(key)?);
+    contents.push_str(descriptor.as_str());
+    contents.push('\n');
+    let written = write_private(&bundle.join("descriptor.txt"), &contents);
+    contents.zeroize();
+    written?;
+
+    let labels = json!({ "type": "addr", "ref": record.address, "label": label });
+    write_private(&bundle.join("labels.jsonl"), &format!("{}\n", labels))?;
+    Ok(bundle)
+}
+
+fn write_private(path: &Path, contents: &str) -> Result<(), String> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn write_error(e: std::io::Error) -> String {
+    format!("Failed to write the export: {}", e)
+}
+
+// Parse the log, keeping the line number of every record for error messages
+fn read_records(reader: impl BufRead, path: &Path) -> Result<Vec<(usize, Record)>, String> {
+    let mut records = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let mut line = line.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if !line.trim().is_empty() {
+            let record = serde_json::from_str(&line);
+            line.zeroize();
+            let record = record.map_err(|e| format!("{}, line {}: {}", path.display(), index + 1, e))?;
+            records.push((index + 1, record));
+        }
+    }
+    Ok(records)
+}
+
+// The key as WIF, after checking that it really controls the address
+fn key(record: &Record) -> Result<Key, String> {
+    check_exportable(&record.address)?;
+    let multisig = record.multisig()?;
+    let mut private_key = parse_private_key(&record.private_key)?;
+    let identified = match &multisig {
+        Some(multisig) => {
+            let public_key = private_key.inner.public_key(&Secp256k1::new());
+            let address = multisig.address(&public_key, private_key.network);
+            address.eq_ignore_ascii_case(&record.address).then_some(AddressType::P2wsh)
+        }
+        None => identify_address(&private_key, &record.address).map(|(address_type, _)| address_type),
+    };
+    let wif = SecretString::from(private_key.to_wif());
+    private_key.inner.non_secure_erase();
+
+    match identified {
+        Some(address_type) => Ok(Key {
+            address_type,
+            multisig,
+            wif,
+        }),
+        None => Err(format!("the key does not control {}", record.address)),
+    }
+}
+
+// Node IDs and silent payment addresses have no descriptor, and no wallet
+// imports their keys on their own
+fn check_exportable(address: &str) -> Result<(), String> {
+    let address_type = if address.len() == 66 && address.bytes().all(|b| b.is_ascii_hexdigit()) {
+        AddressType::NodeId
+    } else if ["sp1", "tsp1", "sprt1"].iter().any(|hrp| address.to_ascii_lowercase().starts_with(hrp)) {
+        AddressType::SilentPayment
+    } else {
+        return Ok(());
+    };
+    Err(format!(
+        "{} is a {} match, and only p2wpkh, p2tr and p2wsh keys can be exported",
+        address, address_type
+    ))
+}
+
+fn electrum_script_type(address_type: AddressType) -> Result<&'static str, String> {
+    match address_type {
+        AddressType::P2wpkh => Ok("p2wpkh"),
+        other => Err(format!("Electrum can't import {} keys", other)),
+    }
+}
+
+// Output descriptor for the key, with its checksum
+fn descriptor(key: &Key) -> Result<String, String> {
+    keys::descriptor(key.address_type, key.multisig.as_ref(), key.wif.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vanity_address_rust::bitcoin::Network;
+
+    const KEY: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+    // A log record of the key's address of `address_type`
+    fn record(address_type: AddressType) -> (usize, Record) {
+        let private_key = parse_private_key(KEY).unwrap();
+        let address = address_type.address(&private_key, Network::Bitcoin).unwrap();
+        let line = json!({ "address": address, "private_key": KEY, "pattern": "q", "suffix": null });
+        (1, serde_json::from_value(line).unwrap())
+    }
+
+    fn run_export(records: &[(usize, Record)], format: ExportFormat, dir: Option<&Path>) -> Result<String, String> {
+        let mut out = Vec::new();
+        write_export(records, Path::new("matches.jsonl"), format, dir, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    // The address type under which an exported WIF controls the address
+    fn imported(wif: &str, address: &str) -> Option<AddressType> {
+        identify_address(&parse_private_key(wif).unwrap(), address).map(|(address_type, _)| address_type)
+    }
+
+    // The WIF inside a `wpkh(...)` or `rawtr(...)` descriptor, after checking
+    // its checksum
+    fn descriptor_key(descriptor: &str) -> String {
+        let (descriptor, checksum) = descriptor.split_once('#').unwrap();
+        assert_eq!(vanity_address_rust::descriptor_checksum(descriptor).unwrap(), checksum);
+        let (_, key) = descriptor.trim_end_matches(')').split_once('(').unwrap();
+        key.to_string()
+    }
+
+    #[test]
+    fn electrum_keys_import_to_the_address() {
+        let (line, record) = record(AddressType::P2wpkh);
+        let address = record.address.clone();
+        let export = run_export(&[(line, record)], ExportFormat::Electrum, None).unwrap();
+        let wif = export.trim_end().strip_prefix("p2wpkh:").unwrap();
+        assert_eq!(imported(wif, &address), Some(AddressType::P2wpkh));
+    }
+
+    #[test]
+    fn core_descriptors_import_to_the_address() {
+        let records = [record(AddressType::P2wpkh), record(AddressType::P2tr)];
+        let addresses: Vec<String> = records.iter().map(|(_, record)| record.address.clone()).collect();
+        let export = run_export(&records, ExportFormat::Core, None).unwrap();
+        let requests: Vec<serde_json::Value> = serde_json::from_str(&export).unwrap();
+        assert_eq!(requests.len(), 2);
+        for ((request, address), (prefix, address_type)) in
+            requests.iter().zip(&addresses).zip([("wpkh(", AddressType::P2wpkh), ("rawtr(", AddressType::P2tr)])
+        {
+            let descriptor = request["desc"].as_str().unwrap();
+            assert!(descriptor.starts_with(prefix), "{}", descriptor);
+            assert_eq!(imported(&descriptor_key(descriptor), address), Some(address_type));
+            assert_eq!(request["label"], "q");
+        }
+    }
+
+    #[test]
+    fn sparrow_bundles_import_to_the_address() {
+        let dir = std::env::temp_dir().join(format!("vanity-export-{}", std::process::id()));
+        let (line, record) = record(AddressType::P2tr);
+        let address = record.address.clone();
+        let export = run_export(&[(line, record)], ExportFormat::Sparrow, Some(&dir)).unwrap();
+        let bundle = dir.join(&address);
+        assert_eq!(export.trim_end(), bundle.display().to_string());
+
+        let descriptor = std::fs::read_to_string(bundle.join("descriptor.txt")).unwrap();
+        let descriptor = descriptor.lines().find(|line| !line.starts_with('#')).unwrap();
+        assert_eq!(imported(&descriptor_key(descriptor), &address), Some(AddressType::P2tr));
+        let labels: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(bundle.join("labels.jsonl")).unwrap()).unwrap();
+        assert_eq!(labels, json!({ "type": "addr", "ref": address, "label": "q" }));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn node_ids_and_silent_payments_are_refused() {
+        let (line, mut record) = record(AddressType::NodeId);
+        assert!(key(&record).err().unwrap().contains("node-id match"));
+        record.address = format!("sp1q{}", "q".repeat(113));
+        let error = run_export(&[(line, record)], ExportFormat::Core, None).unwrap_err();
+        assert!(error.contains("silent-payment match"), "{}", error);
+    }
+
+    #[test]
+    fn keys_for_other_addresses_are_refused() {
+        let (line, mut record) = record(AddressType::P2wpkh);
+        record.address = record.address.replace("bc1q", "bc1p");
+        let error = run_export(&[(line, record)], ExportFormat::Electrum, None).unwrap_err();
+        assert!(error.contains("does not control"), "{}", error);
+    }
+}
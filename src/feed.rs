@@ -0,0 +1,139 @@
+// Patterns streamed in with --patterns
+//
+// Other programs can hand patterns to a running search, one
+// PATTERN[...SUFFIX] per line on stdin (or in a file or named pipe), with #
+// comments. A thread reads the lines as they arrive; the search starts with
+// the first pattern and takes in the rest between batches, for as long as
+// the input stays open. Once it ends, the search goes on with the patterns it
+// has.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use tracing::warn;
+
+use crate::batch::parse_pattern;
+
+/// A pattern and its optional suffix, as the search takes them
+pub type Pattern = (String, Option<String>);
+
+/// The patterns read so far, and the ones still to come
+pub struct Feed {
+    name: String,
+    lines: Receiver<Result<Pattern, String>>,
+    ended: bool,
+}
+
+impl Feed {
+    /// Start reading patterns from the file at `path`, or from stdin for `-`
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let (name, reader): (String, Box<dyn BufRead + Send>) = if path == Path::new("-") {
+            ("stdin".to_string(), Box::new(BufReader::new(io::stdin())))
+        } else {
+            let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+            (path.display().to_string(), Box::new(BufReader::new(file)))
+        };
+        let (sender, lines) = mpsc::channel();
+        let source = name.clone();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(e) => {
+                        let _ = sender.send(Err(format!("Failed to read patterns from {}: {}", source, e)));
+                        break;
+                    }
+                };
+                // The search is over once nobody listens
+                if let Some(parsed) = parse_line(&line) {
+                    if sender.send(parsed).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Feed { name, lines, ended: false })
+    }
+
+    /// Where the patterns come from: "stdin" or the path
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Wait for the first pattern. Lines that aren't patterns are skipped
+    /// with a warning; `None` if the input ended without any.
+    pub fn first(&mut self) -> Option<Pattern> {
+        while let Ok(line) = self.lines.recv() {
+            match line {
+                Ok(pattern) => return Some(pattern),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        self.ended = true;
+        None
+    }
+
+    /// The patterns that arrived since the last call, without waiting.
+    /// Lines that aren't patterns are skipped with a warning.
+    pub fn arrived(&mut self) -> Vec<Pattern> {
+        let mut patterns = Vec::new();
+        while !self.ended {
+            match self.lines.try_recv() {
+                Ok(Ok(pattern)) => patterns.push(pattern),
+                Ok(Err(e)) => warn!("{}", e),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.ended = true,
+            }
+        }
+        patterns
+    }
+
+    /// Whether the input has ended, as of the last call to `first` or
+    /// `arrived`
+    pub fn has_ended(&self) -> bool {
+        self.ended
+    }
+}
+
+// A line of PATTERN[...SUFFIX], or `None` for blank lines and comments
+fn parse_line(line: &str) -> Option<Result<Pattern, String>> {
+    let line = line.split('#').next().unwrap_or_default().trim();
+    if line.is_empty() {
+        return None;
+    }
+    Some(parse_pattern(line).map_err(|e| format!("Skipping {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(pattern: &str, suffix: Option<&str>) -> Option<Result<Pattern, String>> {
+        Some(Ok((pattern.to_string(), suffix.map(str::to_string))))
+    }
+
+    #[test]
+    fn lines_hold_a_pattern_and_suffix() {
+        assert_eq!(parse_line("cafe"), pattern("cafe", None));
+        assert_eq!(parse_line("  CAFE...Dead  "), pattern("cafe", Some("dead")));
+        assert_eq!(parse_line("...dead # suffix only"), pattern("", Some("dead")));
+        assert_eq!(parse_line("cafe..."), pattern("cafe", Some("")));
+    }
+
+    #[test]
+    fn blank_lines_and_comments_are_skipped() {
+        for line in ["", "   ", "# patterns for today", "  # indented"] {
+            assert_eq!(parse_line(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn lines_without_a_pattern_are_reported() {
+        for line in ["...", " ... # nothing"] {
+            assert!(matches!(parse_line(line), Some(Err(_))), "{:?}", line);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+// C interface for embedding the search in C, C++ or Swift applications
+//
+// A search runs on its own thread from `vanity_search_start` until it finds a
+// match or is cancelled. The caller polls its stats, waits for or polls the
+// result, and finally releases it with `vanity_search_free`. The header is
+// generated by cbindgen into include/vanity_address.h.
+
+use std::ffi::{c_char, CStr};
+use std::ptr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+
+use crate::{Match, SearchConfig, VanitySearch};
+
+/// Opaque handle to a running search
+pub struct VanitySearchHandle {
+    search: Arc<VanitySearch>,
+    thread: Mutex<Option<JoinHandle<Option<Match>>>>,
+    result: Mutex<Option<Match>>,
+}
+
+impl VanitySearchHandle {
+    // Join the search thread if it has finished, or wait for it to if `block`
+    fn collect(&self, block: bool) -> Option<Match> {
+        let mut thread = self.thread.lock().unwrap_or_else(PoisonError::into_inner);
+        if thread.as_ref().is_some_and(|t| block || t.is_finished()) {
+            let found = thread.take().unwrap().join().ok().flatten();
+            *self.result.lock().unwrap_or_else(PoisonError::into_inner) = found;
+        }
+        self.result.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    fn finished(&self) -> bool {
+        self.thread.lock().unwrap_or_else(PoisonError::into_inner).as_ref().is_none_or(|t| t.is_finished())
+    }
+}
+
+/// Progress of a search
+#[repr(C)]
+pub struct VanityStats {
+    /// Candidates checked so far
+    pub attempts: u64,
+    /// Seconds spent searching so far
+    pub elapsed_secs: f64,
+    /// Whether the search has ended, by finding a match or being cancelled
+    pub finished: bool,
+}
+
+/// A matching address and its private key, as NUL terminated strings
+#[repr(C)]
+pub struct VanityResult {
+    pub address: [c_char; 128],
+    /// Hex encoded private key
+    pub private_key: [c_char; 128],
+    /// Candidates checked before the match was found
+    pub attempts: u64,
+    /// Seconds spent searching
+    pub elapsed_secs: f64,
+}
+
+/// Start searching on a background thread.
+///
+/// `suffix` may be NULL. `threads` of 0 uses every available core. Returns
+/// NULL if the options are invalid; if `error` is not NULL it then receives
+/// a message that must be released with `vanity_string_free`.
+///
+/// # Safety
+///
+/// `pattern` and a non-NULL `suffix` must be valid NUL terminated strings,
+/// and a non-NULL `error` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn vanity_search_start(
+    pattern: *const c_char,
+    suffix: *const c_char,
+    threads: u32,
+    incremental: bool,
+    error: *mut *mut c_char,
+) -> *mut VanitySearchHandle {
+    let config = (|| {
+        if pattern.is_null() {
+            return Err("pattern must not be NULL".to_string());
+        }
+        let pattern = CStr::from_ptr(pattern).to_str().map_err(|e| e.to_string())?;
+        let suffix = if suffix.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(suffix).to_str().map_err(|e| e.to_string())?)
+        };
+
+        Ok(SearchConfig {
+            pattern: pattern.to_string(),
+            suffix: suffix.map(str::to_string),
+            threads: (threads > 0).then_some(threads as usize),
+            incremental,
+            ..Default::default()
+        })
+    })();
+
+    match config.and_then(|config| VanitySearch::new(config).map_err(String::from)) {
+        Ok(search) => {
+            let search = Arc::new(search);
+            let thread = {
+                let search = Arc::clone(&search);
+                std::thread::spawn(move || search.run().ok().flatten())
+            };
+            Box::into_raw(Box::new(VanitySearchHandle {
+                search,
+                thread: Mutex::new(Some(thread)),
+                result: Mutex::new(None),
+            }))
+        }
+        Err(e) => {
+            if !error.is_null() {
+                *error = std::ffi::CString::new(e).unwrap_or_default().into_raw();
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Fill `stats` with the current progress of the search.
+///
+/// # Safety
+///
+/// `handle` must come from `vanity_search_start` and not have been freed,
+/// and `stats` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn vanity_search_poll_stats(handle: *const VanitySearchHandle, stats: *mut VanityStats) {
+    let handle = &*handle;
+    *stats = VanityStats {
+        attempts: handle.search.attempts(),
+        elapsed_secs: handle.search.elapsed().as_secs_f64(),
+        finished: handle.finished(),
+    };
+}
+
+/// Ask the search to stop. Returns immediately; the search ends shortly after.
+///
+/// # Safety
+///
+/// `handle` must come from `vanity_search_start` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn vanity_search_cancel(handle: *const VanitySearchHandle) {
+    (*handle).search.stop();
+}
+
+/// Copy the match into `result` if the search found one. With `wait`, block
+/// until the search ends first. Returns false if there is no match (yet),
+/// which is also how a search whose threads couldn't be started ends.
+///
+/// # Safety
+///
+/// `handle` must come from `vanity_search_start` and not have been freed,
+/// and `result` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn vanity_search_result(
+    handle: *const VanitySearchHandle,
+    wait: bool,
+    result: *mut VanityResult,
+) -> bool {
+    let Some(found) = (*handle).collect(wait) else {
+        return false;
+    };
+
+    let result = &mut *result;
+    copy_str(&found.address, &mut result.address);
+    copy_str(&found.private_key, &mut result.private_key);
+    result.attempts = found.attempts;
+    result.elapsed_secs = found.elapsed.as_secs_f64();
+    true
+}
+
+/// Cancel the search if it is still running, wait for it to end and
+/// release the handle. NULL is ignored.
+///
+/// # Safety
+///
+/// `handle` must be NULL or come from `vanity_search_start`, and must not be
+/// used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn vanity_search_free(handle: *mut VanitySearchHandle) {
+    if handle.is_null() {
+        return;
+    }
+    let handle = Box::from_raw(handle);
+    handle.search.stop();
+    handle.collect(true);
+}
+
+/// Release a string returned by this library. NULL is ignored.
+///
+/// # Safety
+///
+/// `string` must be NULL or a string returned by this library, and must not
+/// be used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn vanity_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(std::ffi::CString::from_raw(string));
+    }
+}
+
+// Copy `s` into a fixed-size buffer as a NUL terminated string, truncating
+// if it doesn't fit
+fn copy_str(s: &str, buffer: &mut [c_char]) {
+    let len = s.len().min(buffer.len() - 1);
+    for (dst, src) in buffer.iter_mut().zip(&s.as_bytes()[..len]) {
+        *dst = *src as c_char;
+    }
+    buffer[len] = 0;
+}
@@ -0,0 +1,165 @@
+// gRPC server mode
+//
+// The same job queue as the REST API (see `jobs`), as the `vanity.v1.Vanity`
+// service of proto/vanity.proto, for setups where services talk gRPC:
+//
+//   SubmitJob    queue a search
+//   StreamStats  stats snapshots of a job until it's found or cancelled
+//   CancelJob    cancel a queued or running job
+//   GetResult    the match, once found
+//
+// Watching a job blocks, so every stream is fed from a thread of its own.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response};
+use tracing::info;
+
+use vanity_address_rust::HashBackend;
+
+use crate::jobs::{self, Job, JobRequest, Jobs};
+use crate::keys::KeyFormat;
+use crate::scheduling::Threads;
+
+mod proto {
+    tonic::include_proto!("vanity.v1");
+}
+
+use proto::vanity_server::{Vanity, VanityServer};
+
+// Events a stream can fall behind by before its thread waits for the client
+const STREAM_BUFFER: usize = 16;
+
+fn summary(job: &Job) -> proto::Job {
+    let status = match job.status {
+        jobs::Status::Queued => proto::Status::Queued,
+        jobs::Status::Running => proto::Status::Running,
+        jobs::Status::Found => proto::Status::Found,
+        jobs::Status::Cancelled => proto::Status::Cancelled,
+        jobs::Status::Failed => proto::Status::Failed,
+    };
+    proto::Job {
+        id: job.id,
+        pattern: job.pattern.clone(),
+        suffix: job.suffix.clone(),
+        incremental: job.incremental,
+        status: status.into(),
+        attempts: job.attempts(),
+        elapsed_secs: job.elapsed().as_secs_f64(),
+        rate: job.rate(),
+        error: job.error.clone(),
+    }
+}
+
+fn event(job: &Job) -> proto::JobEvent {
+    proto::JobEvent {
+        job: Some(summary(job)),
+        address: job.result.as_ref().map(|found| found.address.clone()),
+    }
+}
+
+fn not_found(id: u64) -> tonic::Status {
+    tonic::Status::not_found(format!("no job {}", id))
+}
+
+struct Service {
+    jobs: Arc<Jobs>,
+    key_format: KeyFormat,
+}
+
+#[tonic::async_trait]
+impl Vanity for Service {
+    type StreamStatsStream = ReceiverStream<Result<proto::JobEvent, tonic::Status>>;
+
+    async fn submit_job(
+        &self,
+        request: Request<proto::SubmitJobRequest>,
+    ) -> Result<Response<proto::Job>, tonic::Status> {
+        let request = request.into_inner();
+        let id = self
+            .jobs
+            .submit(JobRequest {
+                pattern: request.pattern,
+                suffix: request.suffix,
+                incremental: request.incremental,
+            })
+            .map_err(tonic::Status::invalid_argument)?;
+        self.jobs.get(id, summary).map(Response::new).ok_or_else(|| not_found(id))
+    }
+
+    async fn stream_stats(
+        &self,
+        request: Request<proto::JobId>,
+    ) -> Result<Response<Self::StreamStatsStream>, tonic::Status> {
+        let id = request.into_inner().id;
+        if self.jobs.get(id, |_| ()).is_none() {
+            return Err(not_found(id));
+        }
+
+        let (events_tx, events_rx) = mpsc::channel(STREAM_BUFFER);
+        let jobs = Arc::clone(&self.jobs);
+        // Ends when the job does, or as soon as the client hangs up
+        thread::spawn(move || jobs.watch(id, event, |event| events_tx.blocking_send(Ok(event)).is_ok()));
+        Ok(Response::new(ReceiverStream::new(events_rx)))
+    }
+
+    async fn cancel_job(&self, request: Request<proto::JobId>) -> Result<Response<proto::Job>, tonic::Status> {
+        let id = request.into_inner().id;
+        self.jobs.cancel(id, summary).map(Response::new).ok_or_else(|| not_found(id))
+    }
+
+    async fn get_result(&self, request: Request<proto::JobId>) -> Result<Response<proto::JobResult>, tonic::Status> {
+        let id = request.into_inner().id;
+        let result = self.jobs.get(id, |job| {
+            job.result.as_ref().map(|found| {
+                let fields = self.key_format.json_fields(found);
+                let field = |name: &str| fields.get(name).and_then(|value| value.as_str()).map(str::to_string);
+                proto::JobResult {
+                    address: found.address.clone(),
+                    private_key: field("private_key").unwrap_or_default(),
+                    wif: field("wif"),
+                    descriptor: field("descriptor"),
+                }
+            })
+        });
+        match result {
+            Some(Some(result)) => Ok(Response::new(result)),
+            Some(None) => Err(tonic::Status::failed_precondition(format!("job {} has no result", id))),
+            None => Err(not_found(id)),
+        }
+    }
+}
+
+/// Serve the gRPC service on `listen` until the process is killed. Results
+/// hold their keys in `key_format`. Only loopback addresses are accepted
+/// unless `allow_remote` is set.
+pub fn serve(
+    listen: &str,
+    hash_backend: HashBackend,
+    threads: Threads,
+    stats_interval: Duration,
+    key_format: KeyFormat,
+    allow_remote: bool,
+) -> Result<(), String> {
+    jobs::check_listen(listen, allow_remote)?;
+    let address: SocketAddr = listen.parse().map_err(|e| format!("Invalid address {}: {}", listen, e))?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| format!("Failed to start the gRPC server: {}", e))?;
+    let service = Service {
+        jobs: Jobs::start(hash_backend, threads, stats_interval),
+        key_format,
+    };
+
+    info!("gRPC server listening on {}", listen);
+    info!("Using hash backend: {}", hash_backend);
+
+    runtime
+        .block_on(tonic::transport::Server::builder().add_service(VanityServer::new(service)).serve(address))
+        .map_err(|e| format!("Failed to serve gRPC on {}: {}", listen, e))
+}
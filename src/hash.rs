@@ -0,0 +1,107 @@
+// Hash160 (RIPEMD-160 of SHA-256) and SHA-256 backends
+//
+// The portable backend uses the pure Rust implementation from `bitcoin_hashes`.
+// With the `fast-hash` feature, the accelerated backend uses the RustCrypto
+// `sha2` crate, which dispatches to SHA-NI (x86) or the ARMv8 SHA extensions
+// at runtime when the CPU supports them.
+
+use bitcoin::hashes::{hash160, sha256, Hash};
+use clap::ValueEnum;
+
+/// Which hash160 and SHA-256 implementation to use
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum HashBackend {
+    /// Use the accelerated backend if the CPU supports it
+    #[default]
+    Auto,
+    /// Pure Rust implementation, available everywhere
+    Portable,
+    /// Hardware SHA-256 instructions (requires the `fast-hash` feature)
+    Accelerated,
+}
+
+impl HashBackend {
+    /// Resolve `Auto` to a concrete backend for this machine
+    pub fn resolve(self) -> Result<Self, String> {
+        match self {
+            HashBackend::Auto if accelerated_available() => Ok(HashBackend::Accelerated),
+            HashBackend::Auto => Ok(HashBackend::Portable),
+            HashBackend::Accelerated if !cfg!(feature = "fast-hash") => {
+                Err("The accelerated hash backend requires building with the `fast-hash` feature".to_string())
+            }
+            HashBackend::Accelerated if !accelerated_available() => {
+                Err("This CPU has no hardware SHA-256 support".to_string())
+            }
+            backend => Ok(backend),
+        }
+    }
+
+    /// Hash160 of a serialized compressed public key
+    #[inline]
+    pub fn hash160(self, data: &[u8; 33]) -> [u8; 20] {
+        match self {
+            #[cfg(feature = "fast-hash")]
+            HashBackend::Accelerated => accelerated::hash160(data),
+            _ => hash160::Hash::hash(data).to_byte_array(),
+        }
+    }
+
+    /// SHA-256 of a witness script
+    #[inline]
+    pub fn sha256(self, data: &[u8]) -> [u8; 32] {
+        match self {
+            #[cfg(feature = "fast-hash")]
+            HashBackend::Accelerated => accelerated::sha256(data),
+            _ => sha256::Hash::hash(data).to_byte_array(),
+        }
+    }
+}
+
+impl std::fmt::Display for HashBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            HashBackend::Auto => "auto",
+            HashBackend::Portable => "portable",
+            HashBackend::Accelerated => "accelerated",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Whether the CPU has SHA-256 instructions the `sha2` crate can use
+fn accelerated_available() -> bool {
+    if !cfg!(feature = "fast-hash") {
+        return false;
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        std::is_x86_feature_detected!("sha")
+            && std::is_x86_feature_detected!("sse2")
+            && std::is_x86_feature_detected!("ssse3")
+            && std::is_x86_feature_detected!("sse4.1")
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        std::arch::is_aarch64_feature_detected!("sha2")
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        false
+    }
+}
+
+#[cfg(feature = "fast-hash")]
+mod accelerated {
+    use ripemd::Ripemd160;
+    use sha2::{Digest, Sha256};
+
+    pub fn hash160(data: &[u8; 33]) -> [u8; 20] {
+        let sha = Sha256::digest(data);
+        Ripemd160::digest(sha).into()
+    }
+
+    pub fn sha256(data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+}
@@ -0,0 +1,237 @@
+// Effort across runs
+//
+// A hard pattern can take many sessions to find, and every run's stats start
+// from zero. With --stats-file, the attempts, search time and matches of every
+// run are added to a small JSON file, in total and per pattern, and the
+// combined numbers are shown when a run starts and ends. The file is updated
+// on every stats report as well, so a run that is killed still counts up to
+// its last report. Every candidate is checked against all patterns of a
+// search, so a run's attempts count for each of them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use tracing::info;
+
+use vanity_address_rust::Progress;
+
+use crate::batch;
+
+/// A pattern as it appears in the address: "bc1qcafe" or "bc1qcafe...dead"
+pub fn label(prefix: &str, pattern: &str, suffix: Option<&str>) -> String {
+    format!("{}{}", prefix, batch::label(pattern, suffix))
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+struct Totals {
+    attempts: u64,
+    search_secs: f64,
+    runs: u64,
+    matches: u64,
+}
+
+impl Totals {
+    fn add(&mut self, other: &Totals) {
+        self.attempts += other.attempts;
+        self.search_secs += other.search_secs;
+        self.runs += other.runs;
+        self.matches += other.matches;
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} attempts in {:.0?} over {}, {}",
+            self.attempts,
+            Duration::from_secs_f64(self.search_secs),
+            count(self.runs, "run"),
+            count(self.matches, "match")
+        )
+    }
+}
+
+// "1 run", "2 runs", "1 match", "0 matches"
+fn count(n: u64, noun: &str) -> String {
+    match (n, noun.ends_with("ch")) {
+        (1, _) => format!("1 {}", noun),
+        (_, true) => format!("{} {}es", n, noun),
+        (_, false) => format!("{} {}s", n, noun),
+    }
+}
+
+// The contents of the stats file
+#[derive(Default, Serialize, Deserialize)]
+struct History {
+    #[serde(flatten)]
+    total: Totals,
+    // By pattern as it appears in the address, e.g. "bc1qcafe...dead"
+    #[serde(default)]
+    patterns: BTreeMap<String, Totals>,
+}
+
+/// The stats file and this run's part in it
+pub struct Session {
+    path: PathBuf,
+    // The file as it was before this run
+    before: History,
+    // Matches of this run by pattern, for each pattern searched
+    matches: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Session {
+    /// Read the stats file at `path`, if there is one yet, for a run that
+    /// searches for `patterns`
+    pub fn load(path: &Path, patterns: Vec<String>) -> Result<Self, String> {
+        let before = match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("Invalid stats file {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => History::default(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        Ok(Session {
+            path: path.to_path_buf(),
+            before,
+            matches: Mutex::new(patterns.into_iter().map(|pattern| (pattern, 0)).collect()),
+        })
+    }
+
+    /// Log the effort of earlier runs
+    pub fn report_start(&self) {
+        if self.before.total.runs == 0 {
+            info!("No earlier runs in {}", self.path.display());
+            return;
+        }
+        info!("Earlier runs: {}", self.before.total.describe());
+        let matches = self.matches.lock().unwrap_or_else(PoisonError::into_inner);
+        for pattern in matches.keys() {
+            if let Some(totals) = self.before.patterns.get(pattern) {
+                info!("  {}: {}", pattern, totals.describe());
+            }
+        }
+    }
+
+    /// Count a match of this run, by pattern as it appears in the address
+    pub fn record_match(&self, pattern: &str) {
+        let mut matches = self.matches.lock().unwrap_or_else(PoisonError::into_inner);
+        *matches.entry(pattern.to_string()).or_default() += 1;
+    }
+
+    /// Write the earlier runs plus this one, up to `progress`, to the file
+    pub fn save(&self, progress: Progress) -> Result<(), String> {
+        let history = self.combined(progress);
+        let json = serde_json::to_string_pretty(&history).expect("JSON values always serialize");
+        // Through a temporary file, so a crash never leaves half a file
+        let mut temporary = self.path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, json + "\n")
+            .and_then(|_| std::fs::rename(&temporary, &self.path))
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+
+    /// Log the effort of all runs, this one included
+    pub fn report_end(&self, progress: Progress) {
+        let history = self.combined(progress);
+        info!("All runs: {}", history.total.describe());
+        let matches = self.matches.lock().unwrap_or_else(PoisonError::into_inner);
+        for pattern in matches.keys() {
+            if let Some(totals) = history.patterns.get(pattern) {
+                info!("  {}: {}", pattern, totals.describe());
+            }
+        }
+    }
+
+    fn combined(&self, progress: Progress) -> History {
+        let mut history = History {
+            total: self.before.total,
+            patterns: self.before.patterns.clone(),
+        };
+        let matches = self.matches.lock().unwrap_or_else(PoisonError::into_inner);
+        let run = Totals {
+            attempts: progress.attempts,
+            search_secs: progress.elapsed.as_secs_f64(),
+            runs: 1,
+            matches: matches.values().sum(),
+        };
+        history.total.add(&run);
+        for (pattern, &found) in matches.iter() {
+            history.patterns.entry(pattern.clone()).or_default().add(&Totals { matches: found, ..run });
+        }
+        history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A stats file path in the temporary directory, removed on drop
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("vanity-history-{}-{}.json", std::process::id(), name));
+            let _ = std::fs::remove_file(&path);
+            TempPath(path)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn progress(attempts: u64, secs: u64) -> Progress {
+        Progress { attempts, elapsed: Duration::from_secs(secs) }
+    }
+
+    fn patterns() -> Vec<String> {
+        vec![label("bc1q", "cafe", None), label("bc1q", "", Some("dead"))]
+    }
+
+    #[test]
+    fn labels_show_the_address() {
+        assert_eq!(label("bc1q", "cafe", Some("dead")), "bc1qcafe...dead");
+        assert_eq!(label("bc1p", "", Some("dead")), "bc1p...dead");
+    }
+
+    #[test]
+    fn runs_add_up() {
+        let path = TempPath::new("runs");
+
+        let first = Session::load(&path.0, patterns()).unwrap();
+        assert_eq!(first.before.total.runs, 0);
+        first.record_match("bc1qcafe");
+        // Saving again during a run replaces its earlier report
+        first.save(progress(500, 5)).unwrap();
+        first.save(progress(1000, 10)).unwrap();
+
+        let second = Session::load(&path.0, vec![label("bc1q", "cafe", None)]).unwrap();
+        assert_eq!(second.before.total.runs, 1);
+        assert_eq!(second.before.total.attempts, 1000);
+        assert_eq!(second.before.total.matches, 1);
+        assert_eq!(second.before.patterns["bc1q...dead"].matches, 0);
+        second.save(progress(3000, 20)).unwrap();
+
+        let after = Session::load(&path.0, Vec::new()).unwrap().before;
+        assert_eq!(after.total.runs, 2);
+        assert_eq!(after.total.attempts, 4000);
+        assert_eq!(after.total.search_secs, 30.0);
+        let cafe = after.patterns["bc1qcafe"];
+        assert_eq!((cafe.runs, cafe.attempts, cafe.matches), (2, 4000, 1));
+        let dead = after.patterns["bc1q...dead"];
+        assert_eq!((dead.runs, dead.attempts, dead.matches), (1, 1000, 0));
+
+        let mut temporary = path.0.as_os_str().to_owned();
+        temporary.push(".tmp");
+        assert!(!Path::new(&temporary).exists());
+    }
+
+    #[test]
+    fn invalid_files_are_refused() {
+        let path = TempPath::new("invalid");
+        std::fs::write(&path.0, "not json").unwrap();
+        let error = Session::load(&path.0, patterns()).err().unwrap();
+        assert!(error.starts_with("Invalid stats file"), "{}", error);
+    }
+}
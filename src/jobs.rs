@@ -0,0 +1,271 @@
+// Job queue of the server modes
+//
+// The REST API (--serve) and the gRPC service (--grpc) both take searches as
+// jobs, which run one at a time on all search threads, in submission order.
+// Every job keeps its own stats and stop flag, so it can be polled and
+// cancelled independently, and watched until it finishes.
+//
+// Neither server has authentication, and both hand out private keys, so they
+// only listen on loopback addresses unless --allow-remote is given.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::ToSocketAddrs;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use vanity_address_rust::{HashBackend, Match, SearchConfig, VanitySearch};
+
+use crate::scheduling::Threads;
+
+/// A search to queue
+#[derive(Deserialize)]
+pub struct JobRequest {
+    #[serde(default)]
+    pub pattern: String,
+    pub suffix: Option<String>,
+    #[serde(default)]
+    pub incremental: bool,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Queued,
+    Running,
+    Found,
+    Cancelled,
+    /// The search threads couldn't be started
+    Failed,
+}
+
+impl Status {
+    /// Whether the job is still waiting for or running its search
+    pub fn is_active(self) -> bool {
+        matches!(self, Status::Queued | Status::Running)
+    }
+}
+
+pub struct Job {
+    pub id: u64,
+    pub pattern: String,
+    pub suffix: Option<String>,
+    pub incremental: bool,
+    pub status: Status,
+    pub result: Option<Match>,
+    /// Why the job failed
+    pub error: Option<String>,
+    search: Arc<VanitySearch>,
+    // Watchers waiting for the job to finish
+    subscribers: Vec<crossbeam_channel::Sender<()>>,
+}
+
+impl Job {
+    pub fn attempts(&self) -> u64 {
+        self.search.attempts()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.search.elapsed()
+    }
+
+    /// Addresses per second so far
+    pub fn rate(&self) -> f64 {
+        let elapsed = self.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            self.attempts() as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    // Wake up every watcher of a job that just finished
+    fn finish(&mut self) {
+        for subscriber in self.subscribers.drain(..) {
+            let _ = subscriber.send(());
+        }
+    }
+}
+
+pub struct Jobs {
+    jobs: Mutex<BTreeMap<u64, Job>>,
+    queue: crossbeam_channel::Sender<u64>,
+    hash_backend: HashBackend,
+    threads: Threads,
+    stats_interval: Duration,
+}
+
+impl Jobs {
+    /// An empty queue, with a thread that runs its jobs as they come in.
+    /// Watchers get a snapshot every `stats_interval`.
+    pub fn start(hash_backend: HashBackend, threads: Threads, stats_interval: Duration) -> Arc<Self> {
+        let (queue, jobs_rx) = crossbeam_channel::unbounded();
+        let jobs = Arc::new(Jobs {
+            jobs: Mutex::new(BTreeMap::new()),
+            queue,
+            hash_backend,
+            threads,
+            stats_interval,
+        });
+
+        let runner = Arc::clone(&jobs);
+        thread::spawn(move || {
+            for id in jobs_rx {
+                runner.run(id);
+            }
+        });
+        jobs
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<u64, Job>> {
+        self.jobs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Queue a search and return its id
+    pub fn submit(&self, request: JobRequest) -> Result<u64, String> {
+        let pattern = request.pattern.to_lowercase();
+        let suffix = request.suffix.map(|s| s.to_lowercase());
+        let search = VanitySearch::new(SearchConfig {
+            pattern: pattern.clone(),
+            suffix: suffix.clone(),
+            incremental: request.incremental,
+            hash_backend: self.hash_backend,
+            threads: Some(self.threads.count),
+            thread_start: self.threads.start.clone(),
+            ..Default::default()
+        })
+        .map_err(|e| e.to_string())?;
+
+        let mut jobs = self.lock();
+        let id = jobs.keys().next_back().map_or(1, |id| id + 1);
+        jobs.insert(
+            id,
+            Job {
+                id,
+                pattern,
+                suffix,
+                incremental: request.incremental,
+                status: Status::Queued,
+                result: None,
+                error: None,
+                search: Arc::new(search),
+                subscribers: Vec::new(),
+            },
+        );
+        let _ = self.queue.send(id);
+        Ok(id)
+    }
+
+    /// Look at job `id`, if there is one
+    pub fn get<T>(&self, id: u64, f: impl FnOnce(&Job) -> T) -> Option<T> {
+        self.lock().get(&id).map(f)
+    }
+
+    /// Look at every job, in submission order
+    pub fn all<T>(&self, f: impl FnMut(&Job) -> T) -> Vec<T> {
+        self.lock().values().map(f).collect()
+    }
+
+    /// Cancel job `id` if it's queued or running, and look at it after
+    pub fn cancel<T>(&self, id: u64, f: impl FnOnce(&Job) -> T) -> Option<T> {
+        let mut jobs = self.lock();
+        let job = jobs.get_mut(&id)?;
+        if job.status.is_active() {
+            job.status = Status::Cancelled;
+            job.search.stop();
+            job.finish();
+        }
+        Some(f(job))
+    }
+
+    /// Send a snapshot of job `id`, made by `event`, every stats interval and
+    /// the moment the job finishes, until it has finished or `send` returns
+    /// false. Blocks all the while; false if there is no such job.
+    pub fn watch<E>(&self, id: u64, event: impl Fn(&Job) -> E, mut send: impl FnMut(E) -> bool) -> bool {
+        let (finished_tx, finished_rx) = crossbeam_channel::bounded(1);
+        match self.lock().get_mut(&id) {
+            Some(job) if job.status.is_active() => job.subscribers.push(finished_tx),
+            Some(_) => {}
+            None => return false,
+        }
+
+        loop {
+            let (event, done) = {
+                let jobs = self.lock();
+                let Some(job) = jobs.get(&id) else { break };
+                (event(job), !job.status.is_active())
+            };
+            if !send(event) || done {
+                break;
+            }
+            // Either the job finishes, or it's time for the next snapshot
+            let _ = finished_rx.recv_timeout(self.stats_interval);
+        }
+        true
+    }
+
+    fn run(&self, id: u64) {
+        let search = {
+            let mut jobs = self.lock();
+            let Some(job) = jobs.get_mut(&id) else { return };
+            if job.status == Status::Cancelled {
+                return;
+            }
+            job.status = Status::Running;
+            Arc::clone(&job.search)
+        };
+
+        let found = search.run();
+
+        let mut jobs = self.lock();
+        if let Some(job) = jobs.get_mut(&id) {
+            match found {
+                Ok(Some(found)) => {
+                    job.status = Status::Found;
+                    job.result = Some(found);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    job.status = Status::Failed;
+                    job.error = Some(e.to_string());
+                }
+            }
+            job.finish();
+        }
+    }
+}
+
+/// Refuse to serve on `listen` if it resolves to anything but loopback
+/// addresses, unless `allow_remote` is set
+pub fn check_listen(listen: &str, allow_remote: bool) -> Result<(), String> {
+    if allow_remote {
+        return Ok(());
+    }
+    let addresses = listen.to_socket_addrs().map_err(|e| format!("Invalid address {}: {}", listen, e))?;
+    let mut addresses = addresses.peekable();
+    if addresses.peek().is_some() && addresses.all(|address| address.ip().is_loopback()) {
+        return Ok(());
+    }
+    Err(format!(
+        "{} is reachable from other machines, which could fetch the private keys the server finds; \
+         listen on 127.0.0.1 or ::1, or pass --allow-remote behind an authenticating proxy",
+        listen
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_loopback_listens_by_default() {
+        assert!(check_listen("127.0.0.1:8080", false).is_ok());
+        assert!(check_listen("[::1]:8080", false).is_ok());
+        assert!(check_listen("0.0.0.0:8080", false).is_err());
+        assert!(check_listen("[::]:8080", false).is_err());
+        assert!(check_listen("192.168.1.2:8080", false).is_err());
+        assert!(check_listen("0.0.0.0:8080", true).is_ok());
+        assert!(check_listen("no port", false).is_err());
+    }
+}
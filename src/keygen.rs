@@ -0,0 +1,230 @@
+// Candidate key generation for the worker threads
+//
+// Keys are produced in fixed-size batches so that the elliptic curve work,
+// the hashing and the matching each run as a tight loop over one buffer. The
+// hot loop only ever needs the public keys; the secret key of a candidate is
+// reconstructed on demand once it matches. Secret keys held by a generator
+// live in a `SecretBuf`, so they are wiped when it is dropped.
+
+use bitcoin::secp256k1::{self, PublicKey, Scalar, Secp256k1, SecretKey};
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::bip85::{Bip85Child, Bip85Indices};
+use crate::rng::KeyRng;
+use crate::secret::SecretBuf;
+
+/// Number of candidates derived per batch
+pub const BATCH_SIZE: usize = 256;
+
+/// Source of candidate keys for a single worker thread
+pub enum KeyGenerator {
+    /// Every candidate is a fresh random key (one scalar multiplication each)
+    Random {
+        rng: KeyRng,
+        secret_keys: SecretBuf<SecretKey>,
+    },
+    /// Random starting key, then k+1, k+2, ... with the public keys derived
+    /// by point addition, which is far cheaper than a scalar multiplication
+    Incremental {
+        rng: KeyRng,
+        // A single key
+        base: SecretBuf<SecretKey>,
+        // Offset of the first key of the current batch from `base`
+        batch_offset: u64,
+        // Public key of the first key of the next batch, if still valid
+        next_start: Option<PublicKey>,
+        // 1G, 2G, ..., BATCH_SIZE*G
+        multiples: Vec<PublicKey>,
+    },
+    /// The WIF children of a BIP85 master key, at indices shared with the
+    /// other threads of the search
+    Bip85 {
+        indices: Arc<Bip85Indices>,
+        secret_keys: SecretBuf<SecretKey>,
+        // Indices of the current batch
+        batch_indices: Range<u32>,
+    },
+}
+
+impl KeyGenerator {
+    pub fn new(secp: &Secp256k1<secp256k1::All>, incremental: bool, mut rng: KeyRng) -> Self {
+        let mut secret_key = SecretKey::new(&mut rng);
+
+        if !incremental {
+            let secret_keys = SecretBuf::new(secret_key, BATCH_SIZE);
+            secret_key.non_secure_erase();
+            return KeyGenerator::Random { rng, secret_keys };
+        }
+
+        let multiples = (1..=BATCH_SIZE as u64)
+            .map(|i| PublicKey::from_secret_key(secp, &offset_key(i)))
+            .collect();
+
+        let base = SecretBuf::new(secret_key, 1);
+        secret_key.non_secure_erase();
+
+        KeyGenerator::Incremental {
+            rng,
+            base,
+            batch_offset: 0,
+            next_start: None,
+            multiples,
+        }
+    }
+
+    /// A generator of the BIP85 children at `indices`
+    pub fn bip85(indices: Arc<Bip85Indices>) -> Self {
+        KeyGenerator::Bip85 {
+            indices,
+            secret_keys: SecretBuf::new(offset_key(1), BATCH_SIZE),
+            batch_indices: 0..0,
+        }
+    }
+
+    /// Allocate a batch buffer to pass to `fill`
+    pub fn batch(&self, secp: &Secp256k1<secp256k1::All>) -> Vec<PublicKey> {
+        vec![PublicKey::from_secret_key(secp, &offset_key(1)); BATCH_SIZE]
+    }
+
+    /// Replace the contents of `batch` with the next candidates, returning
+    /// how many there are: the whole batch, unless a BIP85 search is running
+    /// out of indices
+    pub fn fill(&mut self, secp: &Secp256k1<secp256k1::All>, batch: &mut [PublicKey]) -> usize {
+        match self {
+            KeyGenerator::Random { rng, secret_keys } => {
+                for (secret_key, public_key) in secret_keys.iter_mut().zip(batch.iter_mut()) {
+                    *secret_key = SecretKey::new(rng);
+                    *public_key = PublicKey::from_secret_key(secp, secret_key);
+                }
+            }
+            KeyGenerator::Bip85 { indices, secret_keys, batch_indices } => {
+                *batch_indices = indices.take(BATCH_SIZE);
+                for ((index, secret_key), public_key) in batch_indices.clone().zip(secret_keys.iter_mut()).zip(batch.iter_mut()) {
+                    *secret_key = indices.bip85.key(secp, index);
+                    *public_key = PublicKey::from_secret_key(secp, secret_key);
+                }
+                return batch_indices.len();
+            }
+            KeyGenerator::Incremental { rng, base, batch_offset, next_start, multiples } => loop {
+                let start = match next_start.take() {
+                    Some(start) => {
+                        *batch_offset += BATCH_SIZE as u64;
+                        start
+                    }
+                    // First call, or the previous batch ran into the end of
+                    // the curve order: jump to a new random start
+                    None => {
+                        base[0] = SecretKey::new(rng);
+                        *batch_offset = 0;
+                        PublicKey::from_secret_key(secp, &base[0])
+                    }
+                };
+
+                batch[0] = start;
+                let mut valid = true;
+                for (public_key, multiple) in batch[1..].iter_mut().zip(multiples.iter()) {
+                    match start.combine(multiple) {
+                        Ok(next) => *public_key = next,
+                        Err(_) => valid = false,
+                    }
+                }
+
+                if valid {
+                    *next_start = start.combine(&multiples[BATCH_SIZE - 1]).ok();
+                    break;
+                }
+            },
+        }
+        BATCH_SIZE
+    }
+
+    /// For an incremental generator, the public key it started from and the
+    /// offset of the candidate at `index` in the batch last passed to `fill`
+    pub fn offset(&self, secp: &Secp256k1<secp256k1::All>, index: usize) -> Option<(PublicKey, u64)> {
+        match self {
+            KeyGenerator::Random { .. } | KeyGenerator::Bip85 { .. } => None,
+            KeyGenerator::Incremental { base, batch_offset, .. } => {
+                Some((PublicKey::from_secret_key(secp, &base[0]), batch_offset + index as u64))
+            }
+        }
+    }
+
+    /// For a BIP85 generator, the child of the candidate at `index` in the
+    /// batch last passed to `fill`
+    pub fn bip85_child(&self, index: usize) -> Option<Bip85Child> {
+        match self {
+            KeyGenerator::Bip85 { indices, batch_indices, .. } => {
+                Some(indices.bip85.child(batch_indices.start + index as u32))
+            }
+            _ => None,
+        }
+    }
+
+    /// Secret key of the candidate at `index` in the batch last passed to `fill`
+    pub fn secret_key(&self, index: usize) -> SecretKey {
+        match self {
+            KeyGenerator::Random { secret_keys, .. } | KeyGenerator::Bip85 { secret_keys, .. } => secret_keys[index],
+            KeyGenerator::Incremental { base, batch_offset, .. } => {
+                let offset = batch_offset + index as u64;
+                if offset == 0 {
+                    return base[0];
+                }
+                // The public key at this offset was a valid point, so base + offset
+                // cannot have wrapped to zero
+                base[0].add_tweak(&Scalar::from(offset_key(offset))).expect("offset key is valid")
+            }
+        }
+    }
+}
+
+// The secret key with value `offset`, which must be non-zero
+fn offset_key(offset: u64) -> SecretKey {
+    let mut bytes = [0u8; 32];
+    bytes[24..].copy_from_slice(&offset.to_be_bytes());
+    SecretKey::from_slice(&bytes).expect("non-zero offset")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Seed;
+
+    // Every candidate's public key must be its secret key times G, or a
+    // match would report a key that doesn't control the address
+    fn check_batches(incremental: bool) {
+        let secp = Secp256k1::new();
+        let seed = Seed::new([7; 32]);
+        let mut keys = KeyGenerator::new(&secp, incremental, KeyRng::new(Some(&seed), 0));
+        let mut batch = keys.batch(&secp);
+        for _ in 0..3 {
+            assert_eq!(keys.fill(&secp, &mut batch), BATCH_SIZE);
+            for (index, public_key) in batch.iter().enumerate() {
+                assert_eq!(PublicKey::from_secret_key(&secp, &keys.secret_key(index)), *public_key);
+            }
+        }
+    }
+
+    #[test]
+    fn random_keys_match_their_public_keys() {
+        check_batches(false);
+    }
+
+    #[test]
+    fn incremental_keys_match_their_public_keys() {
+        check_batches(true);
+    }
+
+    #[test]
+    fn incremental_batches_are_consecutive() {
+        let secp = Secp256k1::new();
+        let mut keys = KeyGenerator::new(&secp, true, KeyRng::new(None, 0));
+        let mut batch = keys.batch(&secp);
+        keys.fill(&secp, &mut batch);
+        let last = keys.secret_key(BATCH_SIZE - 1);
+        keys.fill(&secp, &mut batch);
+        let next = last.add_tweak(&Scalar::from(offset_key(1))).unwrap();
+        assert_eq!(keys.secret_key(0), next);
+        assert_eq!(keys.offset(&secp, 0).map(|(_, offset)| offset), Some(BATCH_SIZE as u64));
+    }
+}
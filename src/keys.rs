@@ -0,0 +1,143 @@
+// Encodings of found private keys
+//
+// A search finds keys as hex. --key-format picks the encoding every sink
+// gets instead: the terminal, --output, --log-matches, webhooks and the job
+// results of the servers. Wallets mostly want WIF, or an output descriptor
+// with the key in it, which says the address type too.
+
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+
+use vanity_address_rust::{descriptor_checksum, identify_address, parse_private_key, AddressType, Match, Multisig};
+use vanity_address_rust::{SecretString, SpendKey};
+
+/// Encoding of the private keys of matches
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum KeyFormat {
+    /// 64 hex digits, as the search finds them
+    #[default]
+    Hex,
+    /// Wallet import format, for a compressed mainnet key
+    Wif,
+    /// Hex, with the WIF next to it
+    Both,
+    /// An output descriptor with the key as WIF, ready for
+    /// `importdescriptors` (P2WPKH, taproot and P2WSH only)
+    Descriptor,
+}
+
+impl KeyFormat {
+    /// Reject formats that keys of `address_type` can't be written in
+    pub fn check(self, address_type: AddressType) -> Result<(), String> {
+        match (self, address_type) {
+            (KeyFormat::Descriptor, AddressType::NodeId | AddressType::SilentPayment) => {
+                Err(format!("There is no output descriptor for {} keys; use another --key-format", address_type))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The private key of `found` in this format, as labeled lines for the
+    /// terminal and the output file
+    pub fn key_lines(self, found: &Match) -> SecretString {
+        let label = match found.spend_key {
+            Some(_) => "Scan key:   ",
+            None => "Private key:",
+        };
+        let mut lines = self.lines(label, &found.private_key, || descriptor_of(found));
+        if let Some(spend_key) = &found.spend_key {
+            let spend_lines = self.lines("Spend key:  ", &spend_key.private_key, unreachable_descriptor);
+            lines = SecretString::from(format!("{}{}", lines.as_str(), spend_lines.as_str()));
+        }
+        lines
+    }
+
+    // `hex` as lines of this format, the first one labeled `label`
+    fn lines(self, label: &str, hex: &str, descriptor: impl FnOnce() -> Result<String, String>) -> SecretString {
+        let text = match self {
+            KeyFormat::Hex => format!("{} {}\n", label, hex),
+            KeyFormat::Wif => format!("{} {}\n", label, wif(hex).as_str()),
+            KeyFormat::Both => format!("{} {}\nWIF:         {}\n", label, hex, wif(hex).as_str()),
+            KeyFormat::Descriptor => {
+                let descriptor = SecretString::from(descriptor().expect("descriptor formats are checked up front"));
+                format!("Descriptor:  {}\n", descriptor.as_str())
+            }
+        };
+        SecretString::from(text)
+    }
+
+    /// The private keys of `found` in this format, as the fields of a JSON
+    /// record: `private_key` in hex or as WIF, next to `wif` or
+    /// `descriptor` for the formats with more than the one encoding
+    pub fn json_fields(self, found: &Match) -> Map<String, Value> {
+        let mut fields = Map::new();
+        let primary = |hex: &str| match self {
+            KeyFormat::Hex | KeyFormat::Both => json!(hex),
+            KeyFormat::Wif | KeyFormat::Descriptor => json!(wif(hex).as_str()),
+        };
+        fields.insert("private_key".to_string(), primary(&found.private_key));
+        match self {
+            KeyFormat::Both => {
+                fields.insert("wif".to_string(), json!(wif(&found.private_key).as_str()));
+            }
+            KeyFormat::Descriptor => {
+                let descriptor = descriptor_of(found).expect("descriptor formats are checked up front");
+                fields.insert("descriptor".to_string(), json!(SecretString::from(descriptor).as_str()));
+            }
+            KeyFormat::Hex | KeyFormat::Wif => {}
+        }
+        if let Some(SpendKey { private_key, .. }) = &found.spend_key {
+            fields.insert("spend_key".to_string(), primary(private_key));
+        }
+        fields
+    }
+
+    /// Whether the lines of this format already include the WIF
+    pub fn shows_wif(self) -> bool {
+        self != KeyFormat::Hex
+    }
+}
+
+/// The WIF of a hex encoded private key of a match
+pub fn wif(hex: &str) -> SecretString {
+    let mut private_key = parse_private_key(hex).expect("matches hold valid keys");
+    let wif = SecretString::from(private_key.to_wif());
+    private_key.inner.non_secure_erase();
+    wif
+}
+
+/// Output descriptor for a key of `address_type` given as WIF, with its
+/// checksum
+pub fn descriptor(address_type: AddressType, multisig: Option<&Multisig>, wif: &str) -> Result<String, String> {
+    let mut descriptor = match (address_type, multisig) {
+        (AddressType::P2wpkh, _) => format!("wpkh({})", wif),
+        // The output key is the key itself, without a BIP 341 tweak
+        (AddressType::P2tr, _) => format!("rawtr({})", wif),
+        (AddressType::P2wsh, Some(multisig)) => return Ok(multisig.descriptor(wif)),
+        (other, _) => return Err(format!("No descriptor for {} keys", other)),
+    };
+    let checksum = descriptor_checksum(&descriptor).expect("WIF only uses descriptor characters");
+    descriptor.push('#');
+    descriptor.push_str(&checksum);
+    Ok(descriptor)
+}
+
+// The descriptor of a match, for the address type its key controls
+fn descriptor_of(found: &Match) -> Result<String, String> {
+    let wif = wif(&found.private_key);
+    if let Some(multisig) = &found.multisig {
+        return descriptor(AddressType::P2wsh, Some(multisig), &wif);
+    }
+    let mut private_key = parse_private_key(&found.private_key)?;
+    let identified = identify_address(&private_key, &found.address);
+    private_key.inner.non_secure_erase();
+    match identified {
+        Some((address_type, _)) => descriptor(address_type, None, &wif),
+        None => Err(format!("the key does not control {}", found.address)),
+    }
+}
+
+// Spend keys only come with silent payments, which have no descriptor
+fn unreachable_descriptor() -> Result<String, String> {
+    Err("No descriptor for a spend key".to_string())
+}
@@ -0,0 +1,685 @@
+//! Bitcoin vanity address search for bc1q (P2WPKH and multisig P2WSH) and
+//! bc1p (P2TR) addresses
+//!
+//! Configure a search with [`VanitySearch::builder`] (or a [`SearchConfig`]),
+//! then run it with [`VanitySearch::run`], which blocks until a matching
+//! address is found or the search is stopped from another thread.
+//!
+//! ```no_run
+//! use vanity_address_rust::VanitySearch;
+//!
+//! let search = VanitySearch::builder()
+//!     .pattern("c0ffee")
+//!     .incremental(true)
+//!     .build()
+//!     .unwrap();
+//!
+//! if let Some(found) = search.run().unwrap() {
+//!     println!("{} {}", found.address, found.private_key.as_str());
+//! }
+//! ```
+//!
+//! To keep searching after the first match, or to stop on a condition of
+//! your own, use [`VanitySearch::run_with`], which hands every match and
+//! periodic progress to a callback. With the `tokio` feature,
+//! `VanitySearch::spawn` runs the search in the background of an async
+//! application and returns an awaitable handle.
+
+mod address;
+mod bip85;
+mod builder;
+mod descriptor;
+mod entropy;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod hash;
+mod keygen;
+mod master;
+mod matcher;
+mod multisig;
+mod pause;
+mod plugin;
+mod rng;
+mod scheduler;
+mod search;
+mod secret;
+#[cfg(feature = "tokio")]
+mod spawn;
+mod stats;
+mod throttle;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "parallel")]
+mod xpub;
+
+#[cfg(feature = "parallel")]
+use crossbeam_channel::RecvTimeoutError;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use address::{identify_address, parse_private_key, AddressType};
+pub use bip85::{Bip85, Bip85Child};
+pub use bitcoin;
+pub use builder::VanitySearchBuilder;
+pub use descriptor::descriptor_checksum;
+pub use entropy::EntropySource;
+pub use error::Error;
+pub use hash::HashBackend;
+pub use master::{master_key_from_mnemonic, master_key_from_xprv};
+pub use multisig::{parse_cosigner, Multisig};
+pub use plugin::CustomMatcher;
+#[cfg(feature = "plugins")]
+pub use plugin::WasmMatcher;
+pub use rng::Seed;
+pub use scheduler::ThreadStart;
+pub use secret::{lock_memory, SecretString};
+#[cfg(feature = "tokio")]
+pub use spawn::SearchHandle;
+#[cfg(feature = "parallel")]
+pub use xpub::{XpubFormat, XpubMatch, XpubSearch};
+
+use bip85::Bip85Indices;
+use matcher::{Matcher, Patterns};
+use scheduler::Scheduler;
+use search::{key_fingerprint, Found, Search};
+
+/// What to search for and how
+#[derive(Clone, Debug, Default)]
+pub struct SearchConfig {
+    /// Pattern the address must start with, after the bc1q prefix
+    pub pattern: String,
+    /// Pattern the address must end with
+    pub suffix: Option<String>,
+    /// Number of threads, or `None` for one per CPU thread of the machine
+    pub threads: Option<usize>,
+    /// Called on every search thread as it starts, e.g. to pin it to a core
+    pub thread_start: Option<ThreadStart>,
+    /// Step through consecutive keys from a random start instead of
+    /// generating every key from scratch
+    pub incremental: bool,
+    /// Hash160 implementation to use
+    pub hash_backend: HashBackend,
+    /// Kind of address to search for
+    pub address_type: AddressType,
+    /// Derive every candidate from this seed instead of the OS random
+    /// number generator, making the search reproducible. For tests only:
+    /// anyone who knows the seed can recreate the keys.
+    pub seed: Option<Seed>,
+    /// The multisig wallet of a [`AddressType::P2wsh`] search, in which
+    /// only our key is ground
+    pub multisig: Option<Multisig>,
+    /// Derive every candidate from this master key with BIP85 instead of
+    /// generating random keys, so matches can be recovered from its backup
+    pub bip85: Option<Bip85>,
+    /// Percentage of the time each search thread may spend working, 1 to
+    /// 100, resting in between, or `None` for no limit
+    pub max_cpu: Option<u8>,
+    /// Match logic of its own, which every candidate matching the patterns
+    /// must also pass. Expected attempts and benchmarks leave it out.
+    pub custom_matcher: Option<Arc<dyn CustomMatcher>>,
+}
+
+/// A matching address and its private key
+#[derive(Clone, Debug)]
+pub struct Match {
+    pub address: String,
+    /// The prefix pattern the address matched
+    pub pattern: String,
+    /// The suffix pattern the address matched, if any
+    pub suffix: Option<String>,
+    /// Hex encoded private key, wiped from memory when dropped. The scan
+    /// key, for [`AddressType::SilentPayment`].
+    pub private_key: SecretString,
+    /// Candidates checked before the match was found
+    pub attempts: u64,
+    /// Time spent searching
+    pub elapsed: Duration,
+    /// How a taproot output key was ground, for [`AddressType::P2tr`]
+    pub taproot: Option<TaprootTweak>,
+    /// The multisig wallet, for [`AddressType::P2wsh`]
+    pub multisig: Option<Multisig>,
+    /// Where the key sits in its master wallet, for BIP85 searches
+    pub bip85: Option<Bip85Child>,
+    /// The other key of the address, for [`AddressType::SilentPayment`]
+    pub spend_key: Option<SpendKey>,
+    /// The score [`SearchConfig::custom_matcher`] gave the address
+    pub score: Option<u32>,
+}
+
+/// The spend key of a silent payment address, whose scan key is the
+/// match's private key
+///
+/// Every search thread keeps its spend key for the whole search, so matches
+/// found by the same thread share it.
+#[derive(Clone, Debug)]
+pub struct SpendKey {
+    /// Hex encoded private spend key, wiped from memory when dropped
+    pub private_key: SecretString,
+    /// Compressed public spend key, in hex
+    pub public_key: String,
+}
+
+/// A taproot output key as a fixed internal key plus a ground tweak
+///
+/// The output key is `internal_key + tweak*G`, so the match's private key is
+/// the internal private key plus the tweak. The tweak is a plain scalar, not
+/// a BIP 341 script commitment: spend the output with the private key alone
+/// (e.g. a `rawtr()` descriptor).
+#[derive(Clone, Debug)]
+pub struct TaprootTweak {
+    /// X-only internal public key, in hex
+    pub internal_key: String,
+    /// 32 byte big-endian scalar added to the internal key, in hex
+    pub tweak: String,
+}
+
+impl Match {
+    /// Short public identifier of the key (BIP32-style: the first 4 bytes
+    /// of the hash160 of the public key), for telling keys apart without
+    /// revealing them
+    pub fn fingerprint(&self) -> String {
+        key_fingerprint(&self.private_key).expect("matches hold valid keys")
+    }
+}
+
+/// Snapshot of a running search
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    /// Candidates checked so far
+    pub attempts: u64,
+    /// Time spent searching so far
+    pub elapsed: Duration,
+}
+
+/// What [`VanitySearch::run_with`] reports to its callback
+#[derive(Clone, Debug)]
+// Events are few and short-lived, boxing matches isn't worth the noise
+#[allow(clippy::large_enum_variant)]
+pub enum Event {
+    /// Sent every stats interval while the search is running
+    Progress(Progress),
+    /// Sent for every matching address
+    Match(Match),
+}
+
+/// Throughput measured by [`benchmark`]
+#[derive(Clone, Debug)]
+pub struct Benchmark {
+    /// The hash backend measured, with `Auto` resolved
+    pub hash_backend: HashBackend,
+    /// Candidates checked in total
+    pub attempts: u64,
+    /// Candidates checked by each thread
+    pub thread_attempts: Vec<u64>,
+    /// Time spent searching
+    pub elapsed: Duration,
+}
+
+impl Benchmark {
+    /// Candidates checked per second, in total
+    pub fn rate(&self) -> f64 {
+        self.attempts as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Candidates checked per second by each thread
+    pub fn thread_rates(&self) -> Vec<f64> {
+        let secs = self.elapsed.as_secs_f64();
+        self.thread_attempts.iter().map(|&attempts| attempts as f64 / secs).collect()
+    }
+}
+
+/// Run the full key generation, hashing and matching pipeline for
+/// `duration` and report its throughput. The pattern of `config` is replaced
+/// by one that can never match (the address of an all-zero hash160, or a
+/// node ID whose x coordinate 0 isn't on the curve), so every candidate
+/// costs the same as in a real search.
+pub fn benchmark(config: SearchConfig, duration: Duration) -> Result<Benchmark, Error> {
+    let pattern = match config.address_type {
+        AddressType::NodeId => format!("02{}", "0".repeat(64)),
+        _ => "q".repeat(32),
+    };
+    let search = VanitySearch::new(SearchConfig {
+        pattern,
+        suffix: None,
+        ..config
+    })?;
+
+    // With nothing to find, the first progress event arrives after `duration`
+    search.run_with(duration, |_| ControlFlow::Break(()))?;
+
+    Ok(Benchmark {
+        hash_backend: search.hash_backend(),
+        attempts: search.attempts(),
+        thread_attempts: search.thread_attempts(),
+        elapsed: search.elapsed(),
+    })
+}
+
+/// A configured search, which can be shared between threads to poll its
+/// progress or stop it while `run` is blocking
+pub struct VanitySearch {
+    pattern: String,
+    suffix: Option<String>,
+    address_type: AddressType,
+    search: Search,
+    scheduler: Scheduler,
+}
+
+impl VanitySearch {
+    /// Start configuring a search
+    pub fn builder() -> VanitySearchBuilder {
+        VanitySearchBuilder::new()
+    }
+
+    /// Validate the configuration and prepare the search
+    pub fn new(config: SearchConfig) -> Result<Self, Error> {
+        if config.threads == Some(0) {
+            return Err(Error::InvalidConfig("The number of threads must be at least 1".to_string()));
+        }
+        let max_cpu = config.max_cpu.unwrap_or(100);
+        check_max_cpu(max_cpu)?;
+
+        let pattern = config.pattern.to_lowercase();
+        let suffix = config.suffix.map(|s| s.to_lowercase());
+        check_not_empty(&pattern, suffix.as_deref(), config.custom_matcher.is_some())?;
+        let matcher = Matcher::new(&pattern, suffix.as_deref(), config.address_type).map_err(Error::InvalidPattern)?;
+        let hash_backend = config.hash_backend.resolve().map_err(Error::InvalidConfig)?;
+        match (config.address_type, &config.multisig) {
+            (AddressType::P2wsh, None) => {
+                return Err(Error::InvalidConfig(
+                    "A P2WSH search needs the cosigner keys and threshold of its wallet".to_string(),
+                ))
+            }
+            (AddressType::P2wsh, Some(_)) | (_, None) => {}
+            (address_type, Some(_)) => {
+                return Err(Error::InvalidConfig(format!("Cosigner keys don't apply to {} searches", address_type)))
+            }
+        }
+
+        if config.bip85.is_some() {
+            if config.incremental {
+                return Err(Error::InvalidConfig(
+                    "BIP85 keys are derived one at a time and can't be stepped through incrementally".to_string(),
+                ));
+            }
+            if config.address_type == AddressType::P2tr {
+                return Err(Error::InvalidConfig(
+                    "Taproot searches grind a tweak, which BIP85 can't derive".to_string(),
+                ));
+            }
+            if config.address_type == AddressType::SilentPayment {
+                return Err(Error::InvalidConfig(
+                    "Silent payment searches draw a random spend key, which BIP85 can't recover".to_string(),
+                ));
+            }
+            if config.seed.is_some() {
+                return Err(Error::InvalidConfig("BIP85 searches take their keys from the master key, not a seed".to_string()));
+            }
+        }
+
+        #[cfg(feature = "parallel")]
+        let threads = config.threads.unwrap_or_else(Scheduler::available_threads);
+        #[cfg(not(feature = "parallel"))]
+        let threads = match config.threads {
+            Some(threads) if threads > 1 => {
+                return Err(Error::InvalidConfig(
+                    "Searching on multiple threads requires the `parallel` feature".to_string(),
+                ))
+            }
+            _ => 1,
+        };
+
+        Ok(VanitySearch {
+            pattern,
+            suffix,
+            address_type: config.address_type,
+            search: Search {
+                patterns: Patterns::new(matcher),
+                // Taproot always grinds the tweak of a fixed internal key
+                incremental: config.incremental || config.address_type == AddressType::P2tr,
+                hash_backend,
+                address_type: config.address_type,
+                seed: config.seed,
+                multisig: config.multisig,
+                bip85: config.bip85.map(|bip85| Arc::new(Bip85Indices::new(bip85))),
+                custom_matcher: config.custom_matcher,
+            },
+            scheduler: Scheduler::new(threads, config.thread_start, max_cpu),
+        })
+    }
+
+    /// Search until a match is found or `stop` is called. Returns `None`
+    /// if the search was stopped; a stopped search stays stopped. Fails with
+    /// `Error::ThreadPool` if the search threads can't be started.
+    ///
+    /// Every match is re-derived from its private key before it's reported.
+    /// A match that fails this check can only come from a bug in the search,
+    /// so it panics rather than hand out a key that may not control the
+    /// address.
+    pub fn run(&self) -> Result<Option<Match>, Error> {
+        let mut found = None;
+        self.drive(None, |event| {
+            if let Event::Match(m) = event {
+                found = Some(m);
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        })?;
+        Ok(found)
+    }
+
+    /// Search until the callback returns `ControlFlow::Break` or `stop` is
+    /// called, passing it every match and a progress snapshot every
+    /// `stats_interval`. The callback runs on the calling thread, never on
+    /// the search threads, so it may block briefly without slowing them down.
+    /// Matches are checked, and panic if they fail, and thread failures are
+    /// returned, as with `run`.
+    pub fn run_with<F>(&self, stats_interval: Duration, on_event: F) -> Result<(), Error>
+    where
+        F: FnMut(Event) -> ControlFlow<()>,
+    {
+        self.drive(Some(stats_interval), on_event)
+    }
+
+    // Run the search threads in the background and feed their matches, and
+    // progress if an interval is given, to `on_event` until it breaks or the
+    // search is stopped
+    #[cfg(feature = "parallel")]
+    fn drive<F>(&self, stats_interval: Option<Duration>, mut on_event: F) -> Result<(), Error>
+    where
+        F: FnMut(Event) -> ControlFlow<()>,
+    {
+        let (found_tx, found_rx) = crossbeam_channel::unbounded::<Found>();
+
+        std::thread::scope(|scope| {
+            // The search threads drop the sender when they exit, which ends
+            // the loop below
+            let threads = scope.spawn(move || self.search.run(&self.scheduler, &found_tx));
+
+            loop {
+                let received = match stats_interval {
+                    Some(interval) => found_rx.recv_timeout(interval),
+                    None => found_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                let event = match received {
+                    Ok(found) => Event::Match(self.to_match(found)),
+                    Err(RecvTimeoutError::Timeout) => Event::Progress(self.progress()),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+
+                if on_event(event).is_break() {
+                    self.stop();
+                    break;
+                }
+            }
+            threads.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+
+    // Without threads to spare, search on the calling thread and check for
+    // matches and due progress between batches
+    #[cfg(not(feature = "parallel"))]
+    fn drive<F>(&self, stats_interval: Option<Duration>, mut on_event: F) -> Result<(), Error>
+    where
+        F: FnMut(Event) -> ControlFlow<()>,
+    {
+        self.scheduler.stats.start();
+        let mut worker = search::Worker::new(&self.search, 0);
+        let mut last_progress = self.elapsed();
+
+        self.scheduler.work(0, || {
+            let mut found = Vec::new();
+            let checked = worker.step(&self.search, |f| found.push(f));
+            let mut events: Vec<Event> = found.into_iter().map(|f| Event::Match(self.to_match(f))).collect();
+            if let Some(interval) = stats_interval {
+                if self.elapsed() - last_progress >= interval {
+                    last_progress = self.elapsed();
+                    events.push(Event::Progress(self.progress()));
+                }
+            }
+            for event in events {
+                if on_event(event).is_break() {
+                    self.stop();
+                    break;
+                }
+            }
+            checked
+        });
+
+        self.scheduler.stats.finish();
+        Ok(())
+    }
+
+    // Every match is checked before it's reported, so a bug in the search
+    // code can never hand out a key that doesn't control the address
+    fn to_match(&self, found: Found) -> Match {
+        if let Err(e) = self.search.check(&found) {
+            self.stop();
+            panic!("Refusing to report a match that fails its re-derivation check: {}", e);
+        }
+        Match {
+            address: found.address,
+            pattern: found.pattern,
+            suffix: found.suffix,
+            private_key: found.private_key,
+            attempts: self.attempts(),
+            elapsed: self.elapsed(),
+            taproot: found.taproot,
+            multisig: self.search.multisig.clone(),
+            bip85: found.bip85,
+            spend_key: found.spend_key,
+            score: found.score,
+        }
+    }
+
+    // A single worker on the calling thread, for callers that schedule the
+    // batches themselves
+    #[cfg(feature = "wasm")]
+    fn worker(&self) -> search::Worker {
+        self.scheduler.stats.start();
+        search::Worker::new(&self.search, 0)
+    }
+
+    // Check one batch with `worker`, returning the first match in it
+    #[cfg(feature = "wasm")]
+    fn step(&self, worker: &mut search::Worker) -> Option<Match> {
+        let mut found = None;
+        let checked = worker.step(&self.search, |f| {
+            found.get_or_insert(f);
+        });
+        self.scheduler.stats.increment(0, checked as u64);
+        found.map(|f| self.to_match(f))
+    }
+
+    /// Ask a running search to stop, or prevent it from starting. A paused
+    /// search stops without being resumed.
+    pub fn stop(&self) {
+        self.scheduler.stop();
+    }
+
+    /// Park the search threads after their current batch until `resume` is
+    /// called, keeping the search state. The elapsed time stands still while
+    /// paused, so rates and timeouts only count time spent searching.
+    pub fn pause(&self) {
+        if self.scheduler.pause.set(true) {
+            self.scheduler.stats.pause();
+        }
+    }
+
+    /// Continue a paused search
+    pub fn resume(&self) {
+        if self.scheduler.pause.set(false) {
+            self.scheduler.stats.resume();
+        }
+    }
+
+    /// Whether the search is paused
+    pub fn is_paused(&self) -> bool {
+        self.scheduler.pause.is_paused()
+    }
+
+    /// Let every search thread spend only `percent` of its time working,
+    /// 1 to 100, from its next batch on
+    pub fn set_max_cpu(&self, percent: u8) -> Result<(), Error> {
+        check_max_cpu(percent)?;
+        self.scheduler.throttle.set(percent);
+        Ok(())
+    }
+
+    /// Percentage of the time the search threads may spend working
+    pub fn max_cpu(&self) -> u8 {
+        self.scheduler.throttle.percent()
+    }
+
+    /// Candidates checked so far
+    pub fn attempts(&self) -> u64 {
+        self.scheduler.stats.attempts()
+    }
+
+    /// Time spent searching so far
+    pub fn elapsed(&self) -> Duration {
+        self.scheduler.stats.elapsed()
+    }
+
+    /// Expected number of candidates to check per match, of any pattern
+    pub fn expected_attempts(&self) -> f64 {
+        self.search.patterns.difficulty()
+    }
+
+    /// Candidates checked so far by each search thread
+    pub fn thread_attempts(&self) -> Vec<u64> {
+        self.scheduler.stats.thread_attempts()
+    }
+
+    /// Attempts and elapsed time so far
+    pub fn progress(&self) -> Progress {
+        Progress {
+            attempts: self.attempts(),
+            elapsed: self.elapsed(),
+        }
+    }
+
+    /// The normalized prefix pattern the search was created with
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The normalized suffix pattern the search was created with, if any
+    pub fn suffix(&self) -> Option<&str> {
+        self.suffix.as_deref()
+    }
+
+    /// Also look for addresses matching these patterns, from the next batch
+    /// on. Matches report which patterns they matched.
+    pub fn add_pattern(&self, pattern: &str, suffix: Option<&str>) -> Result<(), Error> {
+        let suffix = suffix.map(str::to_lowercase);
+        check_not_empty(pattern, suffix.as_deref(), self.search.custom_matcher.is_some())?;
+        let matcher =
+            Matcher::new(&pattern.to_lowercase(), suffix.as_deref(), self.address_type).map_err(Error::InvalidPattern)?;
+        self.search.patterns.add(matcher).map_err(Error::InvalidConfig)
+    }
+
+    /// Stop looking for addresses matching these patterns, from the next
+    /// batch on. Matches already found in the current batch are still
+    /// reported. The last pattern of a search can't be removed.
+    pub fn remove_pattern(&self, pattern: &str, suffix: Option<&str>) -> Result<(), Error> {
+        let suffix = suffix.map(str::to_lowercase);
+        self.search.patterns.remove(&pattern.to_lowercase(), suffix.as_deref()).map_err(Error::InvalidConfig)
+    }
+
+    /// Search for exactly these (pattern, suffix) pairs from the next batch
+    /// on, all swapped in at once. Nothing changes if any of them is invalid.
+    pub fn set_patterns(&self, patterns: &[(String, Option<String>)]) -> Result<(), Error> {
+        for (pattern, suffix) in patterns {
+            check_not_empty(pattern, suffix.as_deref(), self.search.custom_matcher.is_some())?;
+        }
+        let matchers = patterns
+            .iter()
+            .map(|(pattern, suffix)| {
+                let suffix = suffix.as_deref().map(str::to_lowercase);
+                Matcher::new(&pattern.to_lowercase(), suffix.as_deref(), self.address_type)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Error::InvalidPattern)?;
+        self.search.patterns.replace(matchers).map_err(Error::InvalidConfig)
+    }
+
+    /// Every (pattern, suffix) pair searched for, the one the search was
+    /// created with first
+    pub fn patterns(&self) -> Vec<(String, Option<String>)> {
+        let (_, matchers) = self.search.patterns.snapshot();
+        matchers.matchers().iter().map(|m| (m.pattern().to_string(), m.suffix().map(str::to_string))).collect()
+    }
+
+    /// Number of threads the search runs on
+    pub fn threads(&self) -> usize {
+        self.scheduler.workers()
+    }
+
+    /// Kind of address being searched for
+    pub fn address_type(&self) -> AddressType {
+        self.address_type
+    }
+
+    /// The multisig wallet of a P2WSH search
+    pub fn multisig(&self) -> Option<&Multisig> {
+        self.search.multisig.as_ref()
+    }
+
+    /// The master key of a BIP85 search
+    pub fn bip85(&self) -> Option<&Bip85> {
+        self.search.bip85.as_ref().map(|indices| &indices.bip85)
+    }
+
+    /// Whether a BIP85 search has checked every child index, which ends it
+    pub fn is_exhausted(&self) -> bool {
+        self.search.bip85.as_ref().is_some_and(|indices| indices.exhausted())
+    }
+
+    /// The hash backend in use, with `Auto` resolved
+    pub fn hash_backend(&self) -> HashBackend {
+        self.search.hash_backend
+    }
+
+    /// Check that `private_key` controls `address` and that the address
+    /// matches this search's patterns, e.g. for a match found elsewhere
+    pub fn verify(&self, address: &str, private_key: &str) -> bool {
+        self.search.address_for_private_key(private_key).as_deref() == Some(address)
+            && self.search.patterns.find_address(address).is_some()
+    }
+}
+
+// Without a pattern, a suffix or a custom matcher, every candidate would be
+// a match
+fn check_not_empty(pattern: &str, suffix: Option<&str>, custom_matcher: bool) -> Result<(), Error> {
+    if pattern.is_empty() && suffix.is_none_or(str::is_empty) && !custom_matcher {
+        return Err(Error::InvalidPattern("A search needs a pattern, a suffix or a custom matcher".to_string()));
+    }
+    Ok(())
+}
+
+fn check_max_cpu(percent: u8) -> Result<(), Error> {
+    if !(1..=100).contains(&percent) {
+        return Err(Error::InvalidConfig(format!("The CPU limit must be 1 to 100%, not {}%", percent)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn searches_for_everything_are_rejected() {
+        let config = || SearchConfig { threads: Some(1), ..Default::default() };
+        assert!(matches!(VanitySearch::new(config()), Err(Error::InvalidPattern(_))));
+        let empty_suffix = SearchConfig { suffix: Some(String::new()), ..config() };
+        assert!(matches!(VanitySearch::new(empty_suffix), Err(Error::InvalidPattern(_))));
+
+        let search = VanitySearch::new(SearchConfig { suffix: Some("q".to_string()), ..config() }).unwrap();
+        assert!(matches!(search.add_pattern("", None), Err(Error::InvalidPattern(_))));
+        assert!(search.add_pattern("q", None).is_ok());
+    }
+}
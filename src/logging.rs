@@ -0,0 +1,98 @@
+// Progress messages and diagnostics
+//
+// Results (matches, keys, benchmark figures, exports) are printed on stdout,
+// so they can be piped or redirected on their own. Everything else goes
+// through `tracing`: startup details and stats at info, diagnostics at debug,
+// problems at warn and error. Those messages are written to stderr, where -v
+// and -q pick how much of them shows, and to --log-file with timestamps.
+
+use clap::Args;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// How much to report besides results
+#[derive(Args, Debug, Clone, Copy)]
+pub struct Verbosity {
+    /// Report more: -v adds diagnostics, -vv everything
+    #[clap(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Report less: -q only warnings and errors, -qq only errors
+    #[clap(short, long, action = clap::ArgAction::Count, global = true, conflicts_with = "verbose")]
+    quiet: u8,
+}
+
+impl Verbosity {
+    fn level(self) -> Level {
+        match i16::from(self.verbose) - i16::from(self.quiet) {
+            i16::MIN..=-2 => Level::ERROR,
+            -1 => Level::WARN,
+            0 => Level::INFO,
+            1 => Level::DEBUG,
+            _ => Level::TRACE,
+        }
+    }
+}
+
+/// Report messages up to `verbosity` on stderr, and to `log_file` if given.
+/// A daemon's stderr is its log file already, so it only gets the one.
+pub fn init(verbosity: Verbosity, log_file: Option<&Path>, daemon: bool) -> Result<(), String> {
+    let level = verbosity.level();
+    let stderr = tracing_subscriber::fmt::layer()
+        .event_format(Plain)
+        .with_writer(std::io::stderr)
+        .with_filter(tracing_subscriber::filter::LevelFilter::from_level(level));
+
+    let file = match log_file.filter(|_| !daemon) {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_target(false)
+                .with_writer(Mutex::new(file))
+                .with_filter(tracing_subscriber::filter::LevelFilter::from_level(level));
+            Some(layer)
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(stderr)
+        .with(file)
+        .try_init()
+        .map_err(|e| format!("Failed to set up logging: {}", e))
+}
+
+// Just the message on the terminal, with a prefix for anything but info
+struct Plain;
+
+impl<S, N> FormatEvent<S, N> for Plain
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        match *event.metadata().level() {
+            Level::ERROR => write!(writer, "Error: ")?,
+            Level::WARN => write!(writer, "Warning: ")?,
+            Level::INFO => {}
+            Level::DEBUG => write!(writer, "Debug: ")?,
+            Level::TRACE => write!(writer, "Trace: ")?,
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
@@ -1,182 +1,1760 @@
-use bitcoin::address::Address;
-use bitcoin::key::{KeyPair, PublicKey};
-use bitcoin::Network;
-use bitcoin::secp256k1::{Secp256k1, rand};
-use clap::Parser;
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
-
-/// Bitcoin Vanity Address Generator specifically for bc1q addresses
+mod api;
+mod batch;
+mod color;
+mod control;
+mod daemon;
+mod distributed;
+mod estimate;
+mod exit;
+mod export;
+mod feed;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod history;
+mod jobs;
+mod keys;
+mod logging;
+mod manpage;
+mod notify;
+mod output;
+mod reporter;
+mod scheduling;
+mod signals;
+mod thermal;
+mod wizard;
+
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use serde_json::{json, Value};
+use std::fs::{File, OpenOptions};
+use std::io::{IsTerminal, Read, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, error, info, warn};
+
+use batch::Batch;
+use color::{highlight_address, highlight_within, ColorChoice};
+use distributed::{Finish, Job};
+use estimate::Energy;
+use exit::CliError;
+use export::ExportFormat;
+use feed::Feed;
+use history::Session;
+use keys::KeyFormat;
+use logging::Verbosity;
+use notify::{Notify, Outcome};
+use output::{Output, Recipients};
+use reporter::{format_rates, spawn_reporter, stop_reporter, RateWindow, LONG_WINDOW, SHORT_WINDOW};
+use scheduling::{pin_current_thread, Scheduling, Threads};
+use thermal::{Sensor, Thermal};
+use vanity_address_rust::bitcoin::bip32::{DerivationPath, ExtendedPrivKey};
+use vanity_address_rust::bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+use vanity_address_rust::{
+    identify_address, master_key_from_mnemonic, master_key_from_xprv, parse_cosigner, parse_private_key, AddressType,
+    Bip85, CustomMatcher, EntropySource, Error, Event, HashBackend, Match, Multisig, SearchConfig, Seed,
+    SecretString, ThreadStart, VanitySearch, XpubFormat, XpubSearch,
+};
+#[cfg(feature = "plugins")]
+use vanity_address_rust::WasmMatcher;
+use zeroize::Zeroize;
+
+/// Bitcoin vanity address generator for segwit, taproot, multisig and silent
+/// payment addresses, and Lightning node IDs
 #[derive(Parser, Debug)]
 #[clap(version, about, long_about = None)]
+#[clap(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
+#[clap(group(
+    ArgGroup::new("target")
+        .args(["pattern", "suffix", "job", "job_file", "patterns", "serve", "grpc", "plugin"])
+        .multiple(true)
+        .required(true)
+))]
 struct Args {
     /// Pattern to search for after the bc1q prefix
     #[clap(short, long)]
-    pattern: String,
+    pattern: Option<String>,
 
-    /// Pattern that the address should end with
+    /// Pattern that the address should end with (enough on its own, or
+    /// together with --pattern)
     #[clap(short = 'x', long)]
     suffix: Option<String>,
 
-    /// Number of threads to use (defaults to all available)
+    /// Number of threads to use (defaults to one per physical core)
     #[clap(short, long)]
     threads: Option<usize>,
 
     /// Print stats every N seconds
     #[clap(short, long, default_value = "5")]
     stats_interval: u64,
+
+    /// Include attempts and rate of every thread in the stats
+    #[clap(long)]
+    thread_stats: bool,
+
+    /// Log stats as text lines, or print them as JSON lines; both go to stderr
+    #[clap(long, value_enum, default_value_t = StatsFormat::Text)]
+    stats_format: StatsFormat,
+
+    /// Add the attempts, search time and matches of this run to this file,
+    /// and show the totals of all runs at the start and end
+    #[clap(long, value_name = "FILE")]
+    stats_file: Option<PathBuf>,
+
+    /// Start each thread at a random key and step through consecutive keys
+    /// by point addition instead of generating every key from scratch
+    #[clap(short, long)]
+    incremental: bool,
+
+    /// Keep searching after a match and report every match until stopped
+    #[clap(long)]
+    continuous: bool,
+
+    /// Search for several patterns until each has its number of matches,
+    /// e.g. "cafe:3, dead:1, 777:10@2" (PATTERN[...SUFFIX][:COUNT][@PRIORITY],
+    /// repeatable); the job ends once those of the highest priority have them
+    #[clap(long, value_name = "SPEC", conflicts_with_all = ["pattern", "suffix", "continuous"])]
+    job: Vec<String>,
+
+    /// Read job entries like those of --job from this file, one or more per
+    /// line, with # comments
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["pattern", "suffix", "continuous"])]
+    job_file: Option<PathBuf>,
+
+    /// Take in edits to --job-file while the job runs: added patterns join
+    /// the search and removed ones leave it, keeping the stats so far
+    #[clap(long, requires = "job_file", conflicts_with_all = ["pattern", "suffix", "continuous"])]
+    watch_job_file: bool,
+
+    /// Read patterns from this file, or from stdin for -, one
+    /// PATTERN[...SUFFIX] per line; lines that arrive while the search runs
+    /// join it
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["pattern", "suffix", "job", "job_file", "serve", "grpc"])]
+    patterns: Option<PathBuf>,
+
+    /// Give up after this many seconds
+    #[clap(long, value_name = "SECS")]
+    timeout: Option<u64>,
+
+    /// Derive every key from this 64 hex digit seed, to reproduce a search.
+    /// Testing only: anyone with the seed can recreate the keys
+    #[clap(long, value_name = "HEX", value_parser = Seed::from_hex)]
+    seed: Option<Seed>,
+
+    /// Where the seed of every key comes from: os, rdseed (OS randomness
+    /// mixed with the CPU's RDSEED) or file:PATH (OS randomness mixed with
+    /// the file's bytes)
+    #[clap(long, value_name = "SOURCE", default_value = "os", conflicts_with = "seed")]
+    entropy_source: EntropySource,
+
+    /// Read extra entropy, such as dice rolls, from stdin and mix it into
+    /// the seed of every key
+    #[clap(long, conflicts_with = "seed")]
+    extra_entropy: bool,
+
+    /// Derive every key with BIP85 from a master key read from stdin (a
+    /// BIP39 mnemonic, optionally followed by its passphrase on the next
+    /// line, or an xprv), so matches can be recovered from its backup
+    #[clap(long, conflicts_with_all = ["seed", "entropy_source", "extra_entropy", "incremental", "daemon"])]
+    bip85: bool,
+
+    /// With --bip85, the first child index to try, e.g. to continue an
+    /// earlier search
+    #[clap(long, value_name = "INDEX", requires = "bip85")]
+    bip85_start: Option<u32>,
+
+    /// With --bip85, search the master key's own keys at this path instead
+    /// of BIP85 keys, grinding its last index from the one given, e.g.
+    /// m/84'/0'/0'/0/0 (or bip84 for that path) for the receive addresses of
+    /// the first native segwit account
+    #[clap(
+        long,
+        value_name = "PATH",
+        value_parser = parse_wallet_path,
+        requires = "bip85",
+        conflicts_with = "bip85_start"
+    )]
+    path: Option<DerivationPath>,
+
+    /// Hash160 implementation to use
+    #[clap(long, value_enum, default_value_t = HashBackend::Auto)]
+    hash_backend: HashBackend,
+
+    /// Kind of address to search for
+    #[clap(long, value_enum, default_value_t = AddressType::P2wpkh)]
+    address_type: AddressType,
+
+    /// Only report addresses that this WebAssembly plugin also scores as a
+    /// match; without a pattern, it sees every candidate. Needs a build with
+    /// the `plugins` feature
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["serve", "grpc"])]
+    plugin: Option<PathBuf>,
+
+    /// Compressed public key of a fixed cosigner of a P2WSH multisig wallet
+    /// (repeat for every cosigner)
+    #[clap(
+        long = "cosigner",
+        value_name = "PUBKEY",
+        value_parser = parse_cosigner,
+        requires = "threshold",
+        required_if_eq("address_type", "p2wsh")
+    )]
+    cosigners: Vec<PublicKey>,
+
+    /// Number of signatures the P2WSH multisig wallet needs to spend
+    #[clap(long, value_name = "M", requires = "cosigners", required_if_eq("address_type", "p2wsh"))]
+    threshold: Option<usize>,
+
+    /// Run a REST API server on this address instead of a single search
+    #[clap(long, value_name = "ADDR")]
+    serve: Option<String>,
+
+    /// Run a gRPC server on this address (e.g. 127.0.0.1:50051) instead of a
+    /// single search; needs a build with the `grpc` feature
+    #[clap(long, value_name = "ADDR", conflicts_with = "serve")]
+    grpc: Option<String>,
+
+    /// Let --serve and --grpc listen on addresses other machines can reach.
+    /// Neither server has authentication, so put them behind a proxy that
+    /// adds some.
+    #[clap(long)]
+    allow_remote: bool,
+
+    /// Lock key material into RAM so it can never be swapped to disk
+    #[clap(long)]
+    lock_memory: bool,
+
+    /// Let every search thread work only this percentage of the time,
+    /// sleeping in between, to keep the machine cool and quiet
+    #[clap(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(1..=100))]
+    max_cpu: Option<u8>,
+
+    /// Throttle the search while the CPU is hotter than this many degrees
+    /// Celsius, and speed it up again once it has cooled down
+    #[clap(long, value_name = "CELSIUS", value_parser = clap::value_parser!(u8).range(30..=120))]
+    max_temp: Option<u8>,
+
+    /// Read the CPU temperature from this file, in millidegrees Celsius,
+    /// instead of the sensor found in /sys
+    #[clap(long, value_name = "FILE", requires = "max_temp")]
+    temp_sensor: Option<PathBuf>,
+
+    /// Append found addresses and their private keys to this file (with a
+    /// job, to one file per pattern next to it)
+    #[clap(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Never print private keys; show a key fingerprint instead and write
+    /// the key only to the output file
+    #[clap(long, requires = "output")]
+    no_show_key: bool,
+
+    /// How to write private keys, on the terminal and in the output file,
+    /// match log and webhooks, and in the job results of --serve and --grpc
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = KeyFormat::Hex)]
+    key_format: KeyFormat,
+
+    /// Encrypt the output file to this age recipient (age1...), and never
+    /// print or send private keys; repeat for more recipients
+    #[clap(
+        long,
+        value_name = "RECIPIENT",
+        value_parser = output::parse_recipient,
+        requires = "output",
+        conflicts_with_all = ["log_matches", "webhook_include_key"]
+    )]
+    encrypt_to: Vec<age::x25519::Recipient>,
+
+    /// Encrypt the output file to the OpenPGP public key in this file, as
+    /// armored text, using gpg; like --encrypt-to, keys are never printed or
+    /// sent. Repeat for more recipients
+    #[clap(
+        long,
+        value_name = "KEYFILE",
+        requires = "output",
+        conflicts_with_all = ["encrypt_to", "log_matches", "webhook_include_key"]
+    )]
+    encrypt_to_pgp: Vec<PathBuf>,
+
+    /// Append one JSON line per match to this file as soon as it is found
+    #[clap(long, value_name = "FILE")]
+    log_matches: Option<PathBuf>,
+
+    /// Detach from the terminal and keep reporting every match in the
+    /// background, with all output going to --log-file
+    #[clap(long, requires = "log_file", conflicts_with = "extra_entropy")]
+    daemon: bool,
+
+    /// Also append progress messages, warnings and errors to this file, with
+    /// timestamps. With --daemon, everything printed goes here
+    #[clap(long, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Write the process ID to this file while running
+    #[clap(long, value_name = "FILE")]
+    pid_file: Option<PathBuf>,
+
+    /// When to color matches in the output: auto (on a terminal, unless
+    /// NO_COLOR is set), always or never
+    #[clap(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto, global = true)]
+    color: ColorChoice,
+
+    /// Accept commands (status, pause, resume, add-pattern, stop) on this
+    /// Unix socket while searching
+    #[clap(long, value_name = "PATH")]
+    control_socket: Option<PathBuf>,
+
+    #[clap(flatten)]
+    energy: Energy,
+
+    #[clap(flatten)]
+    scheduling: Scheduling,
+
+    #[clap(flatten)]
+    notify: Notify,
+
+    #[clap(flatten)]
+    verbosity: Verbosity,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum StatsFormat {
+    /// Human readable lines, logged to stderr like the other progress messages
+    Text,
+    /// One JSON object per stats interval on stderr
+    Jsonl,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Coordinate a search across worker machines
+    #[clap(group(ArgGroup::new("target").args(["pattern", "suffix"]).multiple(true).required(true)))]
+    Serve {
+        /// Address to listen on for workers
+        #[clap(short, long, default_value = "0.0.0.0:7878")]
+        listen: String,
+
+        /// Pattern to search for after the prefix of the address type
+        #[clap(short, long)]
+        pattern: Option<String>,
+
+        /// Pattern that the address should end with (enough on its own, or
+        /// together with --pattern)
+        #[clap(short = 'x', long)]
+        suffix: Option<String>,
+
+        /// Have workers use incremental key generation
+        #[clap(short, long)]
+        incremental: bool,
+
+        /// Kind of address the workers search for: p2wpkh, p2tr or node-id
+        #[clap(long, value_enum, default_value_t = AddressType::P2wpkh)]
+        address_type: AddressType,
+
+        /// Print combined stats every N seconds
+        #[clap(short, long, default_value = "5")]
+        stats_interval: u64,
+
+        /// Give up and stop the workers after this many seconds
+        #[clap(long, value_name = "SECS")]
+        timeout: Option<u64>,
+
+        /// Append the found address and its private key to this file
+        #[clap(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Never print the private key; show a key fingerprint instead and
+        /// write the key only to the output file
+        #[clap(long, requires = "output")]
+        no_show_key: bool,
+
+        /// How to write the private key, on the terminal and in the output
+        /// file, match log and webhooks
+        #[clap(long, value_enum, value_name = "FORMAT", default_value_t = KeyFormat::Hex)]
+        key_format: KeyFormat,
+
+        /// Append a JSON line describing the match to this file
+        #[clap(long, value_name = "FILE")]
+        log_matches: Option<PathBuf>,
+
+        #[clap(flatten)]
+        notify: Box<Notify>,
+    },
+
+    /// Measure throughput for every available hash backend and key generator
+    Bench {
+        /// Seconds to run each measurement for
+        #[clap(short, long, default_value = "5")]
+        duration: u64,
+
+        /// Number of threads to use (defaults to one per physical core)
+        #[clap(short, long)]
+        threads: Option<usize>,
+
+        #[clap(flatten)]
+        scheduling: Scheduling,
+    },
+
+    /// Estimate how long a pattern takes on this machine, and with --watts
+    /// the energy and cost, for even odds and for a 95% chance of a match
+    #[clap(group(ArgGroup::new("target").args(["pattern", "suffix"]).multiple(true).required(true)))]
+    Estimate {
+        /// Pattern to search for after the prefix of the address type
+        #[clap(short, long)]
+        pattern: Option<String>,
+
+        /// Pattern that the address should end with
+        #[clap(short = 'x', long)]
+        suffix: Option<String>,
+
+        /// Kind of address to search for
+        #[clap(long, value_enum, default_value_t = AddressType::P2wpkh)]
+        address_type: AddressType,
+
+        /// Measure incremental key generation
+        #[clap(short, long)]
+        incremental: bool,
+
+        /// Addresses per second to estimate with, e.g. of another machine,
+        /// instead of measuring this one
+        #[clap(long, value_name = "ADDR/S", value_parser = estimate::parse_positive)]
+        rate: Option<f64>,
+
+        /// Seconds to measure the rate for
+        #[clap(short, long, default_value = "3")]
+        duration: u64,
+
+        /// Number of threads to use (defaults to one per physical core)
+        #[clap(short, long)]
+        threads: Option<usize>,
+
+        /// Hash160 implementation to use
+        #[clap(long, value_enum, default_value_t = HashBackend::Auto)]
+        hash_backend: HashBackend,
+
+        #[clap(flatten)]
+        energy: Energy,
+
+        #[clap(flatten)]
+        scheduling: Scheduling,
+    },
+
+    /// Check that a private key controls an address before funding it
+    Verify {
+        /// Private key, as WIF or as the hex printed by a search
+        #[clap(short, long, value_name = "KEY")]
+        wif: String,
+
+        /// Address the key should control
+        #[clap(short, long)]
+        address: String,
+    },
+
+    /// Print the keys in a --log-matches file in a format wallets can import
+    Export {
+        /// Match log written by --log-matches
+        #[clap(value_name = "FILE")]
+        input: PathBuf,
+
+        /// Wallet format to export to
+        #[clap(short, long, value_enum)]
+        format: ExportFormat,
+
+        /// Directory to write a bundle per key to (sparrow format)
+        #[clap(long, value_name = "DIR", required_if_eq("format", "sparrow"))]
+        dir: Option<PathBuf>,
+    },
+
+    /// Search for an extended public key (xpub or zpub) that contains a
+    /// pattern, e.g. for a branded watch-only wallet
+    Xpub {
+        /// Text the key should contain anywhere (base58, case-sensitive)
+        #[clap(short, long)]
+        pattern: String,
+
+        /// Key format
+        #[clap(short, long, value_enum, default_value_t = XpubFormat::Xpub)]
+        format: XpubFormat,
+
+        /// Path of the accounts [default: m/44'/0' for xpub, m/84'/0' for zpub]
+        #[clap(long, value_name = "PATH")]
+        path: Option<String>,
+
+        /// Grind the account number of an existing wallet, read from stdin as
+        /// a BIP39 mnemonic (optionally followed by its passphrase on the
+        /// next line) or an xprv, instead of new master keys
+        #[clap(long)]
+        wallet: bool,
+
+        /// Number of threads to use (defaults to one per physical core)
+        #[clap(short, long)]
+        threads: Option<usize>,
+
+        /// Print stats every N seconds
+        #[clap(short, long, default_value = "5")]
+        stats_interval: u64,
+
+        /// Give up after this many seconds
+        #[clap(long, value_name = "SECS")]
+        timeout: Option<u64>,
+
+        /// Append the match and its private keys to this file
+        #[clap(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        #[clap(flatten)]
+        scheduling: Scheduling,
+    },
+
+    /// Print a shell completion script
+    Completions {
+        /// Shell to complete in
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+
+    /// Print the man page, in roff
+    Manpage,
+
+    /// Search on behalf of a coordinator
+    Worker {
+        /// Coordinator address to connect to
+        #[clap(short, long)]
+        connect: String,
+
+        /// Number of threads to use (defaults to one per physical core)
+        #[clap(short, long)]
+        threads: Option<usize>,
+
+        /// Hash160 implementation to use
+        #[clap(long, value_enum, default_value_t = HashBackend::Auto)]
+        hash_backend: HashBackend,
+
+        /// Lock key material into RAM so it can never be swapped to disk
+        #[clap(long)]
+        lock_memory: bool,
+
+        #[clap(flatten)]
+        scheduling: Scheduling,
+    },
+}
+
+// Print an error and exit with the status for its kind
+fn fail(error: impl Into<CliError>) -> ! {
+    let error = error.into();
+    error!("{}", error);
+    std::process::exit(error.exit_code());
+}
+
+// Like `fail`, but also report the failed search by email
+fn fail_search(notify: &Notify, pattern: &str, suffix: Option<&str>, error: impl Into<CliError>) -> ! {
+    let error = error.into();
+    error!("{}", error);
+    notify.finished(pattern, suffix, Outcome::Failed(&error.to_string()));
+    std::process::exit(error.exit_code());
+}
+
+// Read entropy from stdin until EOF
+fn read_extra_entropy() -> Result<Vec<u8>, String> {
+    let mut stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprintln!("Enter extra entropy (e.g. dice rolls), then press Ctrl+D on a new line:");
+    }
+
+    let mut entropy = Vec::new();
+    let read = stdin.read_to_end(&mut entropy);
+    let error = match read {
+        Err(e) => format!("Failed to read extra entropy: {}", e),
+        Ok(_) if entropy.iter().all(u8::is_ascii_whitespace) => "No extra entropy given on stdin".to_string(),
+        Ok(_) => return Ok(entropy),
+    };
+    entropy.zeroize();
+    Err(error)
+}
+
+// Read a wallet's master key from stdin, as a BIP39 mnemonic and passphrase
+// or as an xprv
+fn read_master_key() -> Result<ExtendedPrivKey, String> {
+    let stdin = std::io::stdin();
+    let prompt = stdin.is_terminal();
+    if prompt {
+        eprintln!("Enter the BIP39 mnemonic or xprv of the master key:");
+    }
+    let mut key = String::new();
+    let mut passphrase = String::new();
+    let read = stdin.read_line(&mut key);
+    let master = match read {
+        Err(e) => Err(format!("Failed to read the master key: {}", e)),
+        Ok(_) if key.trim().is_empty() => Err("No master key given on stdin".to_string()),
+        Ok(_) if key.trim_start().starts_with("xprv") => master_key_from_xprv(&key),
+        Ok(_) => {
+            if prompt {
+                eprintln!("Enter the BIP39 passphrase, or nothing if there is none:");
+            }
+            match stdin.read_line(&mut passphrase) {
+                Ok(_) => master_key_from_mnemonic(&key, passphrase.trim_end_matches(['\r', '\n'])),
+                Err(e) => Err(format!("Failed to read the passphrase: {}", e)),
+            }
+        }
+    };
+    key.zeroize();
+    passphrase.zeroize();
+    master
 }
 
-// Stats structure to track the progress
-struct Stats {
-    attempts: u64,
-    started_at: Instant,
+// Read the master key of --bip85 from stdin
+fn read_bip85(start: Option<u32>, path: Option<&DerivationPath>) -> Result<Bip85, String> {
+    let master = read_master_key()?;
+    match path {
+        Some(path) => Bip85::at_path(master, path),
+        None => Bip85::from_master(master)?.starting_at(start.unwrap_or(0)),
+    }
+}
+
+// A path for --path: BIP84's first receive address, or any path
+fn parse_wallet_path(path: &str) -> Result<DerivationPath, String> {
+    let path = if path.eq_ignore_ascii_case("bip84") { "m/84'/0'/0'/0/0" } else { path };
+    DerivationPath::from_str(path).map_err(|e| format!("invalid derivation path: {}", e))
+}
+
+// The seed of every key: fixed by --seed, or drawn from the entropy source
+fn search_seed(args: &Args) -> Result<Seed, String> {
+    if let Some(seed) = &args.seed {
+        return Ok(seed.clone());
+    }
+    let mut extra = args.extra_entropy.then(read_extra_entropy).transpose()?;
+    let seed = args.entropy_source.seed(extra.as_deref());
+    extra.zeroize();
+    seed
 }
 
-impl Stats {
-    fn new() -> Self {
-        Stats {
-            attempts: 0,
-            started_at: Instant::now(),
+// The patterns of --job and --job-file, if any
+fn read_job(args: &Args) -> Option<Result<Batch, String>> {
+    if args.job.is_empty() && args.job_file.is_none() {
+        return None;
+    }
+    let mut spec = args.job.join(",");
+    if let Some(path) = &args.job_file {
+        match std::fs::read_to_string(path) {
+            Ok(text) => spec = format!("{}\n{}", spec, text),
+            Err(e) => return Some(Err(format!("Failed to read {}: {}", path.display(), e))),
+        }
+    }
+    Some(batch::parse_targets(&spec).and_then(Batch::new))
+}
+
+// Take in an edited --job-file, or keep the job as it was if it's invalid
+fn reload_job(args: &Args, batch: &mut Batch, search: &VanitySearch) {
+    let path = args.job_file.as_deref().expect("only jobs with a file are watched");
+    let reloaded = read_job(args).expect("the job has a file").and_then(|job| {
+        batch.reload(
+            job,
+            |patterns| search.set_patterns(patterns).map_err(|e| e.to_string()),
+            |path| try_open_results(path, recipients(args)),
+        )
+    });
+    match reloaded {
+        Ok(changes) if changes.is_empty() => debug!("{} changed, but not its patterns", path.display()),
+        Ok(changes) => {
+            info!("Reloaded {}: {}", path.display(), changes.join(", "));
+            info!("Progress:    {}", batch.progress());
         }
+        Err(e) => warn!("Ignoring the edit to {}: {}", path.display(), e),
     }
+}
 
-    fn increment(&mut self, count: u64) {
-        self.attempts += count;
+fn print_job(batch: &Batch, prefix: &str) {
+    let after = if prefix.is_empty() { String::new() } else { format!(" (after {})", prefix) };
+    info!("Looking for {} patterns{}:", batch.targets().len(), after);
+    for target in batch.targets() {
+        let plural = if target.count == 1 { "" } else { "es" };
+        let priority = if batch.is_prioritized() { format!(", priority {}", target.priority) } else { String::new() };
+        info!("  '{}': {} match{}{}", target.label(), target.count, plural, priority);
     }
+}
 
-    fn print(&self) {
-        let elapsed = self.started_at.elapsed().as_secs();
-        if elapsed > 0 {
-            let rate = self.attempts as f64 / elapsed as f64;
-            println!(
-                "Attempts: {}, Time: {}s, Rate: {:.2} addr/s",
-                self.attempts, elapsed, rate
+fn print_fed(patterns: &[feed::Pattern], prefix: &str) {
+    if let [(pattern, suffix)] = patterns {
+        return print_target(pattern, suffix.as_deref(), prefix);
+    }
+    let after = if prefix.is_empty() { String::new() } else { format!(" (after {})", prefix) };
+    info!("Looking for {} patterns{}:", patterns.len(), after);
+    for (pattern, suffix) in patterns {
+        info!("  '{}'", batch::label(pattern, suffix.as_deref()));
+    }
+}
+
+// Add the patterns that arrived on the feed to the search, skipping those
+// that can't match, and return the ones added
+fn take_fed(search: &VanitySearch, feed: &mut Feed) -> Vec<String> {
+    let mut added = Vec::new();
+    for (pattern, suffix) in feed.arrived() {
+        let label = batch::label(&pattern, suffix.as_deref());
+        match search.add_pattern(&pattern, suffix.as_deref()) {
+            Ok(()) => added.push(label),
+            Err(e) => warn!("Skipping '{}': {}", label, e),
+        }
+    }
+    added
+}
+
+fn print_target(pattern: &str, suffix: Option<&str>, prefix: &str) {
+    match suffix {
+        Some(suffix) if pattern.is_empty() => info!("Looking for an address ending with: '{}'", suffix),
+        _ => {
+            if prefix.is_empty() {
+                info!("Looking for pattern: '{}'", pattern);
+            } else {
+                info!("Looking for pattern: '{}' (after {})", pattern, prefix);
+            }
+            if let Some(suffix) = suffix {
+                info!("And ending with: '{}'", suffix);
+            }
+        }
+    }
+}
+
+// The number of search threads, and how to set up each of them: the
+// priority is lowered here, and inherited by the threads started later, while
+// every thread pins itself to its core as it starts
+fn configure_threads(threads: Option<usize>, scheduling: &Scheduling) -> Threads {
+    scheduling.apply_priority();
+    let cores = scheduling.cores().unwrap_or_else(|e| fail(CliError::InvalidOptions(e)));
+
+    let count = scheduling.threads(threads);
+    match &cores {
+        Some(cores) => debug!("Running {} search threads, pinned to cores {:?}", count, cores),
+        None => debug!("Running {} search threads", count),
+    }
+    let start = cores.map(|cores| ThreadStart::new(move |index| pin_current_thread(cores[index % cores.len()])));
+    Threads { count, start }
+}
+
+// Lock key buffers into RAM, carrying on without if the OS won't allow it
+fn configure_memory_lock(lock: bool) {
+    if lock {
+        match vanity_address_rust::lock_memory() {
+            Ok(()) => info!("Key material is locked into RAM"),
+            Err(e) => warn!("{}; key material may be swapped to disk", e),
+        }
+    }
+}
+
+// What the stats reporter remembers between reports
+struct ReportState {
+    // Per-thread attempt counts as of the previous report
+    thread_attempts: Vec<u64>,
+    at: Duration,
+    rates: RateWindow,
+}
+
+impl ReportState {
+    fn new(threads: usize) -> Self {
+        ReportState {
+            thread_attempts: vec![0; threads],
+            at: Duration::ZERO,
+            rates: RateWindow::new(LONG_WINDOW),
+        }
+    }
+}
+
+// One JSON object per report for scripts and dashboards. The ETA is the
+// expected time for the expected number of attempts, which is only an
+// estimate: matches arrive at random.
+fn print_stats_json(search: &VanitySearch, previous: &mut ReportState, thermal: Option<&Thermal>) {
+    let elapsed = search.elapsed();
+    let attempts = search.attempts();
+    previous.rates.record(elapsed, attempts);
+
+    let rate = attempts as f64 / elapsed.as_secs_f64();
+    let rate_10s = previous.rates.recent_rate(SHORT_WINDOW, elapsed);
+    let recent = rate_10s.unwrap_or(rate);
+    let remaining = (search.expected_attempts() - attempts as f64).max(0.0);
+    let eta_secs = (recent > 0.0).then(|| remaining / recent);
+
+    let thread_attempts = search.thread_attempts();
+    let stalled: Vec<usize> = (0..thread_attempts.len())
+        .filter(|&i| elapsed > previous.at && thread_attempts[i] == previous.thread_attempts[i])
+        .collect();
+
+    let line = json!({
+        "attempts": attempts,
+        "elapsed_secs": elapsed.as_secs_f64(),
+        "rate": rate,
+        "rate_10s": rate_10s,
+        "rate_60s": previous.rates.recent_rate(LONG_WINDOW, elapsed),
+        "expected_attempts": search.expected_attempts(),
+        "eta_secs": eta_secs,
+        "threads": thread_attempts.len(),
+        "thread_attempts": thread_attempts,
+        "stalled_threads": stalled,
+        "cpu_limit": search.max_cpu(),
+        "temperature_c": thermal.and_then(Thermal::celsius),
+        "thermal_throttles": thermal.map(Thermal::throttled),
+    });
+    eprintln!("{}", line);
+
+    previous.thread_attempts = thread_attempts;
+    previous.at = elapsed;
+}
+
+fn print_stats(search: &VanitySearch, previous: &mut ReportState, per_thread: bool, thermal: Option<&Thermal>) {
+    let elapsed = search.elapsed();
+    let attempts = search.attempts();
+    previous.rates.record(elapsed, attempts);
+    if elapsed.as_secs() > 0 {
+        info!(
+            "Attempts: {}, Time: {}s, Rate: {}",
+            attempts,
+            elapsed.as_secs(),
+            format_rates(&previous.rates, attempts, elapsed)
+        );
+    }
+    if let Some(celsius) = thermal.and_then(Thermal::celsius) {
+        let throttled = thermal.map_or(0, Thermal::throttled);
+        info!("  CPU: {:.1}°C, limit {}%, throttled {} times", celsius, search.max_cpu(), throttled);
+    }
+
+    // Rates over the last interval, so a thread that slows down or stops
+    // shows up right away instead of being averaged away
+    let now = elapsed;
+    let thread_attempts = search.thread_attempts();
+    let interval = (now - previous.at).as_secs_f64();
+    if interval == 0.0 {
+        return;
+    }
+    for (thread_id, (&attempts, &before)) in thread_attempts.iter().zip(&previous.thread_attempts).enumerate() {
+        let stalled = attempts == before;
+        if per_thread {
+            info!(
+                "  Thread {}: Attempts: {}, Rate: {:.2} addr/s{}",
+                thread_id,
+                attempts,
+                (attempts - before) as f64 / interval,
+                if stalled { " (stalled)" } else { "" }
             );
+        } else if stalled {
+            warn!("thread {} made no progress in the last {:.0}s", thread_id, interval);
+        }
+    }
+    previous.thread_attempts = thread_attempts;
+    previous.at = now;
+}
+
+// Open the output file up front, so a bad path fails before the search
+// rather than after it. Only the owner may read it.
+fn open_output(path: &Path) -> File {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options
+        .open(path)
+        .unwrap_or_else(|e| fail(format!("Failed to open {}: {}", path.display(), e)))
+}
+
+// The file found keys are saved to, encrypted if there are recipients
+fn open_results(path: &Path, recipients: Option<Recipients>) -> Output {
+    try_open_results(path, recipients).unwrap_or_else(|e| fail(e))
+}
+
+fn try_open_results(path: &Path, recipients: Option<Recipients>) -> Result<Output, String> {
+    match recipients {
+        Some(recipients) => Output::encrypted(path, recipients),
+        None => Output::open(path),
+    }
+}
+
+// Who --encrypt-to or --encrypt-to-pgp encrypt the output file to, if anyone
+fn recipients(args: &Args) -> Option<Recipients> {
+    if !args.encrypt_to.is_empty() {
+        Some(Recipients::Age(args.encrypt_to.clone()))
+    } else if !args.encrypt_to_pgp.is_empty() {
+        Some(Recipients::Pgp(args.encrypt_to_pgp.clone()))
+    } else {
+        None
+    }
+}
+
+// The internal key and tweak of a taproot match, which are public
+fn taproot_lines(found: &Match) -> String {
+    match &found.taproot {
+        Some(taproot) => format!("Internal key: {}\nTweak:        {}\n", taproot.internal_key, taproot.tweak),
+        None => String::new(),
+    }
+}
+
+// The wallet descriptor of a P2WSH match, after its WIF unless --key-format
+// already shows it. Without the key, the descriptor has our public key
+// instead, for a watch-only wallet.
+fn multisig_lines(found: &Match, show_key: bool, key_format: KeyFormat) -> SecretString {
+    let Some(multisig) = &found.multisig else {
+        return SecretString::new("");
+    };
+    if !show_key {
+        let mut secret_key: SecretKey = found.private_key.parse().expect("matches hold valid keys");
+        let public_key = secret_key.public_key(&Secp256k1::new());
+        secret_key.non_secure_erase();
+        return SecretString::from(format!("Descriptor:  {}\n", multisig.descriptor(&public_key.to_string())));
+    }
+    let wif = keys::wif(&found.private_key);
+    let descriptor = SecretString::from(multisig.descriptor(&wif));
+    match key_format {
+        // The key line is the descriptor itself
+        KeyFormat::Descriptor => SecretString::new(""),
+        _ if key_format.shows_wif() => SecretString::from(format!("Descriptor:  {}\n", descriptor.as_str())),
+        _ => SecretString::from(format!("WIF:         {}\nDescriptor:  {}\n", wif.as_str(), descriptor.as_str())),
+    }
+}
+
+fn score_lines(found: &Match) -> String {
+    found.score.map_or(String::new(), |score| format!("Score:       {}\n", score))
+}
+
+// Where the key of a BIP85 match sits in its master wallet, which is public
+fn bip85_lines(found: &Match) -> String {
+    match &found.bip85 {
+        Some(child) if child.chain.is_some() => {
+            format!("Path:        master {}, {}\n", child.fingerprint, child.path())
         }
+        Some(child) => format!("BIP85:       master {}, {}\n", child.fingerprint, child.path()),
+        None => String::new(),
+    }
+}
+
+fn print_found(found: &Match, show_key: bool, key_format: KeyFormat) {
+    println!("Address:     {}", highlight_address(&found.address, &found.pattern, found.suffix.as_deref()));
+    print!("{}", score_lines(found));
+    print!("{}", taproot_lines(found));
+    print!("{}", bip85_lines(found));
+    if show_key {
+        print!("{}", key_format.key_lines(found).as_str());
+    } else {
+        println!("Fingerprint: {}", found.fingerprint());
     }
+    print!("{}", multisig_lines(found, show_key, key_format).as_str());
 }
 
-fn generate_p2wpkh_address(secp: &Secp256k1<bitcoin::secp256k1::All>) -> (KeyPair, String) {
-    // Generate a key pair
-    let key_pair = KeyPair::new(secp, &mut rand::thread_rng());
-    let public_key = PublicKey::new(key_pair.public_key());
-    
-    // Create a P2WPKH address (bc1q format)
-    let address = Address::p2wpkh(&public_key, Network::Bitcoin)
-        .expect("Failed to create P2WPKH address");
-    
-    // Return the address string representation
-    (key_pair, address.to_string())
+fn save_found(output: &mut Output, found: &Match, key_format: KeyFormat) {
+    let record = SecretString::from(format!(
+        "Address:     {}\n{}{}{}{}{}Fingerprint: {}\n\n",
+        found.address,
+        score_lines(found),
+        taproot_lines(found),
+        bip85_lines(found),
+        key_format.key_lines(found).as_str(),
+        multisig_lines(found, true, key_format).as_str(),
+        found.fingerprint()
+    ));
+    match output.append(&record) {
+        Ok(()) if output.is_encrypted() => info!("Saved to {} (encrypted)", output.path().display()),
+        Ok(()) => info!("Saved to {}", output.path().display()),
+        Err(e) => error!("{}", e),
+    }
 }
 
-fn check_address(address: &str, prefix_pattern: &str, suffix_pattern: Option<&str>) -> bool {
-    // bc1q addresses are more than 14 characters
-    if address.len() <= 4 || !address.starts_with("bc1q") {
-        return false;
+// Append a match to the JSON lines log, on disk before returning so a crash
+// can't lose it
+fn log_match(log: &mut File, path: &Path, found: &Match, key_format: KeyFormat) {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |t| t.as_secs());
+    let mut line = json!({
+        "address": found.address,
+        "fingerprint": found.fingerprint(),
+        "pattern": found.pattern,
+        "suffix": found.suffix,
+        "attempts": found.attempts,
+        "elapsed_secs": found.elapsed.as_secs_f64(),
+        "timestamp": timestamp,
+    });
+    if let Value::Object(fields) = &mut line {
+        fields.extend(key_format.json_fields(found));
+    }
+    if let Some(taproot) = &found.taproot {
+        line["internal_key"] = json!(taproot.internal_key);
+        line["tweak"] = json!(taproot.tweak);
+    }
+    if let Some(spend_key) = &found.spend_key {
+        line["spend_public_key"] = json!(spend_key.public_key);
     }
+    if let Some(child) = &found.bip85 {
+        line["bip85_fingerprint"] = json!(child.fingerprint.to_string());
+        line["bip85_index"] = json!(child.index);
+        line["bip85_path"] = json!(child.path());
+    }
+    if let Some(score) = found.score {
+        line["score"] = json!(score);
+    }
+    if let Some(multisig) = &found.multisig {
+        line["threshold"] = json!(multisig.threshold());
+        line["cosigners"] = json!(multisig.cosigners());
+    }
+    let mut line = line.to_string();
+    line.push('\n');
 
-    // Check if the prefix pattern appears right after bc1q
-    let prefix_match = address[4..].starts_with(prefix_pattern);
+    let written = log.write_all(line.as_bytes()).and_then(|_| log.sync_data());
+    line.zeroize();
+    if let Err(e) = written {
+        error!("Failed to write to {}: {}", path.display(), e);
+    }
+}
+
+// Print a match and append it to the output file, if any
+fn report_found(found: &Match, output: Option<&mut Output>, show_key: bool, key_format: KeyFormat) {
+    print_found(found, show_key, key_format);
+    if let Some(output) = output {
+        save_found(output, found, key_format);
+    }
+}
+
+// Benchmark every combination of available hash backend and key generator
+fn run_bench(duration: Duration, threads: &Threads) {
+    let backends: Vec<HashBackend> = [HashBackend::Portable, HashBackend::Accelerated]
+        .into_iter()
+        .filter(|backend| backend.resolve().is_ok())
+        .collect();
+
+    info!(
+        "Benchmarking {} threads for {}s per run",
+        threads.count,
+        duration.as_secs()
+    );
+
+    for hash_backend in backends {
+        for incremental in [false, true] {
+            let config = SearchConfig {
+                hash_backend,
+                incremental,
+                threads: Some(threads.count),
+                thread_start: threads.start.clone(),
+                ..Default::default()
+            };
+            let result = vanity_address_rust::benchmark(config, duration).unwrap_or_else(|e| fail(e));
+
+            let keygen = if incremental { "incremental" } else { "random" };
+            println!("\nHash backend: {}, key generation: {}", result.hash_backend, keygen);
+            println!("  {:<11}{:.2} addr/s", "Total:", result.rate());
+            for (thread_id, rate) in result.thread_rates().iter().enumerate() {
+                println!("  {:<11}{:.2} addr/s", format!("Thread {}:", thread_id), rate);
+            }
+        }
+    }
+}
 
-    // If there's no suffix pattern, just return the prefix match result
-    if let Some(suffix) = suffix_pattern {
-        // Check both prefix and suffix
-        prefix_match && address.ends_with(suffix)
+// With --watts, the energy the search took, and what the pattern takes at
+// the rate it ran at
+fn report_energy(search: &VanitySearch, energy: &Energy) {
+    let progress = search.progress();
+    let secs = progress.elapsed.as_secs_f64();
+    let Some(used) = energy.used(secs) else { return };
+    info!("{}", used);
+    if progress.attempts > 0 && secs > 0.0 {
+        for line in energy.estimates(search.expected_attempts(), progress.attempts as f64 / secs) {
+            info!("At this rate: {}", line);
+        }
+    }
+}
+
+// Print the time, energy and cost of a search, at `rate` or at the rate
+// measured for `duration`
+fn run_estimate(config: SearchConfig, rate: Option<f64>, duration: Duration, energy: &Energy) {
+    let search = VanitySearch::new(config.clone()).unwrap_or_else(|e| fail(e));
+    let rate = rate.unwrap_or_else(|| {
+        info!("Measuring {} threads for {}s", search.threads(), duration.as_secs());
+        vanity_address_rust::benchmark(config, duration).unwrap_or_else(|e| fail(e)).rate()
+    });
+
+    let suffix = search.suffix().map_or(String::new(), |suffix| format!("...{}", suffix));
+    println!("Pattern:           {}{}{}", search.address_type().prefix(), search.pattern(), suffix);
+    println!("Expected attempts: {:.0}", search.expected_attempts());
+    println!("Rate:              {:.0} addr/s", rate);
+    for line in energy.estimates(search.expected_attempts(), rate) {
+        println!("{}", line);
+    }
+}
+
+#[cfg(feature = "plugins")]
+fn load_plugin(path: &Path) -> Result<Arc<dyn CustomMatcher>, String> {
+    Ok(Arc::new(WasmMatcher::load(path)?))
+}
+
+#[cfg(not(feature = "plugins"))]
+fn load_plugin(_path: &Path) -> Result<Arc<dyn CustomMatcher>, String> {
+    Err("This build can't load plugins; rebuild with `--features plugins`".to_string())
+}
+
+#[cfg(feature = "grpc")]
+fn serve_grpc(listen: &str, args: &Args) {
+    let hash_backend = args.hash_backend.resolve().unwrap_or_else(|e| fail(CliError::InvalidOptions(e)));
+    let threads = configure_threads(args.threads, &args.scheduling);
+    let stats_interval = Duration::from_secs(args.stats_interval);
+    grpc::serve(listen, hash_backend, threads, stats_interval, args.key_format, args.allow_remote).unwrap_or_else(|e| fail(e));
+}
+
+#[cfg(not(feature = "grpc"))]
+fn serve_grpc(_listen: &str, _args: &Args) {
+    fail(CliError::InvalidOptions(
+        "This build has no gRPC server; rebuild with `--features grpc`".to_string(),
+    ));
+}
+
+// Search for a vanity xpub, printing progress until a match, the timeout or
+// the end of the wallet's accounts
+fn run_xpub(
+    search: XpubSearch,
+    pattern: &str,
+    format: XpubFormat,
+    stats_interval: Duration,
+    timeout: Option<Duration>,
+    output: Option<&Path>,
+) {
+    let mut file = output.map(open_output);
+    let search = Arc::new(search);
+
+    info!("Looking for an extended public key ({}) containing: '{}'", format, pattern);
+    if search.has_wallet() {
+        info!("Grinding the account index below {} of the given wallet", search.path());
     } else {
-        // Only check prefix
-        prefix_match
+        info!("Grinding new wallets, account {}/0'", search.path());
+    }
+    info!("Expected attempts: {:.0}", search.expected_attempts());
+    info!("Threads: {}", search.threads());
+    info!("Press Ctrl+C to stop...");
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let reporter = spawn_reporter(stop.clone(), stats_interval, {
+        let search = search.clone();
+        move || {
+            let (attempts, elapsed) = (search.attempts(), search.elapsed());
+            info!(
+                "Attempts: {}, Time: {:.0}s, Rate: {:.2} keys/s",
+                attempts,
+                elapsed.as_secs_f64(),
+                attempts as f64 / elapsed.as_secs_f64().max(1e-9)
+            );
+        }
+    });
+    // The first report after the timeout stops the search
+    let timer = timeout.map(|timeout| {
+        let search = search.clone();
+        spawn_reporter(stop.clone(), timeout, move || search.stop())
+    });
+
+    let found = search.run();
+    stop_reporter(&stop, reporter);
+    if let Some(timer) = timer {
+        stop_reporter(&stop, timer);
+    }
+    let found = found.unwrap_or_else(|e| fail(e));
+
+    let Some(found) = found else {
+        if search.is_exhausted() {
+            info!(
+                "Tried every account of the wallet after {} attempts in {:.2?}",
+                search.attempts(),
+                search.elapsed()
+            );
+            std::process::exit(exit::STOPPED);
+        }
+        info!("Timed out after {} attempts in {:.2?}", search.attempts(), search.elapsed());
+        std::process::exit(exit::TIMED_OUT);
+    };
+    println!("\n🎉 Found matching {} after {} attempts in {:.2?}!", format, found.attempts, found.elapsed);
+    let keys = SecretString::from(format!(
+        "Path:        {}\nPrivate key: {}\n{}",
+        found.path,
+        found.xprv.as_str(),
+        found.master_key.as_ref().map_or(String::new(), |master| format!("Master key:  {}\n", master.as_str()))
+    ));
+    println!("{}:        {}", format, highlight_within(&found.xpub, pattern));
+    print!("{}", keys.as_str());
+    let record = SecretString::from(format!("{}:        {}\n{}", format, found.xpub, keys.as_str()));
+    if let Some((file, path)) = file.as_mut().zip(output) {
+        let mut record = record.as_str().to_string();
+        record.push('\n');
+        match file.write_all(record.as_bytes()).and_then(|_| file.sync_all()) {
+            Ok(()) => info!("Saved to {}", path.display()),
+            Err(e) => error!("Failed to write to {}: {}", path.display(), e),
+        }
+        record.zeroize();
+    }
+}
+
+// The arguments of this run. Without any, on a terminal, they come from the
+// wizard instead, which is left without searching if it's called off.
+fn parse_args() -> Result<Args, clap::Error> {
+    if std::env::args_os().len() > 1 || !std::io::stdin().is_terminal() {
+        return Args::try_parse();
+    }
+    match wizard::ask(num_cpus::get_physical()) {
+        Ok(Some(args)) => Args::try_parse_from(args),
+        Ok(None) => std::process::exit(exit::SUCCESS),
+        Err(e) => fail(e),
     }
 }
 
 fn main() {
-    let args = Args::parse();
-    
-    // Prepare the patterns
-    let prefix_pattern = Arc::new(args.pattern.to_lowercase());
-    let suffix_pattern = Arc::new(args.suffix.map(|s| s.to_lowercase()));
-    
+    // Usage errors get the status of invalid options rather than clap's own
+    let args = parse_args().unwrap_or_else(|e| {
+        let _ = e.print();
+        std::process::exit(if e.use_stderr() { exit::INVALID_OPTIONS } else { exit::SUCCESS });
+    });
+    color::configure(args.color);
+    if let Err(e) = logging::init(args.verbosity, args.log_file.as_deref(), args.daemon) {
+        eprintln!("Error: {}", e);
+        std::process::exit(exit::FAILED);
+    }
+
+    match args.command {
+        Some(Command::Serve {
+            listen,
+            pattern,
+            suffix,
+            incremental,
+            address_type,
+            stats_interval,
+            timeout,
+            output,
+            no_show_key,
+            key_format,
+            log_matches,
+            notify,
+        }) => {
+            key_format.check(address_type).unwrap_or_else(|e| fail(CliError::InvalidOptions(e)));
+            let mut file = output.as_deref().map(|path| open_results(path, None));
+            let mut log = log_matches.as_deref().map(open_output);
+            let job = Job {
+                pattern: pattern.unwrap_or_default().to_lowercase(),
+                suffix: suffix.map(|s| s.to_lowercase()),
+                incremental,
+                address_type,
+            };
+            let (pattern, suffix) = (job.pattern.clone(), job.suffix.clone());
+            notify.report_panics(&pattern, suffix.as_deref());
+            let finish = distributed::serve(
+                &listen,
+                job,
+                Duration::from_secs(stats_interval),
+                timeout.map(Duration::from_secs),
+            )
+            .unwrap_or_else(|e| fail_search(&notify, &pattern, suffix.as_deref(), e));
+
+            match finish {
+                Finish::Found(found) => {
+                    println!(
+                        "\n🎉 A worker found a matching address after {} attempts across all workers in {:.2?}!",
+                        found.attempts, found.elapsed
+                    );
+                    if let Some((log, path)) = log.as_mut().zip(log_matches.as_deref()) {
+                        log_match(log, path, &found, key_format);
+                    }
+                    report_found(&found, file.as_mut(), !no_show_key, key_format);
+                    notify.found(&found, key_format);
+                    notify.finished(&pattern, suffix.as_deref(), Outcome::Found(&found));
+                }
+                Finish::TimedOut(progress) => {
+                    info!(
+                        "Timed out after {} attempts across all workers in {:.2?}",
+                        progress.attempts, progress.elapsed
+                    );
+                    notify.finished(&pattern, suffix.as_deref(), Outcome::TimedOut { progress, matches: 0 });
+                    std::process::exit(exit::TIMED_OUT);
+                }
+            }
+            return;
+        }
+        Some(Command::Bench { duration, threads, scheduling }) => {
+            let threads = configure_threads(threads, &scheduling);
+            run_bench(Duration::from_secs(duration), &threads);
+            return;
+        }
+        Some(Command::Estimate {
+            pattern,
+            suffix,
+            address_type,
+            incremental,
+            rate,
+            duration,
+            threads,
+            hash_backend,
+            energy,
+            scheduling,
+        }) => {
+            let threads = configure_threads(threads, &scheduling);
+            let config = SearchConfig {
+                pattern: pattern.unwrap_or_default().to_lowercase(),
+                suffix: suffix.map(|s| s.to_lowercase()),
+                address_type,
+                incremental,
+                hash_backend,
+                threads: Some(threads.count),
+                thread_start: threads.start,
+                ..Default::default()
+            };
+            run_estimate(config, rate, Duration::from_secs(duration), &energy);
+            return;
+        }
+        Some(Command::Verify { mut wif, address }) => {
+            let private_key = parse_private_key(&wif);
+            wif.zeroize();
+            let mut private_key = private_key.unwrap_or_else(|e| fail(CliError::InvalidOptions(e)));
+
+            let identified = identify_address(&private_key, &address);
+            private_key.inner.non_secure_erase();
+            match identified {
+                Some((AddressType::SilentPayment, network)) => {
+                    println!("OK: the key is the scan key of {} (silent-payment on {})", address, network)
+                }
+                Some((address_type, network)) => {
+                    println!("OK: the key controls {} ({} on {})", address, address_type, network)
+                }
+                None => fail(CliError::KeyMismatch(format!("The key does not control {}", address))),
+            }
+            return;
+        }
+        Some(Command::Export { input, format, dir }) => {
+            export::export(&input, format, dir.as_deref()).unwrap_or_else(|e| fail(e));
+            return;
+        }
+        Some(Command::Xpub { pattern, format, path, wallet, threads, stats_interval, timeout, output, scheduling }) => {
+            let threads = configure_threads(threads, &scheduling);
+            let master = wallet.then(|| read_master_key().unwrap_or_else(|e| fail(CliError::InvalidOptions(e))));
+            let search = XpubSearch::new(&pattern, format, path.as_deref(), master)
+                .and_then(|search| search.with_threads(threads.count, threads.start))
+                .unwrap_or_else(|e| fail(e));
+            run_xpub(
+                search,
+                &pattern,
+                format,
+                Duration::from_secs(stats_interval),
+                timeout.map(Duration::from_secs),
+                output.as_deref(),
+            );
+            return;
+        }
+        Some(Command::Completions { shell }) => {
+            let mut cmd = Args::command();
+            let name = cmd.get_name().to_string();
+            clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
+            return;
+        }
+        Some(Command::Manpage) => {
+            manpage::render(Args::command(), &mut std::io::stdout()).unwrap_or_else(|e| fail(e.to_string()));
+            return;
+        }
+        Some(Command::Worker { connect, threads, hash_backend, lock_memory, scheduling }) => {
+            let threads = configure_threads(threads, &scheduling);
+            configure_memory_lock(lock_memory);
+
+            match distributed::work(&connect, hash_backend, threads) {
+                Ok(Some(found)) => println!("\n🎉 Found matching address {}, sent it to the coordinator", found.address),
+                Ok(None) => info!("Search finished"),
+                Err(e) => fail(e),
+            }
+            return;
+        }
+        None => {}
+    }
+
+    // Only one thing can read stdin, and a daemon has none
+    if args.patterns.as_deref() == Some(Path::new("-")) && (args.bip85 || args.extra_entropy || args.daemon) {
+        fail(CliError::InvalidOptions(
+            "--patterns - reads stdin, so it can't be used with --bip85, --extra-entropy or --daemon".to_string(),
+        ));
+    }
+    // Before anything else, see `daemon`
+    if let Some(log_file) = args.log_file.as_deref().filter(|_| args.daemon) {
+        daemon::daemonize(log_file).unwrap_or_else(|e| fail(e));
+    }
+    let pid_file = args.pid_file.as_deref().map(|path| daemon::PidFile::create(path).unwrap_or_else(|e| fail(e)));
+    configure_memory_lock(args.lock_memory);
+    args.key_format.check(args.address_type).unwrap_or_else(|e| fail(CliError::InvalidOptions(e)));
+
+    if let Some(listen) = &args.serve {
+        let hash_backend = args.hash_backend.resolve().unwrap_or_else(|e| fail(CliError::InvalidOptions(e)));
+        let threads = configure_threads(args.threads, &args.scheduling);
+        let stats_interval = Duration::from_secs(args.stats_interval);
+        api::serve(listen, hash_backend, threads, stats_interval, args.key_format, args.allow_remote).unwrap_or_else(|e| fail(e));
+        return;
+    }
+
+    if let Some(listen) = &args.grpc {
+        serve_grpc(listen, &args);
+        return;
+    }
+
+    // Before any thread starts, so that all of them inherit the mask
+    let pausable = signals::block().is_ok();
+
     // Set the number of threads to use
-    if let Some(threads) = args.threads {
-        rayon::ThreadPoolBuilder::new()
-            .num_threads(threads)
-            .build_global()
-            .unwrap();
-    }
-    
-    println!("Starting Bitcoin bc1q vanity address generator");
-    println!("Looking for pattern: '{}' (after bc1q)", prefix_pattern);
-    if let Some(suffix) = &*suffix_pattern {
-        println!("And ending with: '{}'", suffix);
-    }
-    println!("Press Ctrl+C to stop...");
-    
-    // Initialize statistics
-    let stats = Arc::new(Mutex::new(Stats::new()));
-    let found: Arc<Mutex<Option<(String, String)>>> = Arc::new(Mutex::new(None));
-    let start_time = Instant::now();
+    let threads = configure_threads(args.threads, &args.scheduling);
+    let mut batch = read_job(&args).map(|batch| batch.unwrap_or_else(|e| fail(CliError::InvalidOptions(e))));
+    let mut output = match &mut batch {
+        Some(batch) => {
+            if let Some(path) = &args.output {
+                batch.open_outputs(path, |path| open_results(path, recipients(&args)));
+            }
+            None
+        }
+        None => args.output.as_deref().map(|path| open_results(path, recipients(&args))),
+    };
+    // Encrypted results would be pointless with the keys on the screen
+    let show_key = !args.no_show_key && recipients(&args).is_none();
+    let mut log = args.log_matches.as_deref().map(open_output);
+
+    // A BIP85 search takes its keys from the master key instead of a seed
+    let (seed, bip85) = if args.bip85 {
+        (None, Some(read_bip85(args.bip85_start, args.path.as_ref()).unwrap_or_else(|e| fail(CliError::InvalidOptions(e)))))
+    } else {
+        (Some(search_seed(&args).unwrap_or_else(|e| fail(e))), None)
+    };
+    let multisig = args
+        .threshold
+        .map(|threshold| Multisig::new(threshold, &args.cosigners))
+        .transpose()
+        .unwrap_or_else(|e| fail(CliError::InvalidOptions(e)));
+    let custom_matcher = args
+        .plugin
+        .as_deref()
+        .map(load_plugin)
+        .transpose()
+        .unwrap_or_else(|e| fail(CliError::InvalidOptions(e)));
+    let mut feed = args.patterns.as_deref().map(|path| Feed::open(path).unwrap_or_else(|e| fail(e)));
+    let first_fed = |feed: &mut Feed| {
+        feed.first().unwrap_or_else(|| {
+            fail(CliError::InvalidOptions(format!("No patterns given on {}", feed.name())))
+        })
+    };
+    // A job starts with its first pattern and adds the others, and so does a
+    // feed, as they arrive
+    let (mut pattern, mut suffix) = match (&batch, &mut feed) {
+        (Some(batch), _) => (batch.targets()[0].pattern.clone(), batch.targets()[0].suffix.clone()),
+        (None, Some(feed)) => first_fed(feed),
+        (None, None) => (args.pattern.clone().unwrap_or_default(), args.suffix.clone()),
+    };
+    let config = SearchConfig {
+        incremental: args.incremental,
+        hash_backend: args.hash_backend,
+        address_type: args.address_type,
+        seed: seed.clone(),
+        multisig,
+        bip85,
+        max_cpu: args.max_cpu,
+        threads: Some(threads.count),
+        thread_start: threads.start,
+        custom_matcher,
+        ..Default::default()
+    };
+    let search = loop {
+        let search = VanitySearch::new(SearchConfig {
+            pattern: pattern.clone(),
+            suffix: suffix.clone(),
+            ..config.clone()
+        });
+        match (search, &mut feed) {
+            // A fed pattern that can't match is skipped, like the ones after it
+            (Err(Error::InvalidPattern(e)), Some(feed)) => {
+                warn!("Skipping '{}': {}", batch::label(&pattern, suffix.as_deref()), e);
+                (pattern, suffix) = first_fed(feed);
+            }
+            (search, _) => break search.unwrap_or_else(|e| fail_search(&args.notify, &pattern, suffix.as_deref(), e)),
+        }
+    };
+    for target in batch.iter().flat_map(|batch| &batch.targets()[1..]) {
+        search
+            .add_pattern(&target.pattern, target.suffix.as_deref())
+            .unwrap_or_else(|e| fail_search(&args.notify, &target.pattern, target.suffix.as_deref(), e));
+    }
+    // Patterns that are already there are searched for from the start
+    if let Some(feed) = &mut feed {
+        take_fed(&search, feed);
+    }
+    let search = Arc::new(search);
+    args.notify.report_panics(search.pattern(), search.suffix());
+    let sensor = args.max_temp.map(|_| match &args.temp_sensor {
+        Some(path) => Sensor::at(path),
+        None => Sensor::find().unwrap_or_else(|| {
+            fail(CliError::InvalidOptions(
+                "No CPU temperature sensor found; name one with --temp-sensor".to_string(),
+            ))
+        }),
+    });
+    if let Some(sensor) = &sensor {
+        sensor.read().unwrap_or_else(|e| fail(CliError::InvalidOptions(e)));
+    }
+
+    info!("Starting Bitcoin vanity address generator");
+    match &batch {
+        Some(batch) => print_job(batch, search.address_type().prefix()),
+        None if feed.is_some() => print_fed(&search.patterns(), search.address_type().prefix()),
+        None => print_target(search.pattern(), search.suffix(), search.address_type().prefix()),
+    }
+    if let Some(multisig) = search.multisig() {
+        info!(
+            "Wallet: {}-of-{} sorted multisig with {} fixed cosigner keys",
+            multisig.threshold(),
+            multisig.cosigners().len() + 1,
+            multisig.cosigners().len()
+        );
+    }
+    if let Some(plugin) = &args.plugin {
+        info!("Custom matcher: {}", plugin.display());
+        if search.pattern().is_empty() && search.suffix().is_none() {
+            warn!("without a pattern, every candidate goes through the plugin, which is far slower");
+        }
+    }
+    if search.address_type() == AddressType::NodeId {
+        info!("Matching Lightning node IDs instead of addresses");
+    }
+    if search.address_type() == AddressType::SilentPayment {
+        info!("Grinding the scan key, with a fixed spend key on every thread");
+    }
+    if search.address_type() == AddressType::P2tr {
+        info!("Grinding the tweak of a fixed internal key on every thread");
+    } else if args.incremental {
+        info!("Using incremental key generation");
+    }
+    let continuous = args.continuous || args.daemon || batch.is_some();
+    if batch.is_some() {
+        match batch.as_ref().filter(|batch| batch.is_prioritized()) {
+            Some(batch) => info!("Searching until every priority {} pattern has its matches", batch.top_priority()),
+            None => info!("Searching until every pattern has its matches"),
+        }
+        if let Some(path) = args.job_file.as_deref().filter(|_| args.watch_job_file) {
+            info!("Watching {} for edits", path.display());
+        }
+    } else if continuous {
+        info!("Reporting every match until stopped");
+    }
+    if let Some(feed) = feed.as_ref().filter(|feed| !feed.has_ended()) {
+        info!("Taking in more patterns from {} as they arrive", feed.name());
+    }
+    // Enough to tell later where the keys of this run came from
+    if let Some(bip85) = search.bip85() {
+        match bip85.chain() {
+            Some(chain) => info!("Entropy source: keys at {}/i of master key {}", chain, bip85.fingerprint()),
+            None => info!("Entropy source: BIP85 from master key {}", bip85.fingerprint()),
+        }
+        if bip85.start() > 0 {
+            info!("Starting at child index {}", bip85.start());
+        }
+    } else if args.seed.is_some() {
+        info!("Entropy source: fixed seed");
+        warn!("keys from a seeded search are predictable; never send funds to them");
+    } else if args.extra_entropy {
+        info!("Entropy source: {} mixed with extra entropy", args.entropy_source);
+    } else {
+        info!("Entropy source: {}", args.entropy_source);
+    }
+    if let Some(seed) = &seed {
+        info!("Seed fingerprint: {}", seed.fingerprint());
+    }
+    info!("Hash backend: {}", search.hash_backend());
+    info!("Threads: {}", search.threads());
+    if search.max_cpu() < 100 {
+        info!("CPU limit: {}% per thread", search.max_cpu());
+    }
+    if let Some((max_temp, sensor)) = args.max_temp.zip(sensor.as_ref()) {
+        info!("Temperature limit: {}°C, read from {}", max_temp, sensor.path().display());
+    }
+    let history = args.stats_file.as_deref().map(|path| {
+        let prefix = search.address_type().prefix();
+        let patterns = search
+            .patterns()
+            .iter()
+            .map(|(pattern, suffix)| history::label(prefix, pattern, suffix.as_deref()))
+            .collect();
+        let session = Session::load(path, patterns).unwrap_or_else(|e| fail(CliError::InvalidOptions(e)));
+        session.save(search.progress()).unwrap_or_else(|e| fail(e));
+        session.report_start();
+        Arc::new(session)
+    });
+    if pausable {
+        info!("Send SIGUSR1 to pause and SIGUSR2 to resume (PID {})", std::process::id());
+    }
+    if !args.daemon {
+        info!("Press Ctrl+C to stop...");
+    }
+
+    // Dedicated reporter thread, so the search threads never print
+    let stop = Arc::new(AtomicBool::new(false));
     let stats_interval = Duration::from_secs(args.stats_interval);
-    let last_stats_print = Arc::new(Mutex::new(Instant::now()));
-    
-    // Start the search in parallel
-    rayon::scope(|s| {
-        for thread_id in 0..rayon::current_num_threads() {
-            let stats = Arc::clone(&stats);
-            let found = Arc::clone(&found);
-            let prefix_pattern = Arc::clone(&prefix_pattern);
-            let suffix_pattern = Arc::clone(&suffix_pattern);
-            let last_stats_print = Arc::clone(&last_stats_print);
-            
-            s.spawn(move |_| {
-                let secp = Secp256k1::new();
-                let batch_size = 1000; // Update stats after checking this many addresses
-                
-                while found.lock().unwrap().is_none() {
-                    // Generate address in batches for better performance
-                    for _ in 0..batch_size {
-                        let (key_pair, address) = generate_p2wpkh_address(&secp);
-                        
-                        if check_address(&address, &prefix_pattern, suffix_pattern.as_deref()) {
-                            let private_key = key_pair.secret_key().display_secret().to_string();
-                            let result = (private_key, address.clone());
-                            
-                            let mut found_guard = found.lock().unwrap();
-                            *found_guard = Some(result);
-                            break;
-                        }
-                    }
-                    
-                    // Update global stats occasionally
-                    let mut stats_guard = stats.lock().unwrap();
-                    stats_guard.increment(batch_size);
-                    drop(stats_guard);
-                    
-                    // Print stats at regular intervals
-                    let mut last_print = last_stats_print.lock().unwrap();
-                    if last_print.elapsed() >= stats_interval {
-                        stats.lock().unwrap().print();
-                        *last_print = Instant::now();
-                    }
-                    
-                    // Check if we need to stop
-                    if found.lock().unwrap().is_some() {
-                        break;
-                    }
+    let monitor = args.max_temp.zip(sensor).map(|(max_temp, sensor)| {
+        let ceiling = search.max_cpu();
+        thermal::spawn_monitor(Arc::clone(&search), sensor, f64::from(max_temp), ceiling, Arc::clone(&stop))
+    });
+    let reporter = {
+        let search = Arc::clone(&search);
+        let thermal = monitor.as_ref().map(|(_, thermal)| Arc::clone(thermal));
+        let history = history.clone();
+        let mut previous = ReportState::new(search.threads());
+        spawn_reporter(Arc::clone(&stop), stats_interval, move || {
+            // Nothing changes while paused
+            if search.is_paused() {
+                return;
+            }
+            match args.stats_format {
+                StatsFormat::Text => print_stats(&search, &mut previous, args.thread_stats, thermal.as_deref()),
+                StatsFormat::Jsonl => print_stats_json(&search, &mut previous, thermal.as_deref()),
+            }
+            if let Err(e) = history.as_ref().map_or(Ok(()), |history| history.save(search.progress())) {
+                warn!("{}", e);
+            }
+        })
+    };
+    if pausable {
+        signals::listen(Arc::clone(&search));
+    }
+    let control = args.control_socket.as_deref().map(|path| {
+        control::listen(path, Arc::clone(&search)).unwrap_or_else(|e| fail(CliError::InvalidOptions(e)))
+    });
+    let notifier = daemon::spawn_notifier(Arc::clone(&search), Arc::clone(&stop), stats_interval);
+
+    let mut on_found = |found: &Match, output: Option<&mut Output>| {
+        if let Some((log, path)) = log.as_mut().zip(args.log_matches.as_deref()) {
+            log_match(log, path, found, args.key_format);
+        }
+        println!("\n🎉 Found matching address after {} attempts in {:.2?}!", found.attempts, found.elapsed);
+        if search.patterns().len() > 1 {
+            let suffix = found.suffix.as_deref().map_or(String::new(), |suffix| format!("...{}", suffix));
+            println!("Matched:     {}{}{}", search.address_type().prefix(), found.pattern, suffix);
+        }
+        report_found(found, output, show_key, args.key_format);
+        args.notify.found(found, args.key_format);
+    };
+
+    // Start the search in parallel. Progress is printed by the reporter
+    // thread, the progress events only serve to check the time limit and
+    // the job file.
+    let timeout = args.timeout.map(Duration::from_secs);
+    let mut watch = args.job_file.as_deref().filter(|_| args.watch_job_file).map(batch::Watch::new);
+    let tick = if timeout.is_some() || watch.is_some() || feed.is_some() { Duration::from_secs(1) } else { Duration::MAX };
+    let mut found = None;
+    let mut matches = 0;
+    let mut timed_out = false;
+    daemon::sd_notify("READY=1");
+    let run = search.run_with(tick, |event| {
+        if let Event::Match(m) = event {
+            // Batches already under way can still find a pattern that has
+            // all its matches
+            if batch.as_ref().is_some_and(|batch| !batch.wants(&m)) {
+                return ControlFlow::Continue(());
+            }
+            matches += 1;
+            if let Some(history) = &history {
+                let prefix = search.address_type().prefix();
+                history.record_match(&history::label(prefix, &m.pattern, m.suffix.as_deref()));
+            }
+            if !continuous {
+                found = Some(m);
+                return ControlFlow::Break(());
+            }
+            let Some(batch) = &mut batch else {
+                on_found(&m, output.as_mut());
+                return ControlFlow::Continue(());
+            };
+            on_found(&m, batch.output(&m));
+            if let Some(target) = batch.record(&m) {
+                info!("Done with '{}'", target.label());
+                // Fails for the last pattern, which ends the job anyway
+                if search.remove_pattern(&target.pattern, target.suffix.as_deref()).is_ok() {
+                    debug!("Removed '{}' from the search", target.label());
+                }
+            }
+            info!("Progress:    {}", batch.progress());
+            if batch.is_complete() {
+                return ControlFlow::Break(());
+            }
+        }
+        if let Some((watch, batch)) = watch.as_mut().zip(batch.as_mut()) {
+            if watch.changed() {
+                reload_job(&args, batch, &search);
+                if batch.is_complete() {
+                    return ControlFlow::Break(());
                 }
-                
-                println!("Thread {} finished", thread_id);
-            });
+            }
         }
+        if let Some(feed) = &mut feed {
+            let ended = feed.has_ended();
+            for label in take_fed(&search, feed) {
+                info!("Added '{}' from {}", label, feed.name());
+            }
+            if feed.has_ended() && !ended {
+                info!("No more patterns from {}, searching for the {} given", feed.name(), search.patterns().len());
+            }
+        }
+        if timeout.is_some_and(|timeout| search.elapsed() >= timeout) {
+            timed_out = true;
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
     });
-    
-    // Print the result
-    let result = found.lock().unwrap().clone();
-    if let Some((private_key, address)) = result {
-        let elapsed = start_time.elapsed();
-        let attempts = stats.lock().unwrap().attempts;
-        
-        println!("\n🎉 Found matching address after {} attempts in {:.2?}!", attempts, elapsed);
-        println!("Address:     {}", address);
-        println!("Private key: {}", private_key);
+    daemon::sd_notify("STOPPING=1");
+    stop_reporter(&stop, reporter);
+    if let Some(notifier) = notifier {
+        stop_reporter(&stop, notifier);
+    }
+    if let Some((monitor, _)) = monitor {
+        stop_reporter(&stop, monitor);
+    }
+    drop(control);
+    drop(pid_file);
+    if let Err(e) = run {
+        fail_search(&args.notify, search.pattern(), search.suffix(), e);
+    }
+    if let Some(history) = &history {
+        if let Err(e) = history.save(search.progress()) {
+            error!("{}", e);
+        }
+        history.report_end(search.progress());
+    }
+    report_energy(&search, &args.energy);
+
+    if let Some(found) = found {
+        on_found(&found, output.as_mut());
+        args.notify.finished(search.pattern(), search.suffix(), Outcome::Found(&found));
+    } else if let Some(batch) = batch.as_ref().filter(|batch| batch.is_complete()) {
+        let progress = search.progress();
+        let (attempts, elapsed) = (progress.attempts, progress.elapsed);
+        if batch.has_everything() {
+            info!("Found every match of the job after {} attempts in {:.2?}", attempts, elapsed);
+        } else {
+            let top = batch.top_priority();
+            info!("Found every priority {} match of the job after {} attempts in {:.2?}", top, attempts, elapsed);
+        }
+        batch.print_summary();
+        args.notify.finished(search.pattern(), search.suffix(), Outcome::Completed { progress, matches });
+    } else if timed_out {
+        let progress = search.progress();
+        info!("Timed out after {} attempts in {:.2?}", progress.attempts, progress.elapsed);
+        if let Some(batch) = &batch {
+            batch.print_summary();
+        }
+        args.notify.finished(search.pattern(), search.suffix(), Outcome::TimedOut { progress, matches });
+        if matches == 0 {
+            std::process::exit(exit::TIMED_OUT);
+        }
+    } else {
+        // Nothing else ends the search: it was stopped through the control
+        // socket, or a BIP85 search ran out of indices
+        let progress = search.progress();
+        if search.is_exhausted() {
+            info!("Tried every BIP85 index in {:.2?}", progress.elapsed);
+        } else {
+            info!("Stopped after {} attempts in {:.2?}", progress.attempts, progress.elapsed);
+        }
+        if let Some(batch) = &batch {
+            batch.print_summary();
+        }
+        args.notify.finished(search.pattern(), search.suffix(), Outcome::Stopped { progress, matches });
+        if matches == 0 {
+            std::process::exit(exit::STOPPED);
+        }
     }
 }
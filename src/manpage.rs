@@ -0,0 +1,34 @@
+// Man page, rendered by clap_mangen from the command line definition, plus
+// the exit statuses, which clap doesn't know about
+
+use clap::Command;
+use clap_mangen::Man;
+use std::io::{self, Write};
+
+use crate::exit;
+
+/// Write the man page of `cmd` in roff
+pub fn render(cmd: Command, out: &mut dyn Write) -> io::Result<()> {
+    let man = Man::new(cmd);
+    man.render_title(out)?;
+    man.render_name_section(out)?;
+    man.render_synopsis_section(out)?;
+    man.render_description_section(out)?;
+    man.render_options_section(out)?;
+    man.render_subcommands_section(out)?;
+
+    writeln!(out, ".SH \"EXIT STATUS\"")?;
+    for (code, meaning) in [
+        (exit::SUCCESS, "A match was found (with \\-\\-continuous, at least one), or the command succeeded."),
+        (exit::FAILED, "A runtime error: a file, the network, the thread pool or the entropy source failed."),
+        (exit::TIMED_OUT, "\\-\\-timeout ran out without a match."),
+        (exit::INVALID_PATTERN, "A pattern can never match an address."),
+        (exit::INVALID_OPTIONS, "Unknown or contradicting options, or ones this build or CPU doesn't support."),
+        (exit::KEY_MISMATCH, "verify: the key does not control the address."),
+        (exit::STOPPED, "The search was stopped through the control socket, or tried every \\-\\-bip85 index or xpub \\-\\-wallet account, without a match."),
+    ] {
+        writeln!(out, ".TP\n{}\n{}", code, meaning)?;
+    }
+
+    man.render_version_section(out)
+}
@@ -0,0 +1,37 @@
+// Master keys of existing wallets
+//
+// Searches that build on a wallet the user already has (BIP85 keys, account
+// xpubs) take its BIP32 master key, given as a BIP39 mnemonic and passphrase
+// or as an xprv. `ExtendedPrivKey` doesn't wipe itself, so whoever holds one
+// erases it once done.
+
+use bitcoin::bip32::ExtendedPrivKey;
+use bitcoin::Network;
+use std::str::FromStr;
+use zeroize::Zeroize;
+
+/// The master key of a BIP39 mnemonic and passphrase (empty if none)
+pub fn master_key_from_mnemonic(mnemonic: &str, passphrase: &str) -> Result<ExtendedPrivKey, String> {
+    let mnemonic =
+        bip39::Mnemonic::parse_normalized(mnemonic.trim()).map_err(|e| format!("Invalid BIP39 mnemonic: {}", e))?;
+    let mut seed = mnemonic.to_seed_normalized(passphrase);
+    let master = ExtendedPrivKey::new_master(Network::Bitcoin, &seed);
+    seed.zeroize();
+    master.map_err(|e| format!("Invalid master key: {}", e))
+}
+
+/// A master key given as an xprv, which must not be a child key
+pub fn master_key_from_xprv(xprv: &str) -> Result<ExtendedPrivKey, String> {
+    let mut master = ExtendedPrivKey::from_str(xprv.trim()).map_err(|e| format!("Invalid xprv: {}", e))?;
+    if master.depth != 0 {
+        erase(&mut master);
+        return Err("Expected a master key, but this xprv is a child key".to_string());
+    }
+    Ok(master)
+}
+
+/// Overwrite the private key and chain code of `key`
+pub fn erase(key: &mut ExtendedPrivKey) {
+    key.private_key.non_secure_erase();
+    key.chain_code = [0u8; 32].into();
+}
@@ -0,0 +1,733 @@
+// Pattern matching directly on witness programs
+//
+// A segwit address is "bc1" followed by the bech32 data part: the witness
+// version ('q' for v0, 'p' for v1), the witness program split into five-bit
+// groups (32 for a 20-byte P2WPKH hash160, 52 for a 32-byte taproot key or
+// P2WSH script hash), and a 6 character checksum (bech32 for v0, bech32m for
+// v1). Instead of encoding every candidate to a string we translate the
+// patterns to 5-bit values once and compare them against the program groups,
+// only computing the checksum when a suffix needs it.
+//
+// Silent payment addresses (BIP 352) are bech32m too, with the "sp" HRP and
+// a 66-byte program: the scan key followed by the spend key. The search
+// grinds the scan key and keeps a spend key fixed, so patterns can cover the
+// scan key and the checksum, but not the spend key in between.
+//
+// Lightning node IDs are matched the same way, with hex digits of the
+// 33-byte compressed public key in place of 5-bit groups and no checksum.
+//
+// A search holds a list of matchers that can grow while it runs. Workers
+// keep their own snapshot of the list and only take the lock to refresh it
+// when the generation counter says it changed.
+//
+// The list is compiled into tries, one over the prefixes and one over the
+// suffixes of matchers without a prefix, read backwards. A candidate's
+// groups are computed once and walked down both, so checking it takes time
+// in the length of the address, whether there is one pattern or thousands.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::address::AddressType;
+
+/// The bech32 alphabet, indexed by 5-bit value
+pub const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+// Length of the bech32 checksum
+const CHECKSUM_LEN: usize = 6;
+// Characters after the prefix of the longest address: the 106 program
+// groups and checksum of a silent payment address
+const MAX_DATA_LEN: usize = 106 + CHECKSUM_LEN;
+// Program groups of a silent payment address that only depend on the scan
+// key, which takes up the first 264 bits
+const SCAN_KEY_GROUPS: usize = 33 * 8 / 5;
+
+// What the checksum is XORed with: 1 for bech32 (BIP 173), and a constant
+// for bech32m (BIP 350), which witness versions 1 and up use
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2bc830a3;
+
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+fn polymod_step(chk: u32, value: u8) -> u32 {
+    let top = chk >> 25;
+    let mut chk = ((chk & 0x1ffffff) << 5) ^ value as u32;
+    for (i, g) in GENERATOR.iter().enumerate() {
+        if (top >> i) & 1 == 1 {
+            chk ^= g;
+        }
+    }
+    chk
+}
+
+/// Compiled prefix/suffix patterns for one type of segwit address, or for
+/// node IDs
+#[derive(Clone)]
+pub struct Matcher {
+    address_type: AddressType,
+    // The patterns as given, for reporting which one matched
+    pattern: String,
+    suffix_pattern: Option<String>,
+    prefix: Vec<u8>,
+    suffix: Vec<u8>,
+    // Number of 5-bit groups the witness program is split into, or of hex
+    // digits of a node ID
+    program_groups: usize,
+    // None for node IDs, which have no checksum
+    checksum: Option<Checksum>,
+}
+
+#[derive(Clone, Copy)]
+struct Checksum {
+    // State after feeding the expanded HRP and witness version
+    base: u32,
+    constant: u32,
+}
+
+impl Matcher {
+    /// Compile the patterns, rejecting characters that can never appear in
+    /// an address of the type and patterns longer than the address itself
+    pub fn new(pattern: &str, suffix_pattern: Option<&str>, address_type: AddressType) -> Result<Self, String> {
+        let (hrp, version, program_len, checksum_const): (&[u8], u8, usize, u32) = match address_type {
+            AddressType::P2wpkh => (b"bc", 0, 20, BECH32_CONST),
+            AddressType::P2tr => (b"bc", 1, 32, BECH32M_CONST),
+            AddressType::P2wsh => (b"bc", 0, 32, BECH32_CONST),
+            AddressType::SilentPayment => (b"sp", 0, 66, BECH32M_CONST),
+            AddressType::NodeId => return Self::node_id(pattern, suffix_pattern),
+        };
+        let prefix = to_values(pattern)?;
+        let suffix = to_values(suffix_pattern.unwrap_or(""))?;
+
+        let program_groups = (program_len * 8).div_ceil(5);
+        let data_len = program_groups + CHECKSUM_LEN;
+        if prefix.len() > data_len || suffix.len() > data_len {
+            return Err(format!(
+                "Patterns can be at most {} characters long (the length of a {} address after the prefix)",
+                data_len,
+                address_type.prefix()
+            ));
+        }
+        if address_type == AddressType::SilentPayment {
+            check_silent_payment(pattern, &prefix, &suffix)?;
+        }
+
+        let mut checksum_base = 1;
+        for c in hrp {
+            checksum_base = polymod_step(checksum_base, c >> 5);
+        }
+        checksum_base = polymod_step(checksum_base, 0);
+        for c in hrp {
+            checksum_base = polymod_step(checksum_base, c & 31);
+        }
+        checksum_base = polymod_step(checksum_base, version);
+
+        Ok(Matcher {
+            address_type,
+            pattern: pattern.to_string(),
+            suffix_pattern: suffix_pattern.map(str::to_string),
+            prefix,
+            suffix,
+            program_groups,
+            checksum: Some(Checksum {
+                base: checksum_base,
+                constant: checksum_const,
+            }),
+        })
+    }
+
+    // Patterns for the hex digits of a 33-byte compressed public key, which
+    // always starts with 02 or 03
+    fn node_id(pattern: &str, suffix_pattern: Option<&str>) -> Result<Self, String> {
+        let prefix = to_hex_values(pattern)?;
+        let suffix = to_hex_values(suffix_pattern.unwrap_or(""))?;
+        let data_len = 66;
+        if prefix.len() > data_len || suffix.len() > data_len {
+            return Err(format!("Node ID patterns can be at most {} hex digits long", data_len));
+        }
+        if prefix.first().is_some_and(|&digit| digit != 0) || prefix.get(1).is_some_and(|&digit| digit != 2 && digit != 3) {
+            return Err(format!("Node IDs start with 02 or 03, so '{}' can never match", pattern));
+        }
+
+        Ok(Matcher {
+            address_type: AddressType::NodeId,
+            pattern: pattern.to_string(),
+            suffix_pattern: suffix_pattern.map(str::to_string),
+            prefix,
+            suffix,
+            program_groups: data_len,
+            checksum: None,
+        })
+    }
+
+    /// Check whether the address for this witness program (or the node ID
+    /// for this serialized public key) matches. Searches check all their
+    /// matchers at once with a `MatcherSet`; this is the plain version it is
+    /// tested against.
+    #[cfg(test)]
+    pub fn matches(&self, program: &[u8]) -> bool {
+        let mut data = [0u8; MAX_DATA_LEN];
+        match self.checksum {
+            Some(_) => to_groups(program, &mut data),
+            None => to_nibbles(program, &mut data),
+        }
+        let data = &mut data[..self.program_groups + self.checksum_len()];
+        if let Some(checksum) = self.checksum {
+            write_checksum(checksum, data, self.program_groups);
+        }
+        data.starts_with(&self.prefix) && data.ends_with(&self.suffix)
+    }
+
+    /// The prefix pattern
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The suffix pattern, if any
+    pub fn suffix(&self) -> Option<&str> {
+        self.suffix_pattern.as_deref()
+    }
+
+    /// Expected number of candidates per match: every pattern character
+    /// pins down 5 uniformly distributed bits, or 4 for the hex digits of a
+    /// node ID. Node IDs and silent payment addresses start with a public
+    /// key, so their first character is fixed and the second takes one of a
+    /// few values: 2 for node IDs (the key's parity), 8 for silent payments
+    /// (the parity and the first two bits of the x coordinate).
+    pub fn difficulty(&self) -> f64 {
+        let (base, key_start) = match self.address_type {
+            AddressType::NodeId => (16f64, 2.0),
+            AddressType::SilentPayment => (32f64, 8.0),
+            _ => return 32f64.powi((self.prefix.len() + self.suffix.len()) as i32),
+        };
+        let fixed = self.prefix.len().min(2);
+        let start = if fixed == 2 { key_start } else { 1.0 };
+        start * base.powi((self.prefix.len() - fixed + self.suffix.len()) as i32)
+    }
+
+    /// Check an already encoded address, e.g. one reported by another machine
+    pub fn matches_address(&self, address: &str) -> bool {
+        let data = match self.checksum {
+            Some(_) => address.strip_prefix(self.address_type.prefix()).map(to_values),
+            None => Some(to_hex_values(address)),
+        };
+        let data = match data {
+            Some(Ok(data)) if data.len() == self.program_groups + self.checksum_len() => data,
+            _ => return false,
+        };
+        data.starts_with(&self.prefix) && data.ends_with(&self.suffix)
+    }
+
+    fn checksum_len(&self) -> usize {
+        if self.checksum.is_some() {
+            CHECKSUM_LEN
+        } else {
+            0
+        }
+    }
+}
+
+fn write_checksum(checksum: Checksum, data: &mut [u8], program_groups: usize) {
+    let (program, data_checksum) = data.split_at_mut(program_groups);
+    let mut chk = checksum.base;
+    for &value in program.iter() {
+        chk = polymod_step(chk, value);
+    }
+    for _ in 0..CHECKSUM_LEN {
+        chk = polymod_step(chk, 0);
+    }
+    chk ^= checksum.constant;
+    for (i, value) in data_checksum.iter_mut().enumerate() {
+        *value = ((chk >> (5 * (5 - i))) & 31) as u8;
+    }
+}
+
+/// Matchers of one address type, compiled to be checked all at once
+pub struct MatcherSet {
+    matchers: Vec<Matcher>,
+    // Matchers by their prefix, and those without one by their suffix
+    // backwards
+    prefixes: Trie,
+    suffixes: Trie,
+    // Shared by all the matchers
+    program_groups: usize,
+    checksum: Option<Checksum>,
+}
+
+impl MatcherSet {
+    fn new(matchers: Vec<Matcher>) -> Self {
+        let first = matchers.first().expect("a search has at least one pattern");
+        let (program_groups, checksum) = (first.program_groups, first.checksum);
+        let mut prefixes = Trie::default();
+        let mut suffixes = Trie::default();
+        for (index, matcher) in matchers.iter().enumerate() {
+            match matcher.prefix.is_empty() {
+                false => prefixes.insert(matcher.prefix.iter().copied(), index),
+                true => suffixes.insert(matcher.suffix.iter().rev().copied(), index),
+            }
+        }
+        MatcherSet {
+            matchers,
+            prefixes,
+            suffixes,
+            program_groups,
+            checksum,
+        }
+    }
+
+    /// The matchers, in the order they were added
+    pub fn matchers(&self) -> &[Matcher] {
+        &self.matchers
+    }
+
+    /// The first matcher that the address for this witness program (or the
+    /// node ID for this serialized public key) matches, if any
+    pub fn find(&self, program: &[u8]) -> Option<&Matcher> {
+        let mut data = [0u8; MAX_DATA_LEN];
+        match self.checksum {
+            Some(_) => to_groups(program, &mut data),
+            None => to_nibbles(program, &mut data),
+        }
+        let checksum_len = if self.checksum.is_some() { CHECKSUM_LEN } else { 0 };
+        let data = &mut data[..self.program_groups + checksum_len];
+        // Only written once a pattern reaches into it
+        let mut has_checksum = self.checksum.is_none();
+        let mut first: Option<usize> = None;
+
+        // Prefixes usually fit inside the program groups, so most candidates
+        // leave the trie within a group or two, without touching the
+        // checksum
+        let mut node = &self.prefixes.nodes[0];
+        for position in 0..=data.len() {
+            for &index in &node.ends {
+                let suffix = &self.matchers[index].suffix;
+                if !suffix.is_empty() && !has_checksum {
+                    self.write_checksum(data, &mut has_checksum);
+                }
+                if data.ends_with(suffix) && first.is_none_or(|first| index < first) {
+                    first = Some(index);
+                }
+            }
+            if position == data.len() {
+                break;
+            }
+            if position >= self.program_groups && !has_checksum {
+                self.write_checksum(data, &mut has_checksum);
+            }
+            match self.prefixes.child(node, data[position]) {
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+
+        let mut node = &self.suffixes.nodes[0];
+        for position in (0..=data.len()).rev() {
+            if let Some(&index) = node.ends.first() {
+                if first.is_none_or(|first| index < first) {
+                    first = Some(index);
+                }
+            }
+            if position == 0 || node.is_leaf() {
+                break;
+            }
+            if !has_checksum {
+                self.write_checksum(data, &mut has_checksum);
+            }
+            match self.suffixes.child(node, data[position - 1]) {
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+        first.map(|index| &self.matchers[index])
+    }
+
+    fn write_checksum(&self, data: &mut [u8], has_checksum: &mut bool) {
+        if let Some(checksum) = self.checksum {
+            write_checksum(checksum, data, self.program_groups);
+        }
+        *has_checksum = true;
+    }
+}
+
+// Patterns by their values, one node per value read so far
+struct Trie {
+    // The root first
+    nodes: Vec<Node>,
+}
+
+impl Default for Trie {
+    fn default() -> Self {
+        Trie { nodes: vec![Node::default()] }
+    }
+}
+
+#[derive(Default)]
+struct Node {
+    // Index of the node after each 5-bit value, or hex digit; 0 for none, as
+    // the root is no one's child
+    children: [u32; 32],
+    // The matchers whose pattern ends here, in the order they were added
+    ends: Vec<usize>,
+}
+
+impl Node {
+    fn is_leaf(&self) -> bool {
+        self.children.iter().all(|&child| child == 0)
+    }
+}
+
+impl Trie {
+    fn insert(&mut self, values: impl Iterator<Item = u8>, index: usize) {
+        let mut node = 0;
+        for value in values {
+            node = match self.nodes[node].children[value as usize] {
+                0 => {
+                    self.nodes.push(Node::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children[value as usize] = child as u32;
+                    child
+                }
+                child => child as usize,
+            };
+        }
+        self.nodes[node].ends.push(index);
+    }
+
+    fn child(&self, node: &Node, value: u8) -> Option<&Node> {
+        match node.children[value as usize] {
+            0 => None,
+            child => Some(&self.nodes[child as usize]),
+        }
+    }
+}
+
+/// The matchers of a search
+pub struct Patterns {
+    matchers: RwLock<Arc<MatcherSet>>,
+    // Bumped on every change
+    generation: AtomicU64,
+}
+
+impl Patterns {
+    pub fn new(matcher: Matcher) -> Self {
+        Patterns {
+            matchers: RwLock::new(Arc::new(MatcherSet::new(vec![matcher]))),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// The current matchers, and the generation they belong to
+    pub fn snapshot(&self) -> (u64, Arc<MatcherSet>) {
+        let matchers = self.matchers.read().unwrap_or_else(PoisonError::into_inner);
+        (self.generation(), Arc::clone(&matchers))
+    }
+
+    /// Changes whenever matchers are added or removed
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Add a matcher, unless one with the same patterns exists already
+    pub fn add(&self, matcher: Matcher) -> Result<(), String> {
+        let mut matchers = self.matchers.write().unwrap_or_else(PoisonError::into_inner);
+        if matchers.matchers().iter().any(|m| m.pattern() == matcher.pattern() && m.suffix() == matcher.suffix()) {
+            return Err(format!("Already searching for '{}...{}'", matcher.pattern(), matcher.suffix().unwrap_or("")));
+        }
+        let mut updated = matchers.matchers().to_vec();
+        updated.push(matcher);
+        *matchers = Arc::new(MatcherSet::new(updated));
+        self.generation.fetch_add(1, Ordering::Release);
+        Ok(())
+    }
+
+    /// Remove the matcher with these patterns. The last one stays, so there
+    /// is always something to search for.
+    pub fn remove(&self, pattern: &str, suffix: Option<&str>) -> Result<(), String> {
+        let mut matchers = self.matchers.write().unwrap_or_else(PoisonError::into_inner);
+        let Some(index) = matchers.matchers().iter().position(|m| m.pattern() == pattern && m.suffix() == suffix) else {
+            return Err(format!("Not searching for '{}...{}'", pattern, suffix.unwrap_or("")));
+        };
+        if matchers.matchers().len() == 1 {
+            return Err("Can't remove the last pattern of a search".to_string());
+        }
+        let mut updated = matchers.matchers().to_vec();
+        updated.remove(index);
+        *matchers = Arc::new(MatcherSet::new(updated));
+        self.generation.fetch_add(1, Ordering::Release);
+        Ok(())
+    }
+
+    /// Swap all matchers for these at once
+    pub fn replace(&self, matchers: Vec<Matcher>) -> Result<(), String> {
+        if matchers.is_empty() {
+            return Err("A search needs at least one pattern".to_string());
+        }
+        *self.matchers.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(MatcherSet::new(matchers));
+        self.generation.fetch_add(1, Ordering::Release);
+        Ok(())
+    }
+
+    /// The matcher for an already encoded address, if any
+    pub fn find_address(&self, address: &str) -> Option<(String, Option<String>)> {
+        let (_, matchers) = self.snapshot();
+        let matcher = matchers.matchers().iter().find(|m| m.matches_address(address))?;
+        Some((matcher.pattern().to_string(), matcher.suffix().map(str::to_string)))
+    }
+
+    /// Expected number of candidates per match of any of the patterns
+    pub fn difficulty(&self) -> f64 {
+        let (_, matchers) = self.snapshot();
+        1.0 / matchers.matchers().iter().map(|m| 1.0 / m.difficulty()).sum::<f64>()
+    }
+}
+
+// Split the program into five-bit groups, most significant bits first, with
+// the last group padded with zero bits
+fn to_groups(program: &[u8], out: &mut [u8]) {
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    let mut idx = 0;
+    for &byte in program {
+        acc = (acc << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out[idx] = ((acc >> bits) & 31) as u8;
+            idx += 1;
+        }
+    }
+    if bits > 0 {
+        out[idx] = ((acc << (5 - bits)) & 31) as u8;
+    }
+}
+
+// Reject silent payment patterns that depend on the fixed spend key, or that
+// contradict the start of every scan key, 0x02 or 0x03
+fn check_silent_payment(pattern: &str, prefix: &[u8], suffix: &[u8]) -> Result<(), String> {
+    if prefix.len() > SCAN_KEY_GROUPS {
+        return Err(format!(
+            "Silent payment patterns can be at most {} characters long, the rest of the address depends on the spend key",
+            SCAN_KEY_GROUPS
+        ));
+    }
+    if suffix.len() > CHECKSUM_LEN {
+        return Err(format!(
+            "Silent payment suffixes can be at most {} characters long (the checksum), the characters before it depend on the spend key",
+            CHECKSUM_LEN
+        ));
+    }
+    // 0x02 and 0x03 start with the groups 00000 and 010xx or 011xx
+    if prefix.first().is_some_and(|&value| value != 0) || prefix.get(1).is_some_and(|&value| !(8..16).contains(&value)) {
+        return Err(format!(
+            "Silent payment addresses start with sp1qq followed by one of \"{}\", so '{}' can never match",
+            std::str::from_utf8(&CHARSET[8..16]).unwrap(),
+            pattern
+        ));
+    }
+    Ok(())
+}
+
+// Split the serialized public key into hex digits, most significant first
+fn to_nibbles(program: &[u8], out: &mut [u8]) {
+    for (pair, &byte) in out.chunks_exact_mut(2).zip(program) {
+        pair[0] = byte >> 4;
+        pair[1] = byte & 15;
+    }
+}
+
+// Translate a node ID pattern to the values of its hex digits
+fn to_hex_values(pattern: &str) -> Result<Vec<u8>, String> {
+    pattern
+        .chars()
+        .map(|c| {
+            c.to_digit(16)
+                .map(|v| v as u8)
+                .ok_or_else(|| format!("Invalid character '{}' in pattern '{}': node IDs are hex", c, pattern))
+        })
+        .collect()
+}
+
+// Translate a pattern to bech32 5-bit values
+fn to_values(pattern: &str) -> Result<Vec<u8>, String> {
+    pattern
+        .chars()
+        .map(|c| {
+            CHARSET
+                .iter()
+                .position(|&x| x as char == c)
+                .map(|v| v as u8)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid character '{}' in pattern '{}': bech32 addresses only use \"{}\"",
+                        c,
+                        pattern,
+                        std::str::from_utf8(CHARSET).unwrap()
+                    )
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::{hash160, sha256, Hash};
+    use bitcoin::key::TweakedPublicKey;
+    use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+    use bitcoin::{Address, Network, ScriptBuf};
+
+    fn public_key(n: u8) -> PublicKey {
+        PublicKey::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&[n; 32]).unwrap())
+    }
+
+    // Witness programs next to the addresses the bitcoin crate encodes them as
+    fn vectors() -> Vec<(AddressType, Vec<u8>, String)> {
+        let mut vectors = Vec::new();
+        for n in 1..=3 {
+            let key = public_key(n);
+            let hash = hash160::Hash::hash(&key.serialize());
+            let address = Address::p2wpkh(&bitcoin::PublicKey::new(key), Network::Bitcoin).unwrap();
+            vectors.push((AddressType::P2wpkh, hash.to_byte_array().to_vec(), address.to_string()));
+
+            let output_key = TweakedPublicKey::dangerous_assume_tweaked(key.x_only_public_key().0);
+            let address = Address::p2tr_tweaked(output_key, Network::Bitcoin);
+            vectors.push((AddressType::P2tr, output_key.serialize().to_vec(), address.to_string()));
+
+            let script = ScriptBuf::from_bytes(vec![0x51, 0x21, n, 0xae]);
+            let hash = sha256::Hash::hash(script.as_bytes());
+            let address = Address::p2wsh(&script, Network::Bitcoin);
+            vectors.push((AddressType::P2wsh, hash.to_byte_array().to_vec(), address.to_string()));
+        }
+        vectors
+    }
+
+    #[test]
+    fn patterns_match_encoded_addresses() {
+        for (address_type, program, address) in vectors() {
+            let data = address.strip_prefix(address_type.prefix()).unwrap();
+            // Every length of prefix and suffix, the longer ones reaching
+            // into the checksum
+            for len in 0..=data.len() {
+                let (prefix, suffix) = (&data[..len], &data[data.len() - len..]);
+                assert!(Matcher::new(prefix, None, address_type).unwrap().matches(&program), "{} {}", address, prefix);
+                assert!(Matcher::new("", Some(suffix), address_type).unwrap().matches(&program), "{} {}", address, suffix);
+                let both = Matcher::new(&data[..len / 2], Some(&data[data.len() - len / 2..]), address_type).unwrap();
+                assert!(both.matches(&program), "{} {}", address, len);
+            }
+        }
+    }
+
+    // Another bech32 character than `c`
+    fn next(c: u8) -> char {
+        CHARSET[(CHARSET.iter().position(|&x| x == c).unwrap() + 1) % 32] as char
+    }
+
+    #[test]
+    fn patterns_reject_other_addresses() {
+        for (address_type, program, address) in vectors() {
+            let data = address.strip_prefix(address_type.prefix()).unwrap();
+            for len in 1..=data.len() {
+                // The same patterns with their innermost character swapped
+                let prefix = format!("{}{}", &data[..len - 1], next(data.as_bytes()[len - 1]));
+                assert!(!Matcher::new(&prefix, None, address_type).unwrap().matches(&program), "{} {}", address, prefix);
+                let start = data.len() - len;
+                let suffix = format!("{}{}", next(data.as_bytes()[start]), &data[start + 1..]);
+                let matcher = Matcher::new("", Some(&suffix), address_type).unwrap();
+                assert!(!matcher.matches(&program), "{} {}", address, suffix);
+            }
+        }
+    }
+
+    // Check `MatcherSet::find` against trying its matchers one by one, on
+    // programs of `len` bytes: both find the first in the list that matches
+    fn check_matcher_set(address_type: AddressType, len: usize, patterns: &[(&str, Option<&str>)]) {
+        let mut matchers: Vec<Matcher> =
+            patterns.iter().map(|&(pattern, suffix)| Matcher::new(pattern, suffix, address_type).unwrap()).collect();
+        for _ in 0..2 {
+            let set = MatcherSet::new(matchers.clone());
+            let mut found = 0;
+            for n in 0u32..2000 {
+                let mut program = sha256::Hash::hash(&n.to_le_bytes()).to_byte_array().repeat(3);
+                program.truncate(len);
+                // Node IDs and silent payments hold public keys
+                if matches!(address_type, AddressType::NodeId | AddressType::SilentPayment) {
+                    for key in (0..len).step_by(33) {
+                        program[key] = 2 + (n % 2) as u8;
+                    }
+                }
+                let expected = matchers.iter().position(|m| m.matches(&program));
+                let index = set.find(&program).map(|m| set.matchers().iter().position(|x| std::ptr::eq(x, m)).unwrap());
+                assert_eq!(index, expected, "{:?} {}", address_type, hex::encode(&program));
+                found += usize::from(index.is_some());
+            }
+            assert!(found > 100, "{:?}: only {} matches", address_type, found);
+            // Again with the overlapping patterns the other way round
+            matchers.reverse();
+        }
+    }
+
+    #[test]
+    fn matcher_sets_find_the_first_matching_pattern() {
+        let shared = [
+            ("qq", None),
+            ("q", None),
+            ("qqz", None),
+            ("", Some("q")),
+            ("z", Some("z")),
+            ("qz", None),
+            ("", Some("zq")),
+            ("q", Some("p")),
+            ("z", None),
+            ("", Some("p")),
+        ];
+        check_matcher_set(AddressType::P2wpkh, 20, &shared);
+        check_matcher_set(AddressType::P2wsh, 32, &shared);
+        let taproot = [("p", None), ("p", Some("q")), ("pq", None), ("", Some("qq")), ("", Some("q")), ("x", None)];
+        check_matcher_set(AddressType::P2tr, 32, &taproot);
+        let node_id = [("02a", None), ("0", Some("f")), ("02", None), ("03", None), ("", Some("ff"))];
+        check_matcher_set(AddressType::NodeId, 33, &node_id);
+        let silent_payment = [("qg", None), ("q", Some("c")), ("", Some("c")), ("qgz", None), ("qf", None), ("", Some("zc"))];
+        check_matcher_set(AddressType::SilentPayment, 66, &silent_payment);
+    }
+
+    #[test]
+    fn difficulty_counts_the_values_a_pattern_pins_down() {
+        let difficulty = |pattern: &str, suffix: Option<&str>, address_type| {
+            Matcher::new(pattern, suffix, address_type).unwrap().difficulty()
+        };
+        assert_eq!(difficulty("qq", None, AddressType::P2wpkh), 1024.0);
+        assert_eq!(difficulty("q", Some("qq"), AddressType::P2tr), 32768.0);
+        assert_eq!(difficulty("0", None, AddressType::NodeId), 1.0);
+        assert_eq!(difficulty("02", None, AddressType::NodeId), 2.0);
+        assert_eq!(difficulty("02a", Some("f"), AddressType::NodeId), 512.0);
+        assert_eq!(difficulty("q", None, AddressType::SilentPayment), 1.0);
+        assert_eq!(difficulty("qg", None, AddressType::SilentPayment), 8.0);
+        assert_eq!(difficulty("qgq", Some("q"), AddressType::SilentPayment), 8192.0);
+    }
+
+    #[test]
+    fn silent_payment_difficulty_matches_the_scan_keys() {
+        // The second character of every scan key is one of 8, evenly
+        let secp = Secp256k1::new();
+        let matcher = Matcher::new("qg", None, AddressType::SilentPayment).unwrap();
+        let spend_key = public_key(1).serialize();
+        let hits = (1..=4000u32)
+            .filter(|n| {
+                let mut secret = [0u8; 32];
+                secret[28..].copy_from_slice(&n.to_be_bytes());
+                let scan_key = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&secret).unwrap());
+                matcher.matches(&[scan_key.serialize().as_slice(), &spend_key].concat())
+            })
+            .count();
+        assert!((400..600).contains(&hits), "{} of 4000", hits);
+    }
+
+    #[test]
+    fn addresses_are_checked_after_encoding() {
+        for (address_type, _, address) in vectors() {
+            let data = address.strip_prefix(address_type.prefix()).unwrap();
+            let matcher = Matcher::new(&data[..3], Some(&data[data.len() - 3..]), address_type).unwrap();
+            assert!(matcher.matches_address(&address));
+            assert!(!matcher.matches_address(&address[..address.len() - 1]));
+        }
+    }
+}
@@ -0,0 +1,212 @@
+// Sorted multisig witness scripts with fixed cosigner keys
+//
+// A P2WSH address commits to the SHA-256 of its witness script. For a
+// `sortedmulti` descriptor that script is
+//
+//     OP_m <key> ... <key> OP_n OP_CHECKMULTISIG
+//
+// with the keys sorted by their serialization (BIP 67). The cosigners' keys
+// stay fixed while the search grinds our own, so every candidate only needs
+// its key inserted at the right position before the script is hashed.
+
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::{Address, Network, ScriptBuf};
+
+use crate::descriptor::descriptor_checksum;
+use crate::hash::HashBackend;
+
+// Most keys CHECKMULTISIG accepts, and descriptors allow in wsh()
+const MAX_KEYS: usize = 20;
+
+const OP_CHECKMULTISIG: u8 = 0xae;
+// Push of a 33 byte compressed key
+const OP_PUSHBYTES_33: u8 = 0x21;
+
+/// An m-of-n sorted multisig wallet in which every key but ours is fixed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Multisig {
+    threshold: usize,
+    // Serialized compressed keys, in script order
+    cosigners: Vec<[u8; 33]>,
+}
+
+impl Multisig {
+    /// A wallet of our key and `cosigners`, spendable with `threshold` of
+    /// their signatures
+    pub fn new(threshold: usize, cosigners: &[PublicKey]) -> Result<Self, String> {
+        if cosigners.is_empty() {
+            return Err("A multisig wallet needs at least one cosigner key".to_string());
+        }
+        let keys = cosigners.len() + 1;
+        if keys > MAX_KEYS {
+            return Err(format!(
+                "A multisig wallet can have at most {} keys, ours and {} cosigners",
+                MAX_KEYS,
+                MAX_KEYS - 1
+            ));
+        }
+        if threshold == 0 || threshold > keys {
+            return Err(format!("The threshold must be between 1 and {}, the number of keys", keys));
+        }
+
+        let mut serialized: Vec<[u8; 33]> = cosigners.iter().map(PublicKey::serialize).collect();
+        serialized.sort_unstable();
+        if let Some(pair) = serialized.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(format!("Cosigner key {} is given twice", hex::encode(pair[0])));
+        }
+
+        Ok(Multisig {
+            threshold,
+            cosigners: serialized,
+        })
+    }
+
+    /// Number of signatures needed to spend
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// The cosigners' compressed public keys, in hex
+    pub fn cosigners(&self) -> Vec<String> {
+        self.cosigners.iter().map(hex::encode).collect()
+    }
+
+    /// The witness script of the wallet with our key `public_key`
+    pub fn witness_script(&self, public_key: &PublicKey) -> ScriptBuf {
+        let mut script = Vec::new();
+        self.write_script(&public_key.serialize(), &mut script);
+        ScriptBuf::from(script)
+    }
+
+    /// The P2WSH address of the wallet with our key `public_key`
+    pub fn address(&self, public_key: &PublicKey, network: Network) -> String {
+        Address::p2wsh(&self.witness_script(public_key), network).to_string()
+    }
+
+    /// The wallet's `wsh(sortedmulti(...))` descriptor, with its checksum.
+    /// `key` stands for our key: its WIF, or its public key for a watch-only
+    /// wallet.
+    pub fn descriptor(&self, key: &str) -> String {
+        let mut descriptor = format!("wsh(sortedmulti({},{}", self.threshold, key);
+        for cosigner in &self.cosigners {
+            descriptor.push(',');
+            descriptor.push_str(&hex::encode(cosigner));
+        }
+        descriptor.push_str("))");
+
+        let checksum = descriptor_checksum(&descriptor).expect("keys only use descriptor characters");
+        descriptor.push('#');
+        descriptor.push_str(&checksum);
+        descriptor
+    }
+
+    // The witness program for our serialized key: the SHA-256 of the witness
+    // script, built in `script` to reuse its allocation
+    #[inline]
+    pub(crate) fn program(&self, key: &[u8; 33], script: &mut Vec<u8>, hash_backend: HashBackend) -> [u8; 32] {
+        self.write_script(key, script);
+        hash_backend.sha256(script)
+    }
+
+    fn write_script(&self, key: &[u8; 33], script: &mut Vec<u8>) {
+        script.clear();
+        push_number(script, self.threshold);
+
+        let position = self.cosigners.partition_point(|cosigner| cosigner < key);
+        let (before, after) = self.cosigners.split_at(position);
+        for entry in before.iter().chain([key]).chain(after) {
+            script.push(OP_PUSHBYTES_33);
+            script.extend_from_slice(entry);
+        }
+
+        push_number(script, self.cosigners.len() + 1);
+        script.push(OP_CHECKMULTISIG);
+    }
+}
+
+/// Parse a cosigner's compressed public key, given as 66 hex digits
+pub fn parse_cosigner(key: &str) -> Result<PublicKey, String> {
+    if key.len() != 66 {
+        return Err(format!("Invalid cosigner key '{}', expected a compressed public key of 66 hex digits", key));
+    }
+    key.parse().map_err(|e| format!("Invalid cosigner key '{}': {}", key, e))
+}
+
+// Minimal push of a key count: OP_1 to OP_16, or a one byte push above that
+fn push_number(script: &mut Vec<u8>, n: usize) {
+    match n {
+        1..=16 => script.push(0x50 + n as u8),
+        _ => script.extend_from_slice(&[1, n as u8]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::blockdata::opcodes::all::OP_CHECKMULTISIG;
+    use bitcoin::blockdata::script::Builder;
+    use bitcoin::hashes::{sha256, Hash};
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+
+    // n·G: 1·G is 0279be..., 2·G 02c604..., 3·G 02f930...
+    fn key(n: u8) -> PublicKey {
+        let mut secret = [0u8; 32];
+        secret[31] = n;
+        PublicKey::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&secret).unwrap())
+    }
+
+    #[test]
+    fn keys_are_sorted_into_the_script() {
+        let multisig = Multisig::new(2, &[key(3), key(1)]).unwrap();
+        assert_eq!(multisig.cosigners(), [key(1).to_string(), key(3).to_string()]);
+
+        // Our key sorts between the two cosigners
+        let script = multisig.witness_script(&key(2));
+        let expected = format!("5221{}21{}21{}53ae", key(1), key(2), key(3));
+        assert_eq!(hex::encode(script.as_bytes()), expected);
+
+        let mut buffer = Vec::new();
+        let program = multisig.program(&key(2).serialize(), &mut buffer, HashBackend::Portable);
+        assert_eq!(program, sha256::Hash::hash(script.as_bytes()).to_byte_array());
+    }
+
+    #[test]
+    fn addresses_match_the_sorted_multisig_script() {
+        let multisig = Multisig::new(2, &[key(3), key(1)]).unwrap();
+        let script = Builder::new()
+            .push_int(2)
+            .push_key(&bitcoin::PublicKey::new(key(1)))
+            .push_key(&bitcoin::PublicKey::new(key(2)))
+            .push_key(&bitcoin::PublicKey::new(key(3)))
+            .push_int(3)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script();
+        assert_eq!(multisig.address(&key(2), Network::Bitcoin), Address::p2wsh(&script, Network::Bitcoin).to_string());
+    }
+
+    #[test]
+    fn descriptors_list_our_key_first_with_a_checksum() {
+        let multisig = Multisig::new(1, &[key(3), key(1)]).unwrap();
+        let descriptor = multisig.descriptor(&key(2).to_string());
+        let body = format!("wsh(sortedmulti(1,{},{},{}))", key(2), key(1), key(3));
+        assert_eq!(descriptor, format!("{}#{}", body, descriptor_checksum(&body).unwrap()));
+    }
+
+    #[test]
+    fn large_wallets_push_their_key_count() {
+        let cosigners: Vec<PublicKey> = (1..=16).map(key).collect();
+        let script = Multisig::new(17, &cosigners).unwrap().witness_script(&key(17));
+        let bytes = script.as_bytes();
+        assert_eq!(&bytes[..2], [1, 17]);
+        assert_eq!(&bytes[bytes.len() - 3..], [1, 17, OP_CHECKMULTISIG.to_u8()]);
+    }
+
+    #[test]
+    fn invalid_wallets_are_refused() {
+        assert!(Multisig::new(1, &[]).is_err());
+        assert!(Multisig::new(0, &[key(1)]).is_err());
+        assert!(Multisig::new(3, &[key(1)]).is_err());
+        assert!(Multisig::new(1, &[key(1), key(1)]).is_err());
+        assert!(Multisig::new(1, &(1..=20).map(key).collect::<Vec<_>>()).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+// Notifications about matches, for long unattended runs
+//
+// A webhook gets a JSON description of the match for automation; Telegram
+// and Discord get a short human readable message. Neither message ever
+// contains the private key, only the webhook can opt in to it. Email is
+// sent once, when the search ends: with a match, on timeout or on an error.
+//
+// Delivery is best effort: a failed notification is reported on stderr but
+// never affects the search or the saved result.
+
+use clap::Args;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{SmtpTransport, Transport};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::warn;
+
+use vanity_address_rust::{Match, Progress, SecretString};
+
+use crate::keys::KeyFormat;
+
+// How long to wait for a notification endpoint before giving up
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const TELEGRAM_API: &str = "https://api.telegram.org";
+
+/// Where to send notifications
+#[derive(Args, Debug, Clone)]
+pub struct Notify {
+    /// POST a JSON description of every match to this URL
+    #[clap(long, value_name = "URL")]
+    pub webhook_url: Option<String>,
+
+    /// Include the private key in the webhook payload
+    #[clap(long, requires = "webhook_url")]
+    pub webhook_include_key: bool,
+
+    /// Telegram bot token to send a message with on every match
+    #[clap(long, value_name = "TOKEN", requires = "telegram_chat_id")]
+    pub telegram_token: Option<String>,
+
+    /// Telegram chat to send the message to
+    #[clap(long, value_name = "ID", requires = "telegram_token")]
+    pub telegram_chat_id: Option<String>,
+
+    /// Discord webhook URL to post a message to on every match
+    #[clap(long, value_name = "URL")]
+    pub discord_webhook: Option<String>,
+
+    /// Email when the search ends, using the SMTP settings in this TOML file
+    #[clap(long, value_name = "FILE", value_parser = EmailConfig::load)]
+    pub email_config: Option<EmailConfig>,
+}
+
+/// How a search ended
+pub enum Outcome<'a> {
+    Found(&'a Match),
+    TimedOut { progress: Progress, matches: usize },
+    Stopped { progress: Progress, matches: usize },
+    Completed { progress: Progress, matches: usize },
+    Failed(&'a str),
+}
+
+/// SMTP server and addresses for email notifications
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EmailConfig {
+    host: String,
+    port: Option<u16>,
+    #[serde(default)]
+    tls: Security,
+    username: Option<String>,
+    password: Option<SecretString>,
+    from: String,
+    to: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum Security {
+    /// TLS from the start, usually on port 465
+    Tls,
+    /// Upgrade a plain connection with STARTTLS, usually on port 587
+    #[default]
+    Starttls,
+    /// No encryption, only for a relay on a trusted network
+    None,
+}
+
+impl Notify {
+    /// Send a notification about `found` through every configured channel,
+    /// with its key in `key_format` if the webhook gets it
+    pub fn found(&self, found: &Match, key_format: KeyFormat) {
+        if let Some(url) = &self.webhook_url {
+            let mut payload = json!({
+                "event": "match",
+                "address": found.address,
+                "pattern": found.pattern,
+                "suffix": found.suffix,
+                "attempts": found.attempts,
+                "elapsed_secs": found.elapsed.as_secs_f64(),
+            });
+            if let (true, Value::Object(fields)) = (self.webhook_include_key, &mut payload) {
+                fields.extend(key_format.json_fields(found));
+            }
+            report("webhook", post_json(url, &payload));
+        }
+
+        let text = message(found);
+        if let (Some(token), Some(chat_id)) = (&self.telegram_token, &self.telegram_chat_id) {
+            let url = format!("{}/bot{}/sendMessage", TELEGRAM_API, token);
+            let result = post_json(&url, &json!({ "chat_id": chat_id, "text": text }));
+            // The token is part of the URL, keep it out of the warning
+            report("Telegram", result.map_err(|e| e.replace(token.as_str(), "<token>")));
+        }
+        if let Some(url) = &self.discord_webhook {
+            report("Discord", post_json(url, &json!({ "content": text })));
+        }
+    }
+
+    /// Send the email notification about how the search ended, if configured
+    pub fn finished(&self, pattern: &str, suffix: Option<&str>, outcome: Outcome) {
+        if let Some(email) = &self.email_config {
+            let (subject, body) = summary(pattern, suffix, outcome);
+            report("email", email.send(&subject, body));
+        }
+    }
+
+    /// Email about panics, which end the search without going through
+    /// [`Notify::finished`]
+    pub fn report_panics(&self, pattern: &str, suffix: Option<&str>) {
+        let Some(email) = self.email_config.clone() else { return };
+        let (pattern, suffix) = (pattern.to_string(), suffix.map(str::to_string));
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            default_hook(info);
+            let (subject, body) = summary(&pattern, suffix.as_deref(), Outcome::Failed(&info.to_string()));
+            report("email", email.send(&subject, body));
+        }));
+    }
+}
+
+impl EmailConfig {
+    /// Read and check the settings in `path`
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let config: EmailConfig = toml::from_str(&text).map_err(|e| format!("Invalid email config {}: {}", path, e))?;
+        if config.username.is_some() != config.password.is_some() {
+            return Err(format!("Invalid email config {}: set both username and password, or neither", path));
+        }
+        for address in [&config.from, &config.to] {
+            address
+                .parse::<Mailbox>()
+                .map_err(|e| format!("Invalid email address '{}' in {}: {}", address, path, e))?;
+        }
+        Ok(config)
+    }
+
+    fn send(&self, subject: &str, body: String) -> Result<(), String> {
+        let email = lettre::Message::builder()
+            .from(self.from.parse().map_err(|e| format!("{}", e))?)
+            .to(self.to.parse().map_err(|e| format!("{}", e))?)
+            .subject(subject)
+            .body(body)
+            .map_err(|e| e.to_string())?;
+
+        let mut transport = match self.tls {
+            Security::Tls => SmtpTransport::relay(&self.host).map_err(|e| e.to_string())?,
+            Security::Starttls => SmtpTransport::starttls_relay(&self.host).map_err(|e| e.to_string())?,
+            Security::None => SmtpTransport::builder_dangerous(&self.host),
+        }
+        .timeout(Some(TIMEOUT));
+        if let Some(port) = self.port {
+            transport = transport.port(port);
+        }
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            transport = transport.credentials(Credentials::new(username.clone(), password.to_string()));
+        }
+
+        transport.build().send(&email).map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+// Subject and body of the email about how a search ended
+fn summary(pattern: &str, suffix: Option<&str>, outcome: Outcome) -> (String, String) {
+    let mut body = String::new();
+    if !pattern.is_empty() {
+        body += &format!("Pattern: {}\n", pattern);
+    }
+    if let Some(suffix) = suffix {
+        body += &format!("Suffix:  {}\n", suffix);
+    }
+
+    let subject = match outcome {
+        Outcome::Found(found) => {
+            body += &format!(
+                "Address: {}\nAttempts: {}\nElapsed: {:.2?}\n",
+                found.address, found.attempts, found.elapsed
+            );
+            format!("Vanity search found {}", found.address)
+        }
+        Outcome::TimedOut { progress, matches } => {
+            body += &format!(
+                "Attempts: {}\nElapsed: {:.2?}\nMatches: {}\n",
+                progress.attempts, progress.elapsed, matches
+            );
+            format!("Vanity search for {} timed out", target(pattern, suffix))
+        }
+        Outcome::Stopped { progress, matches } => {
+            body += &format!(
+                "Attempts: {}\nElapsed: {:.2?}\nMatches: {}\n",
+                progress.attempts, progress.elapsed, matches
+            );
+            format!("Vanity search for {} was stopped", target(pattern, suffix))
+        }
+        Outcome::Completed { progress, matches } => {
+            body += &format!(
+                "Attempts: {}\nElapsed: {:.2?}\nMatches: {}\n",
+                progress.attempts, progress.elapsed, matches
+            );
+            "Vanity search job finished".to_string()
+        }
+        Outcome::Failed(error) => {
+            body += &format!("Error: {}\n", error);
+            format!("Vanity search for {} failed", target(pattern, suffix))
+        }
+    };
+    (subject, body)
+}
+
+fn message(found: &Match) -> String {
+    format!(
+        "🎉 Found {} matching {} after {} attempts in {:.2?}",
+        found.address,
+        target(&found.pattern, found.suffix.as_deref()),
+        found.attempts,
+        found.elapsed
+    )
+}
+
+// The patterns as they'd appear in an address, e.g. 'c0ffee...xmas'
+fn target(pattern: &str, suffix: Option<&str>) -> String {
+    match suffix {
+        Some(suffix) => format!("'{}...{}'", pattern, suffix),
+        None => format!("'{}'", pattern),
+    }
+}
+
+fn post_json(url: &str, payload: &serde_json::Value) -> Result<(), String> {
+    ureq::post(url)
+        .timeout(TIMEOUT)
+        .send_json(payload)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn report(channel: &str, result: Result<(), String>) {
+    if let Err(e) = result {
+        warn!("failed to send the {} notification: {}", channel, e);
+    }
+}
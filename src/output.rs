@@ -0,0 +1,183 @@
+// Files that found keys are saved to
+//
+// Records are appended and synced as soon as a key is found. With
+// --encrypt-to, the file is an age file encrypted to the given recipients
+// instead, so the keys can only be read with their identities, e.g. when the
+// search runs on a rented server; --encrypt-to-pgp does the same with OpenPGP
+// public keys, through the gpg command, as an armored message. Encrypted files
+// can't be appended to, so every new record rewrites the file with all records
+// so far: into a temporary file next to it, which then replaces it. That way
+// the file is complete and decrypts at any time, even if the search is killed
+// halfway through a write.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use age::x25519::Recipient;
+use vanity_address_rust::SecretString;
+use zeroize::Zeroize;
+
+/// Parse an age recipient (age1...), for clap
+pub fn parse_recipient(recipient: &str) -> Result<Recipient, String> {
+    recipient.parse().map_err(|e| format!("invalid age recipient: {}", e))
+}
+
+/// Who encrypted output files are for
+pub enum Recipients {
+    /// age public keys
+    Age(Vec<Recipient>),
+    /// Files with OpenPGP public keys, armored or binary
+    Pgp(Vec<PathBuf>),
+}
+
+impl Recipients {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Recipients::Age(recipients) => {
+                let encryptor = age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+                    .map_err(|e| e.to_string())?;
+                let mut encrypted = Vec::new();
+                let mut writer = encryptor.wrap_output(&mut encrypted).map_err(|e| e.to_string())?;
+                writer.write_all(plaintext).and_then(|_| writer.finish()).map_err(|e| e.to_string())?;
+                Ok(encrypted)
+            }
+            Recipients::Pgp(keys) => gpg_encrypt(keys, plaintext),
+        }
+    }
+}
+
+// Encrypt with gpg, straight to the keys in the files rather than the keyring,
+// and trusting them as given. The plaintext only ever goes through a pipe.
+fn gpg_encrypt(keys: &[PathBuf], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let mut command = Command::new("gpg");
+    command.args(["--batch", "--quiet", "--armor", "--trust-model", "always", "--encrypt"]);
+    for key in keys {
+        command.arg("--recipient-file").arg(key);
+    }
+    let mut gpg = command
+        .args(["--output", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run gpg: {}", e))?;
+    // Written from another thread, as gpg may fill the output pipe before it
+    // has read all of its input
+    let mut stdin = gpg.stdin.take().expect("stdin is piped");
+    let plaintext = plaintext.to_vec();
+    let writer = std::thread::spawn(move || {
+        let mut plaintext = plaintext;
+        let written = stdin.write_all(&plaintext);
+        plaintext.zeroize();
+        written
+    });
+    let output = gpg.wait_with_output().map_err(|e| format!("Failed to run gpg: {}", e))?;
+    let written = writer.join().expect("the writer thread doesn't panic");
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("gpg failed: {}", message.trim().replace('\n', "; ")));
+    }
+    written.map_err(|e| format!("Failed to run gpg: {}", e))?;
+    Ok(output.stdout)
+}
+
+/// An output file, in the clear or encrypted
+pub struct Output {
+    path: PathBuf,
+    target: Target,
+}
+
+enum Target {
+    Plain(File),
+    Encrypted {
+        recipients: Recipients,
+        // Everything written so far, in the clear
+        records: SecretString,
+    },
+}
+
+impl Output {
+    /// Open `path` for appending. Only the owner may read it.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = private_options()
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        Ok(Output {
+            path: path.to_path_buf(),
+            target: Target::Plain(file),
+        })
+    }
+
+    /// Write records to `path` encrypted to `recipients`. The file must not
+    /// exist yet, as it could not be decrypted to add to it.
+    pub fn encrypted(path: &Path, recipients: Recipients) -> Result<Self, String> {
+        if path.exists() {
+            return Err(format!(
+                "{} already exists; encrypted files can't be added to, so pick a new one",
+                path.display()
+            ));
+        }
+        let mut output = Output {
+            path: path.to_path_buf(),
+            target: Target::Encrypted {
+                recipients,
+                records: SecretString::new(""),
+            },
+        };
+        // Fail before the search if the file can't be written
+        output.append("")?;
+        Ok(output)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        matches!(self.target, Target::Encrypted { .. })
+    }
+
+    /// Add `record` to the file and sync it to disk
+    pub fn append(&mut self, record: &str) -> Result<(), String> {
+        let written = match &mut self.target {
+            Target::Plain(file) => file.write_all(record.as_bytes()).and_then(|_| file.sync_all()),
+            Target::Encrypted { recipients, records } => {
+                *records = SecretString::from(format!("{}{}", records.as_str(), record));
+                return replace(&self.path, recipients, records);
+            }
+        };
+        written.map_err(|e| format!("Failed to write to {}: {}", self.path.display(), e))
+    }
+}
+
+// Encrypt `records` into a temporary file and move it over `path`
+fn replace(path: &Path, recipients: &Recipients, records: &SecretString) -> Result<(), String> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+
+    let encrypted = recipients
+        .encrypt(records.as_bytes())
+        .map_err(|e| format!("Failed to encrypt {}: {}", path.display(), e))?;
+    let written = private_options()
+        .write(true)
+        .truncate(true)
+        .open(&temporary)
+        .and_then(|mut file| file.write_all(&encrypted).and_then(|_| file.sync_all()))
+        .and_then(|_| std::fs::rename(&temporary, path));
+    written.map_err(|e| {
+        let _ = std::fs::remove_file(&temporary);
+        format!("Failed to write to {}: {}", path.display(), e)
+    })
+}
+
+fn private_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.create(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+}
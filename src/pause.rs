@@ -0,0 +1,55 @@
+// Pausing the search threads
+//
+// Workers check the flag with a relaxed load between batches, just like the
+// stop flag, and only take the lock once it is set, so a running search pays
+// nothing for it. Paused workers sleep on a condition variable until the
+// search is resumed or stopped.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, PoisonError};
+
+pub struct Pause {
+    paused: AtomicBool,
+    lock: Mutex<()>,
+    changed: Condvar,
+}
+
+impl Pause {
+    pub fn new() -> Self {
+        Pause {
+            paused: AtomicBool::new(false),
+            lock: Mutex::new(()),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Pause or resume, returning whether that changed anything
+    pub fn set(&self, paused: bool) -> bool {
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        let changed = self.paused.swap(paused, Ordering::Relaxed) != paused;
+        self.changed.notify_all();
+        changed
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Wake every waiting worker, so they notice that `stop` was set. Taking
+    /// the lock makes sure no worker is between checking `stop` and waiting.
+    pub fn wake(&self) {
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        self.changed.notify_all();
+    }
+
+    /// Block while paused, returning early once `stop` is set
+    pub fn wait(&self, stop: &AtomicBool) {
+        if !self.is_paused() {
+            return;
+        }
+        let mut guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        while self.is_paused() && !stop.load(Ordering::Relaxed) {
+            guard = self.changed.wait(guard).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
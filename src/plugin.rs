@@ -0,0 +1,138 @@
+// Custom matchers
+//
+// Patterns cover the start and end of an address. For anything else, a
+// `CustomMatcher` sees every candidate that matches the patterns, as its
+// address and witness program, and decides whether it's a match, with a
+// score. Library users implement the trait directly; with the `plugins`
+// feature, `WasmMatcher` runs one compiled to WebAssembly, so the tool can
+// take new criteria without a rebuild.
+//
+// A WebAssembly plugin imports nothing and exports:
+//
+//   memory                                    its linear memory
+//   buffer() -> i32                           where the candidate goes, 256 bytes
+//   score(address_len: i32, program_len: i32) -> i32
+//
+// For every candidate, the address (ASCII) is written to the buffer,
+// directly followed by the program, and `score` returns a positive score for
+// a match, or 0 or less for none. Instances are pooled across the search
+// threads, and a call may land on any of them, so a plugin should be a pure
+// function of the candidate.
+
+use std::fmt::Debug;
+
+/// Match logic of its own, applied to every candidate that matches the
+/// search's patterns
+///
+/// Matchers run on the search threads, and unlike patterns need the
+/// encoded address of every candidate they see: with an empty pattern, that
+/// costs a bech32 encoding per candidate on top of the matcher itself.
+pub trait CustomMatcher: Debug + Send + Sync {
+    /// Score the candidate with this `address` and witness `program` (the
+    /// serialized public key, for node IDs; the scan and spend keys, for
+    /// silent payments): `None` if it isn't a match
+    fn score(&self, address: &str, program: &[u8]) -> Option<u32>;
+}
+
+#[cfg(feature = "plugins")]
+pub use wasm_plugin::WasmMatcher;
+
+#[cfg(feature = "plugins")]
+mod wasm_plugin {
+    use std::sync::{Mutex, PoisonError};
+    use wasmi::{Engine, Linker, Memory, Module, Store, TypedFunc};
+
+    use super::CustomMatcher;
+
+    // Bytes of address and program a plugin's buffer must hold: a silent
+    // payment address and its two keys
+    const BUFFER_LEN: usize = 256;
+
+    /// A [`CustomMatcher`] compiled to WebAssembly, run in an interpreter
+    pub struct WasmMatcher {
+        name: String,
+        engine: Engine,
+        module: Module,
+        // Idle instances; a search thread takes one for every call
+        instances: Mutex<Vec<Instance>>,
+    }
+
+    struct Instance {
+        store: Store<()>,
+        memory: Memory,
+        buffer: usize,
+        score: TypedFunc<(i32, i32), i32>,
+    }
+
+    impl WasmMatcher {
+        /// Compile the plugin in `wasm`, called `name` in errors, and check
+        /// its exports
+        pub fn new(name: &str, wasm: &[u8]) -> Result<Self, String> {
+            let engine = Engine::default();
+            let module = Module::new(&engine, wasm).map_err(|e| format!("Invalid plugin {}: {}", name, e))?;
+            let matcher = WasmMatcher {
+                name: name.to_string(),
+                engine,
+                module,
+                instances: Mutex::new(Vec::new()),
+            };
+            let instance = matcher.instantiate()?;
+            matcher.release(instance);
+            Ok(matcher)
+        }
+
+        /// Load the plugin in the .wasm file at `path`
+        pub fn load(path: &std::path::Path) -> Result<Self, String> {
+            let wasm = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            Self::new(&path.display().to_string(), &wasm)
+        }
+
+        fn instantiate(&self) -> Result<Instance, String> {
+            let fail = |e: &dyn std::fmt::Display| format!("Plugin {} can't be used: {}", self.name, e);
+            let mut store = Store::new(&self.engine, ());
+            let instance = Linker::<()>::new(&self.engine)
+                .instantiate(&mut store, &self.module)
+                .and_then(|instance| instance.start(&mut store))
+                .map_err(|e| fail(&e))?;
+            let memory = instance.get_memory(&store, "memory").ok_or_else(|| fail(&"it exports no memory"))?;
+            let buffer = instance.get_typed_func::<(), i32>(&store, "buffer").map_err(|e| fail(&e))?;
+            let score = instance.get_typed_func::<(i32, i32), i32>(&store, "score").map_err(|e| fail(&e))?;
+
+            let buffer = buffer.call(&mut store, ()).map_err(|e| fail(&e))? as u32 as usize;
+            if buffer.checked_add(BUFFER_LEN).is_none_or(|end| end > memory.data(&store).len()) {
+                return Err(fail(&format!("its buffer doesn't fit {} bytes into its memory", BUFFER_LEN)));
+            }
+            Ok(Instance { store, memory, buffer, score })
+        }
+
+        fn release(&self, instance: Instance) {
+            self.instances.lock().unwrap_or_else(PoisonError::into_inner).push(instance);
+        }
+    }
+
+    impl CustomMatcher for WasmMatcher {
+        // A plugin that traps or runs out of memory is broken, and would
+        // otherwise never match, so it takes the search down instead
+        fn score(&self, address: &str, program: &[u8]) -> Option<u32> {
+            let idle = self.instances.lock().unwrap_or_else(PoisonError::into_inner).pop();
+            let mut instance = idle.map_or_else(|| self.instantiate(), Ok).unwrap_or_else(|e| panic!("{}", e));
+
+            let Instance { store, memory, buffer, score } = &mut instance;
+            let data = &mut memory.data_mut(&mut *store)[*buffer..*buffer + BUFFER_LEN];
+            data[..address.len()].copy_from_slice(address.as_bytes());
+            data[address.len()..address.len() + program.len()].copy_from_slice(program);
+            let result = score
+                .call(&mut *store, (address.len() as i32, program.len() as i32))
+                .unwrap_or_else(|e| panic!("Plugin {} failed: {}", self.name, e));
+
+            self.release(instance);
+            u32::try_from(result).ok().filter(|&score| score > 0)
+        }
+    }
+
+    impl std::fmt::Debug for WasmMatcher {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("WasmMatcher").field("name", &self.name).finish_non_exhaustive()
+        }
+    }
+}
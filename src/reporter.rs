@@ -0,0 +1,167 @@
+// Periodic progress reporting on a dedicated thread
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Call `report` every `interval` on a dedicated thread until `stop` is set,
+/// so workers never have to coordinate printing. Unpark the returned thread
+/// after setting `stop` to have it exit immediately.
+pub fn spawn_reporter<F>(stop: Arc<AtomicBool>, interval: Duration, mut report: F) -> JoinHandle<()>
+where
+    F: FnMut() + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut next_report = Instant::now() + interval;
+        while !stop.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            if now >= next_report {
+                report();
+                next_report = now + interval;
+            } else {
+                thread::park_timeout(next_report - now);
+            }
+        }
+    })
+}
+
+/// Stop a reporter thread and wait for it to exit
+pub fn stop_reporter(stop: &AtomicBool, reporter: JoinHandle<()>) {
+    stop.store(true, Ordering::Relaxed);
+    reporter.thread().unpark();
+    // A panic in the reporter was already printed, and the search result
+    // doesn't depend on it
+    if reporter.join().is_err() {
+        warn!("the stats reporter stopped early");
+    }
+}
+
+/// Sliding windows the reported rates are averaged over
+pub const SHORT_WINDOW: Duration = Duration::from_secs(10);
+pub const LONG_WINDOW: Duration = Duration::from_secs(60);
+
+/// "10s: 123.45, 60s: 120.00, avg: 110.00 addr/s", leaving out windows
+/// longer than the run so far
+pub fn format_rates(rates: &RateWindow, attempts: u64, elapsed: Duration) -> String {
+    let mut parts = Vec::new();
+    for window in [SHORT_WINDOW, LONG_WINDOW] {
+        if let Some(rate) = rates.recent_rate(window, elapsed) {
+            parts.push(format!("{}s: {:.2}", window.as_secs(), rate));
+        }
+    }
+    parts.push(format!("avg: {:.2}", attempts as f64 / elapsed.as_secs_f64()));
+    format!("{} addr/s", parts.join(", "))
+}
+
+/// Recent attempt counts, for rates over a sliding window rather than the
+/// whole run, which would hide throttling or pauses
+pub struct RateWindow {
+    // (elapsed, attempts), oldest first
+    samples: VecDeque<(Duration, u64)>,
+    span: Duration,
+}
+
+impl RateWindow {
+    /// Keep enough samples for rates over windows up to `span`
+    pub fn new(span: Duration) -> Self {
+        RateWindow {
+            samples: VecDeque::new(),
+            span,
+        }
+    }
+
+    pub fn record(&mut self, elapsed: Duration, attempts: u64) {
+        self.samples.push_back((elapsed, attempts));
+        // Keep one sample at or beyond the span, so the longest window
+        // always has a starting point
+        while self.samples.len() > 2 && elapsed - self.samples[1].0 >= self.span {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Rate over about the last `window`: from the oldest sample inside it,
+    /// or the one just before if the window holds only the latest sample.
+    /// `None` until there are two samples.
+    pub fn rate(&self, window: Duration) -> Option<f64> {
+        let &(now, attempts) = self.samples.back()?;
+        let start = self
+            .samples
+            .iter()
+            .position(|&(at, _)| now - at <= window)
+            .filter(|&i| i + 1 < self.samples.len())
+            .or_else(|| self.samples.len().checked_sub(2))?;
+
+        let (at, before) = self.samples[start];
+        let secs = (now - at).as_secs_f64();
+        (secs > 0.0).then(|| (attempts - before) as f64 / secs)
+    }
+
+    /// Like `rate`, but `None` while the run is shorter than `window`, when
+    /// the rate wouldn't cover the whole window yet
+    pub fn recent_rate(&self, window: Duration, elapsed: Duration) -> Option<f64> {
+        (elapsed >= window).then(|| self.rate(window)).flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `rate` attempts per second, sampled every `step` seconds from `from`
+    // to `to`, starting at `attempts`
+    fn run(rates: &mut RateWindow, from: u64, to: u64, step: u64, attempts: u64, rate: u64) -> u64 {
+        let mut total = attempts;
+        for secs in (from..=to).step_by(step as usize) {
+            if secs > from {
+                total += rate * step;
+            }
+            rates.record(Duration::from_secs(secs), total);
+        }
+        total
+    }
+
+    #[test]
+    fn rates_need_two_samples() {
+        let mut rates = RateWindow::new(LONG_WINDOW);
+        assert_eq!(rates.rate(SHORT_WINDOW), None);
+        rates.record(Duration::from_secs(1), 100);
+        assert_eq!(rates.rate(SHORT_WINDOW), None);
+        rates.record(Duration::from_secs(3), 500);
+        assert_eq!(rates.rate(SHORT_WINDOW), Some(200.0));
+    }
+
+    #[test]
+    fn rates_follow_the_window() {
+        let mut rates = RateWindow::new(LONG_WINDOW);
+        let attempts = run(&mut rates, 0, 60, 5, 0, 100);
+        let attempts = run(&mut rates, 60, 80, 5, attempts, 10);
+        assert_eq!(attempts, 6200);
+
+        assert_eq!(rates.rate(SHORT_WINDOW), Some(10.0));
+        // From 20s, when 2000 attempts were made
+        assert_eq!(rates.rate(LONG_WINDOW), Some(70.0));
+        // Samples older than the span are dropped, but one is kept at it
+        assert_eq!(rates.samples.front().map(|&(at, _)| at), Some(Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn sparse_samples_use_the_one_before_the_window() {
+        let mut rates = RateWindow::new(LONG_WINDOW);
+        rates.record(Duration::ZERO, 0);
+        rates.record(Duration::from_secs(30), 3000);
+        assert_eq!(rates.rate(SHORT_WINDOW), Some(100.0));
+    }
+
+    #[test]
+    fn short_runs_leave_out_long_windows() {
+        let mut rates = RateWindow::new(LONG_WINDOW);
+        let attempts = run(&mut rates, 0, 20, 5, 0, 100);
+        let elapsed = Duration::from_secs(20);
+        assert_eq!(rates.recent_rate(LONG_WINDOW, elapsed), None);
+        assert_eq!(format_rates(&rates, attempts, elapsed), "10s: 100.00, avg: 100.00 addr/s");
+        assert_eq!(format_rates(&rates, attempts, Duration::from_secs(5)), "avg: 400.00 addr/s");
+    }
+}
@@ -0,0 +1,109 @@
+// Randomness for candidate keys
+//
+// By default every search thread draws its keys from its own `ThreadRng`.
+// A search configured with a `Seed` instead gives every thread a ChaCha20
+// stream of its own, keyed by the seed and numbered by the thread's index,
+// so the candidates each thread checks are fully determined by the seed.
+// That is also how other entropy sources and user supplied entropy are used:
+// hashed into a seed that nobody knows (see `entropy`).
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::rand::rngs::ThreadRng;
+use bitcoin::secp256k1::rand::{self, CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use zeroize::Zeroize;
+
+/// A 32 byte seed that every candidate key of a search is derived from
+///
+/// Anyone who knows the seed can recreate every key of the search, so a
+/// seed chosen by hand is only fit for tests and bug reports, never for keys
+/// that will hold funds. Wiped from memory when dropped; `Debug` output is
+/// redacted.
+#[derive(Clone)]
+pub struct Seed([u8; 32]);
+
+impl Seed {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Seed(bytes)
+    }
+
+    /// Parse a seed of exactly 64 hex digits
+    pub fn from_hex(hex: &str) -> Result<Self, String> {
+        let mut bytes = [0u8; 32];
+        hex::decode_to_slice(hex, &mut bytes).map_err(|e| format!("Invalid seed, expected 64 hex digits: {}", e))?;
+        Ok(Seed(bytes))
+    }
+
+    /// Short public identifier of the seed: the first 4 bytes of its
+    /// SHA-256, in hex. Tells seeds apart without revealing them.
+    pub fn fingerprint(&self) -> String {
+        hex::encode(&sha256::Hash::hash(&self.0)[..4])
+    }
+}
+
+impl std::fmt::Debug for Seed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Seed(..)")
+    }
+}
+
+impl Drop for Seed {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Random number generator of one search thread
+pub enum KeyRng {
+    Os(ThreadRng),
+    Seeded(Box<ChaCha20Rng>),
+}
+
+impl KeyRng {
+    /// The generator for the thread with index `thread_id`: the OS backed
+    /// `ThreadRng`, or that thread's stream of `seed`
+    pub fn new(seed: Option<&Seed>, thread_id: usize) -> Self {
+        match seed {
+            None => KeyRng::Os(rand::thread_rng()),
+            Some(seed) => {
+                let mut rng = ChaCha20Rng::from_seed(seed.0);
+                rng.set_stream(thread_id as u64);
+                KeyRng::Seeded(Box::new(rng))
+            }
+        }
+    }
+}
+
+impl RngCore for KeyRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            KeyRng::Os(rng) => rng.next_u32(),
+            KeyRng::Seeded(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            KeyRng::Os(rng) => rng.next_u64(),
+            KeyRng::Seeded(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            KeyRng::Os(rng) => rng.fill_bytes(dest),
+            KeyRng::Seeded(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match self {
+            KeyRng::Os(rng) => rng.try_fill_bytes(dest),
+            KeyRng::Seeded(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+// Both generators are cryptographically secure; a seeded one is only as
+// secret as its seed
+impl CryptoRng for KeyRng {}
@@ -0,0 +1,140 @@
+// Worker threads of a search
+//
+// The scheduler starts the workers of a search, each on a thread of its own,
+// and joins them once the search ends. It also holds everything the workers
+// share with whoever controls the search: the stop flag, the pause and the
+// throttle, and the attempt counter of every worker. A search brings the work
+// as a batch function per worker, which the scheduler calls in a loop: it
+// waits while paused, counts what every batch checked, rests as the throttle
+// asks, and ends once the search is stopped or a batch checks nothing (the
+// search has run out of candidates).
+//
+// Every search owns its scheduler, so searches with different thread counts
+// can run side by side, and nothing needs setting up for the whole process.
+// Without the `parallel` feature there are no threads to start: the caller
+// runs the one worker's loop itself.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "parallel")]
+use crate::error::Error;
+use crate::pause::Pause;
+use crate::stats::{Instant, Stats};
+use crate::throttle::Throttle;
+
+/// Called on every search thread as it starts, with the index of its worker,
+/// e.g. to pin it to a core
+#[derive(Clone)]
+#[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+pub struct ThreadStart(Arc<dyn Fn(usize) + Send + Sync>);
+
+impl ThreadStart {
+    pub fn new(start: impl Fn(usize) + Send + Sync + 'static) -> Self {
+        ThreadStart(Arc::new(start))
+    }
+}
+
+impl fmt::Debug for ThreadStart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ThreadStart(..)")
+    }
+}
+
+pub struct Scheduler {
+    workers: usize,
+    #[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+    thread_start: Option<ThreadStart>,
+    pub stats: Stats,
+    pub stop: AtomicBool,
+    pub pause: Pause,
+    pub throttle: Throttle,
+}
+
+impl Scheduler {
+    /// A scheduler for `workers` workers, which may work `max_cpu` percent of
+    /// the time
+    pub fn new(workers: usize, thread_start: Option<ThreadStart>, max_cpu: u8) -> Self {
+        Scheduler {
+            workers,
+            thread_start,
+            stats: Stats::new(workers),
+            stop: AtomicBool::new(false),
+            pause: Pause::new(),
+            throttle: Throttle::new(max_cpu),
+        }
+    }
+
+    /// The number of threads the machine can run at once
+    #[cfg(feature = "parallel")]
+    pub fn available_threads() -> usize {
+        std::thread::available_parallelism().map_or(1, |threads| threads.get())
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Ask every worker to stop after its current batch, paused ones included
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.pause.wake();
+    }
+
+    /// Run the batches of `worker` until the search is stopped or a batch
+    /// checks nothing. `batch` returns the number of candidates it checked.
+    pub fn work(&self, worker: usize, mut batch: impl FnMut() -> usize) {
+        let mut owed = Duration::ZERO;
+        while !self.is_stopped() {
+            self.pause.wait(&self.stop);
+            let started = Instant::now();
+            let checked = batch();
+            if checked == 0 {
+                break;
+            }
+
+            // Publish this worker's progress for the reporter
+            self.stats.increment(worker, checked as u64);
+            self.throttle.rest(started.elapsed(), &mut owed);
+        }
+    }
+
+    /// Run every worker on a thread of its own until all of them have
+    /// ended. `make_worker` sets up the batch function of a worker, on that
+    /// worker's thread. A panic on any thread is passed on once all are done.
+    /// If a thread can't be started, the ones already running are stopped
+    /// and the search fails with `Error::ThreadPool`.
+    #[cfg(feature = "parallel")]
+    pub fn run<W, B>(&self, make_worker: W) -> Result<(), Error>
+    where
+        W: Fn(usize) -> B + Sync,
+        B: FnMut() -> usize,
+    {
+        self.stats.start();
+        let joined = crossbeam_utils::thread::scope(|scope| {
+            for worker in 0..self.workers {
+                let make_worker = &make_worker;
+                let spawned = scope.builder().name(format!("vanity-search-{}", worker)).spawn(move |_| {
+                    if let Some(ThreadStart(start)) = &self.thread_start {
+                        start(worker);
+                    }
+                    self.work(worker, make_worker(worker));
+                });
+                // Only when the OS is out of threads or memory
+                if let Err(e) = spawned {
+                    self.stop();
+                    return Err(Error::ThreadPool(format!("Failed to start search thread {}: {}", worker, e)));
+                }
+            }
+            Ok(())
+        });
+        self.stats.finish();
+        joined.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
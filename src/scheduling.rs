@@ -0,0 +1,116 @@
+// CPU placement and priority of the search threads
+
+use clap::Args;
+use tracing::warn;
+
+use vanity_address_rust::ThreadStart;
+
+/// Options controlling where and how eagerly the search threads run
+#[derive(Args, Debug, Clone)]
+pub struct Scheduling {
+    /// Pin each search thread to its own core: all cores in order, or a
+    /// list like 0-3,8,10
+    #[clap(long, value_name = "CORES", num_args = 0..=1, default_missing_value = "all")]
+    pub pin_cores: Option<String>,
+
+    /// Run at the lowest CPU priority, so other work on the machine comes first
+    #[clap(long)]
+    pub low_priority: bool,
+
+    /// Without --threads, run a thread per logical CPU (hyperthread) instead
+    /// of one per physical core
+    #[clap(long)]
+    pub use_smt: bool,
+}
+
+impl Scheduling {
+    /// Number of search threads: as asked for, or by default one per
+    /// physical core, since sibling hyperthreads add little for this workload
+    pub fn threads(&self, threads: Option<usize>) -> usize {
+        match threads {
+            Some(threads) => threads,
+            None if self.use_smt => num_cpus::get(),
+            None => num_cpus::get_physical(),
+        }
+    }
+
+    /// Lower the priority if asked to. Must run before any search thread is
+    /// started, since threads inherit the priority of the thread creating them.
+    pub fn apply_priority(&self) {
+        if self.low_priority {
+            if let Err(e) = lower_priority() {
+                warn!("failed to lower the priority: {}", e);
+            }
+        }
+    }
+
+    /// The cores to pin thread `i` to `cores[i % cores.len()]`, if pinning
+    pub fn cores(&self) -> Result<Option<Vec<usize>>, String> {
+        let Some(spec) = &self.pin_cores else { return Ok(None) };
+        let available: Vec<usize> = core_affinity::get_core_ids()
+            .ok_or("Pinning threads to cores is not supported on this platform")?
+            .into_iter()
+            .map(|core| core.id)
+            .collect();
+
+        if spec == "all" {
+            return Ok(Some(available));
+        }
+
+        let cores = parse_core_list(spec)?;
+        if let Some(core) = cores.iter().find(|core| !available.contains(core)) {
+            return Err(format!("Core {} is not available; this machine has cores {:?}", core, available));
+        }
+        Ok(Some(cores))
+    }
+}
+
+/// The search threads to run, as configured
+#[derive(Clone, Debug)]
+pub struct Threads {
+    pub count: usize,
+    /// Sets up every thread as it starts
+    pub start: Option<ThreadStart>,
+}
+
+/// Pin the calling thread to `core`
+pub fn pin_current_thread(core: usize) {
+    if !core_affinity::set_for_current(core_affinity::CoreId { id: core }) {
+        warn!("failed to pin a thread to core {}", core);
+    }
+}
+
+// Parse a list of cores and inclusive ranges, like "0-3,8,10"
+fn parse_core_list(spec: &str) -> Result<Vec<usize>, String> {
+    let invalid = || format!("Invalid core list '{}': expected e.g. 0-3,8,10", spec);
+    let mut cores = Vec::new();
+
+    for part in spec.split(',').map(str::trim) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let start: usize = start.trim().parse().map_err(|_| invalid())?;
+                let end: usize = end.trim().parse().map_err(|_| invalid())?;
+                if start > end {
+                    return Err(invalid());
+                }
+                cores.extend(start..=end);
+            }
+            None => cores.push(part.parse().map_err(|_| invalid())?),
+        }
+    }
+    Ok(cores)
+}
+
+#[cfg(unix)]
+fn lower_priority() -> Result<(), String> {
+    // SAFETY: setpriority has no memory safety preconditions
+    match unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error().to_string()),
+    }
+}
+
+#[cfg(not(unix))]
+fn lower_priority() -> Result<(), String> {
+    Err("not supported on this platform".to_string())
+}
@@ -0,0 +1,317 @@
+// The search loop
+//
+// A `Worker` checks one batch of candidates at a time. With the `parallel`
+// feature every thread of the search's scheduler runs its own worker; without
+// it (e.g. in the browser) the caller drives a single worker on its own thread.
+//
+// Taproot searches always use the incremental generator: each thread keeps
+// one internal key fixed and grinds only the tweak added to it, so every
+// candidate output key costs one point addition, and its witness program is
+// the key itself, with no hashing at all.
+//
+// P2WSH searches hash a whole multisig witness script per candidate instead
+// of one public key (see `multisig`), and node ID searches match the
+// serialized public key itself.
+//
+// Silent payment searches grind the scan key, and pair every candidate with
+// a spend key drawn once per thread, ahead of the thread's candidates.
+//
+// BIP85 searches derive every candidate from a master key instead of the
+// RNG (see `bip85`), and end once they run out of child indices.
+//
+// A custom matcher (see `plugin`) gets the encoded address of every
+// candidate the patterns let through, and has the last word.
+
+use bitcoin::hashes::Hash;
+use bitcoin::key::PublicKey;
+use bitcoin::secp256k1::{self, Secp256k1, SecretKey};
+use bitcoin::Network;
+use std::sync::Arc;
+use zeroize::Zeroize;
+#[cfg(feature = "parallel")]
+use crossbeam_channel::Sender;
+
+use crate::address::{silent_payment_address, AddressType};
+use crate::bip85::{Bip85Child, Bip85Indices};
+#[cfg(feature = "parallel")]
+use crate::error::Error;
+use crate::hash::HashBackend;
+use crate::keygen::{KeyGenerator, BATCH_SIZE};
+use crate::matcher::{Matcher, MatcherSet, Patterns};
+use crate::multisig::Multisig;
+use crate::plugin::CustomMatcher;
+use crate::rng::{KeyRng, Seed};
+#[cfg(feature = "parallel")]
+use crate::scheduler::Scheduler;
+use crate::secret::{SecretBuf, SecretString};
+use crate::{SpendKey, TaprootTweak};
+
+/// Everything a worker thread needs to know about the search
+pub struct Search {
+    pub patterns: Patterns,
+    pub incremental: bool,
+    pub hash_backend: HashBackend,
+    pub address_type: AddressType,
+    /// Derive every thread's candidates from this seed instead of the OS RNG
+    pub seed: Option<Seed>,
+    /// The wallet of a P2WSH search
+    pub multisig: Option<Multisig>,
+    /// Derive the candidates from a BIP85 master key
+    pub bip85: Option<Arc<Bip85Indices>>,
+    /// Checked after the patterns, on the encoded address
+    pub custom_matcher: Option<Arc<dyn CustomMatcher>>,
+}
+
+/// A matching address together with its hex encoded private key
+#[derive(Clone, Debug)]
+pub struct Found {
+    pub address: String,
+    pub private_key: SecretString,
+    pub taproot: Option<TaprootTweak>,
+    pub bip85: Option<Bip85Child>,
+    pub spend_key: Option<SpendKey>,
+    /// The patterns the address matched
+    pub pattern: String,
+    pub suffix: Option<String>,
+    /// The custom matcher's score
+    pub score: Option<u32>,
+}
+
+/// Key generator and buffers of one search thread
+pub struct Worker {
+    secp: Secp256k1<secp256k1::All>,
+    keys: KeyGenerator,
+    batch: Vec<secp256k1::PublicKey>,
+    // Witness programs: 20 byte hash160s, 32 byte x-only taproot keys or
+    // witness script hashes, or 66 byte silent payment scan and spend keys;
+    // or 33 byte node IDs
+    programs: [[u8; 66]; BATCH_SIZE],
+    // Witness script of the current P2WSH candidate
+    script: Vec<u8>,
+    // Spend key of a silent payment search
+    spend_key: Option<(SecretBuf<SecretKey>, secp256k1::PublicKey)>,
+    // This thread's snapshot of the search's patterns
+    matchers: Arc<MatcherSet>,
+    generation: u64,
+}
+
+impl Worker {
+    /// The worker of the thread with index `thread_id`, which selects the
+    /// thread's stream of a seeded search
+    pub fn new(search: &Search, thread_id: usize) -> Self {
+        let secp = Secp256k1::new();
+        let mut rng = KeyRng::new(search.seed.as_ref(), thread_id);
+        let spend_key = (search.address_type == AddressType::SilentPayment).then(|| {
+            let mut secret_key = SecretKey::new(&mut rng);
+            let public_key = secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
+            let spend_key = (SecretBuf::new(secret_key, 1), public_key);
+            secret_key.non_secure_erase();
+            spend_key
+        });
+        let keys = match &search.bip85 {
+            Some(indices) => KeyGenerator::bip85(Arc::clone(indices)),
+            None => KeyGenerator::new(&secp, search.incremental, rng),
+        };
+        let batch = keys.batch(&secp);
+        let (generation, matchers) = search.patterns.snapshot();
+
+        Worker {
+            secp,
+            keys,
+            batch,
+            programs: [[0u8; 66]; BATCH_SIZE],
+            script: Vec::new(),
+            spend_key,
+            matchers,
+            generation,
+        }
+    }
+
+    /// Check the next `BATCH_SIZE` candidates, handing every match to
+    /// `on_found`. Returns the number of candidates checked, which is only
+    /// less than `BATCH_SIZE` once a BIP85 search runs out of indices.
+    #[inline]
+    pub fn step(&mut self, search: &Search, mut on_found: impl FnMut(Found)) -> usize {
+        if search.patterns.generation() != self.generation {
+            (self.generation, self.matchers) = search.patterns.snapshot();
+        }
+
+        // Derive, hash and match a whole batch at a time so each
+        // stage runs as a tight loop over one buffer
+        let count = self.keys.fill(&self.secp, &mut self.batch);
+        let batch = &self.batch[..count];
+        let program_len = match search.address_type {
+            AddressType::P2wpkh => {
+                for (program, public_key) in self.programs.iter_mut().zip(batch) {
+                    program[..20].copy_from_slice(&search.hash_backend.hash160(&public_key.serialize()));
+                }
+                20
+            }
+            AddressType::P2tr => {
+                for (program, public_key) in self.programs.iter_mut().zip(batch) {
+                    program[..32].copy_from_slice(&public_key.x_only_public_key().0.serialize());
+                }
+                32
+            }
+            AddressType::P2wsh => {
+                let multisig = search.multisig.as_ref().expect("P2WSH searches have a wallet");
+                for (program, public_key) in self.programs.iter_mut().zip(batch) {
+                    program[..32].copy_from_slice(&multisig.program(
+                        &public_key.serialize(),
+                        &mut self.script,
+                        search.hash_backend,
+                    ));
+                }
+                32
+            }
+            AddressType::NodeId => {
+                for (program, public_key) in self.programs.iter_mut().zip(batch) {
+                    program[..33].copy_from_slice(&public_key.serialize());
+                }
+                33
+            }
+            AddressType::SilentPayment => {
+                let (_, spend_key) = self.spend_key.as_ref().expect("silent payment searches have a spend key");
+                let spend_key = spend_key.serialize();
+                for (program, public_key) in self.programs.iter_mut().zip(batch) {
+                    program[..33].copy_from_slice(&public_key.serialize());
+                    program[33..].copy_from_slice(&spend_key);
+                }
+                66
+            }
+        };
+
+        // Only encode the full address once we know it matches
+        for (index, program) in self.programs[..count].iter().enumerate() {
+            if let Some(matcher) = self.matchers.find(&program[..program_len]) {
+                let address = match &self.spend_key {
+                    Some((_, spend_key)) => silent_payment_address(&self.batch[index], spend_key),
+                    None => search.address(&self.batch[index]),
+                };
+                let score = match &search.custom_matcher {
+                    Some(custom) => match custom.score(&address, &program[..program_len]) {
+                        Some(score) => Some(score),
+                        None => continue,
+                    },
+                    None => None,
+                };
+                let mut secret_key = self.keys.secret_key(index);
+                on_found(Found {
+                    address,
+                    private_key: secret_hex(&secret_key),
+                    taproot: self.taproot_tweak(search, index),
+                    bip85: self.keys.bip85_child(index),
+                    spend_key: self.spend_key.as_ref().map(|(secret_key, public_key)| SpendKey {
+                        private_key: secret_hex(&secret_key[0]),
+                        public_key: public_key.to_string(),
+                    }),
+                    pattern: matcher.pattern().to_string(),
+                    suffix: matcher.suffix().map(str::to_string),
+                    score,
+                });
+                secret_key.non_secure_erase();
+            }
+        }
+        count
+    }
+
+    fn taproot_tweak(&self, search: &Search, index: usize) -> Option<TaprootTweak> {
+        if search.address_type != AddressType::P2tr {
+            return None;
+        }
+        let (internal_key, offset) = self.keys.offset(&self.secp, index)?;
+        let mut tweak = [0u8; 32];
+        tweak[24..].copy_from_slice(&offset.to_be_bytes());
+        Some(TaprootTweak {
+            internal_key: internal_key.x_only_public_key().0.to_string(),
+            tweak: hex::encode(tweak),
+        })
+    }
+}
+
+impl Search {
+    /// The address of a candidate key
+    pub fn address(&self, public_key: &secp256k1::PublicKey) -> String {
+        self.address_type
+            .encode(public_key, self.multisig.as_ref(), Network::Bitcoin)
+            .expect("candidates are compressed keys, and P2WSH searches have a wallet")
+    }
+
+    /// Re-derive the address of a hex encoded private key, unless it takes
+    /// more than the one key
+    pub fn address_for_private_key(&self, private_key: &str) -> Option<String> {
+        let public_key = public_key_of(private_key)?;
+        self.address_type.encode(&public_key, self.multisig.as_ref(), Network::Bitcoin)
+    }
+
+    /// Check a match the slow way, sharing nothing with the batch code that
+    /// found it: derive the address from the private key alone, and match
+    /// the address text against the patterns it is said to match
+    pub fn check(&self, found: &Found) -> Result<(), String> {
+        let derived = match &found.spend_key {
+            Some(spend_key) => {
+                let scan_key = public_key_of(found.private_key.as_str());
+                let spend_key = public_key_of(spend_key.private_key.as_str());
+                scan_key.zip(spend_key).map(|(scan_key, spend_key)| silent_payment_address(&scan_key, &spend_key))
+            }
+            None => self.address_for_private_key(found.private_key.as_str()),
+        };
+        match derived {
+            Some(derived) if derived == found.address => {}
+            Some(derived) => return Err(format!("its key derives {}, not {}", derived, found.address)),
+            None => return Err(format!("no address derives from the key of {}", found.address)),
+        }
+
+        let matcher = Matcher::new(&found.pattern, found.suffix.as_deref(), self.address_type)?;
+        if !matcher.matches_address(&found.address) {
+            return Err(format!("{} doesn't match the pattern it was found for", found.address));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl Search {
+    /// Search on every thread of `scheduler` until it is stopped, sending
+    /// every match to `found_tx` as it is found. Deciding when to stop is up
+    /// to the receiver. Fails if the search threads can't be started.
+    pub fn run(&self, scheduler: &Scheduler, found_tx: &Sender<Found>) -> Result<(), Error> {
+        // Workers poll the stop flag with relaxed loads and hand results over
+        // the channel, so nothing on the critical path takes a lock
+        scheduler.run(|thread_id| {
+            let mut worker = Worker::new(self, thread_id);
+            move || {
+                worker.step(self, |found| {
+                    let _ = found_tx.send(found);
+                })
+            }
+        })
+    }
+}
+
+// The public key of a hex encoded private key
+fn public_key_of(private_key: &str) -> Option<secp256k1::PublicKey> {
+    let mut secret_key: SecretKey = private_key.parse().ok()?;
+    let public_key = secp256k1::PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+    secret_key.non_secure_erase();
+    Some(public_key)
+}
+
+/// BIP32-style fingerprint of the key: the first 4 bytes of the hash160 of
+/// its compressed public key, in hex
+pub fn key_fingerprint(private_key: &str) -> Option<String> {
+    let public_key = public_key_of(private_key)?;
+    Some(hex::encode(&PublicKey::new(public_key).pubkey_hash().as_byte_array()[..4]))
+}
+
+/// Hex encode a secret key, wiping the intermediate copies
+pub fn secret_hex(secret_key: &SecretKey) -> SecretString {
+    let mut bytes = secret_key.secret_bytes();
+    let mut hex = [0u8; 64];
+    hex::encode_to_slice(bytes, &mut hex).expect("64 hex digits for 32 bytes");
+
+    let secret = SecretString::new(std::str::from_utf8(&hex).expect("hex is ASCII"));
+    bytes.zeroize();
+    hex.zeroize();
+    secret
+}
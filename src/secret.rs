@@ -0,0 +1,217 @@
+// Heap buffers for secret material
+//
+// Every buffer gets its own page-aligned allocation, so it can be locked into
+// RAM with mlock without sharing a page with unrelated data (mlock is not
+// reference counted, so unlocking a shared page would unlock its neighbours
+// too). Buffers are wiped before their memory is returned. Locking is best
+// effort: a buffer that can't be locked, e.g. because RLIMIT_MEMLOCK is used
+// up, still works, it just isn't kept out of swap.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::alloc::{self, Layout};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, Ordering};
+use zeroize::Zeroize;
+
+// Whether new secret buffers should be locked into RAM
+static LOCK_MEMORY: AtomicBool = AtomicBool::new(false);
+
+/// Lock every secret buffer allocated from now on (candidate keys and the
+/// private keys of matches) into RAM, so keys never end up in swap.
+///
+/// Fails, leaving locking off, if the OS refuses to lock memory for this
+/// process at all. Later buffers that don't fit in the remaining
+/// `RLIMIT_MEMLOCK` budget silently fall back to ordinary memory.
+pub fn lock_memory() -> Result<(), String> {
+    let probe = SecretBuf::<u8>::with_lock(0, 1, true);
+    if !probe.locked {
+        return Err(format!("Failed to lock memory: {}", lock_error()));
+    }
+    LOCK_MEMORY.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// A fixed-size heap buffer of `T` in pages of its own, wiped when dropped
+pub(crate) struct SecretBuf<T: Copy> {
+    ptr: NonNull<T>,
+    len: usize,
+    layout: Layout,
+    locked: bool,
+}
+
+// The buffer owns its allocation exclusively, like a Box<[T]>
+unsafe impl<T: Copy + Send> Send for SecretBuf<T> {}
+unsafe impl<T: Copy + Sync> Sync for SecretBuf<T> {}
+
+impl<T: Copy> SecretBuf<T> {
+    /// `len` copies of `fill`, locked into RAM if `lock_memory` was called
+    pub fn new(fill: T, len: usize) -> Self {
+        Self::with_lock(fill, len, LOCK_MEMORY.load(Ordering::Relaxed))
+    }
+
+    fn with_lock(fill: T, len: usize, lock: bool) -> Self {
+        let page = page_size();
+        let size = (len.max(1) * std::mem::size_of::<T>()).div_ceil(page) * page;
+        let layout = Layout::from_size_align(size, page.max(std::mem::align_of::<T>())).expect("valid layout");
+
+        // SAFETY: `layout` has a non-zero size, and every element is
+        // initialized before the buffer is handed out
+        let ptr = unsafe {
+            let ptr = alloc::alloc(layout) as *mut T;
+            let Some(ptr) = NonNull::new(ptr) else { alloc::handle_alloc_error(layout) };
+            for i in 0..len {
+                ptr.as_ptr().add(i).write(fill);
+            }
+            ptr
+        };
+        let locked = lock && mlock(ptr.as_ptr() as *const u8, size);
+
+        SecretBuf { ptr, len, layout, locked }
+    }
+}
+
+impl<T: Copy> Deref for SecretBuf<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: the first `len` elements are initialized and owned by us
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: Copy> DerefMut for SecretBuf<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: as above, and `&mut self` guarantees exclusive access
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: Copy> Drop for SecretBuf<T> {
+    fn drop(&mut self) {
+        // SAFETY: the allocation is `layout.size()` bytes long and `T: Copy`
+        // has no destructor that could observe the wiped bytes
+        unsafe {
+            let bytes = std::slice::from_raw_parts_mut(self.ptr.as_ptr() as *mut u8, self.layout.size());
+            bytes.zeroize();
+            if self.locked {
+                munlock(bytes.as_ptr(), bytes.len());
+            }
+            alloc::dealloc(self.ptr.as_ptr() as *mut u8, self.layout);
+        }
+    }
+}
+
+/// A string holding secret material, such as a hex encoded private key
+///
+/// Wiped from memory when dropped, and locked into RAM if [`lock_memory`]
+/// was called. `Debug` output is redacted.
+pub struct SecretString {
+    buf: SecretBuf<u8>,
+}
+
+impl SecretString {
+    pub fn new(s: &str) -> Self {
+        let mut buf = SecretBuf::new(0, s.len());
+        buf.copy_from_slice(s.as_bytes());
+        SecretString { buf }
+    }
+
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.buf).expect("created from a str")
+    }
+}
+
+impl Deref for SecretString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Clone for SecretString {
+    fn clone(&self) -> Self {
+        SecretString::new(self.as_str())
+    }
+}
+
+impl std::fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretString(..)")
+    }
+}
+
+impl From<String> for SecretString {
+    /// Move the secret out of `s`, wiping the original
+    fn from(mut s: String) -> Self {
+        let secret = SecretString::new(&s);
+        s.zeroize();
+        secret
+    }
+}
+
+impl Serialize for SecretString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(SecretString::from)
+    }
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as usize,
+        _ => 4096,
+    }
+}
+
+#[cfg(not(unix))]
+fn page_size() -> usize {
+    4096
+}
+
+#[cfg(unix)]
+fn mlock(ptr: *const u8, len: usize) -> bool {
+    // SAFETY: the range is a live allocation of ours
+    unsafe { libc::mlock(ptr as *const libc::c_void, len) == 0 }
+}
+
+#[cfg(unix)]
+fn munlock(ptr: *const u8, len: usize) {
+    // SAFETY: as above
+    unsafe { libc::munlock(ptr as *const libc::c_void, len) };
+}
+
+#[cfg(unix)]
+fn lock_error() -> String {
+    let error = std::io::Error::last_os_error();
+    // SAFETY: getrlimit only writes to the struct we pass
+    let limit = unsafe {
+        let mut limit = std::mem::zeroed::<libc::rlimit>();
+        (libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) == 0).then_some(limit.rlim_cur)
+    };
+    match limit {
+        Some(limit) if limit != libc::RLIM_INFINITY => format!("{} (RLIMIT_MEMLOCK is {} bytes)", error, limit),
+        _ => error.to_string(),
+    }
+}
+
+#[cfg(not(unix))]
+fn mlock(_ptr: *const u8, _len: usize) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+fn munlock(_ptr: *const u8, _len: usize) {}
+
+#[cfg(not(unix))]
+fn lock_error() -> String {
+    "memory locking is not supported on this platform".to_string()
+}
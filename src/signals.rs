@@ -0,0 +1,69 @@
+// Pausing and resuming a search with SIGUSR1 and SIGUSR2
+//
+// Both signals are blocked on every thread and received synchronously by one
+// listener thread with sigwait, so no code runs in a signal handler. Threads
+// inherit the signal mask of the thread that spawns them, so `block` has to
+// run before the search and reporter threads start.
+
+use std::sync::Arc;
+use vanity_address_rust::VanitySearch;
+
+/// Block SIGUSR1 and SIGUSR2 on this thread and every thread it spawns from
+/// now on, leaving them to `listen`
+#[cfg(unix)]
+pub fn block() -> Result<(), String> {
+    let set = pause_signals();
+    // SAFETY: `set` is an initialized signal set, and the old mask isn't read
+    match unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut()) } {
+        0 => Ok(()),
+        error => Err(std::io::Error::from_raw_os_error(error).to_string()),
+    }
+}
+
+/// Pause `search` on SIGUSR1 and resume it on SIGUSR2, on a background
+/// thread that lives until the process exits
+#[cfg(unix)]
+pub fn listen(search: Arc<VanitySearch>) {
+    std::thread::spawn(move || {
+        let set = pause_signals();
+        loop {
+            let mut signal = 0;
+            // SAFETY: `set` is an initialized signal set and `signal` a valid
+            // place for the result
+            if unsafe { libc::sigwait(&set, &mut signal) } != 0 {
+                return;
+            }
+            match signal {
+                libc::SIGUSR1 if !search.is_paused() => {
+                    search.pause();
+                    tracing::info!("Paused after {} attempts, send SIGUSR2 to resume", search.attempts());
+                }
+                libc::SIGUSR2 if search.is_paused() => {
+                    search.resume();
+                    tracing::info!("Resumed");
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+#[cfg(unix)]
+fn pause_signals() -> libc::sigset_t {
+    // SAFETY: sigemptyset initializes the set before sigaddset reads it
+    unsafe {
+        let mut set = std::mem::zeroed::<libc::sigset_t>();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGUSR1);
+        libc::sigaddset(&mut set, libc::SIGUSR2);
+        set
+    }
+}
+
+#[cfg(not(unix))]
+pub fn block() -> Result<(), String> {
+    Err("not supported on this platform".to_string())
+}
+
+#[cfg(not(unix))]
+pub fn listen(_search: Arc<VanitySearch>) {}
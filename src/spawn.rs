@@ -0,0 +1,97 @@
+// Async front-end for embedding the search in a tokio application
+//
+// The search itself stays on its own threads; the runtime only hosts a tiny
+// task that turns cancellation of the token into a call to `stop`.
+
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::{oneshot, watch};
+use tokio_util::sync::CancellationToken;
+
+use crate::{Error, Event, Match, Progress, VanitySearch};
+
+/// Handle to a search started with [`VanitySearch::spawn`]
+///
+/// Awaiting the handle yields the match, or `None` if the search was
+/// cancelled, or the error if its threads couldn't be started. Dropping the
+/// handle does not stop the search.
+pub struct SearchHandle {
+    result: oneshot::Receiver<Result<Option<Match>, Error>>,
+    cancel: CancellationToken,
+    progress: watch::Receiver<Progress>,
+}
+
+impl SearchHandle {
+    /// Stop the search; the handle then resolves to `None`
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Token that stops the search when cancelled, e.g. to tie it to the
+    /// shutdown of a larger service
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Progress updated every stats interval, and once more when the search ends
+    pub fn progress(&self) -> watch::Receiver<Progress> {
+        self.progress.clone()
+    }
+}
+
+impl Future for SearchHandle {
+    type Output = Result<Option<Match>, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The sender only goes away without a value if the search panicked
+        Pin::new(&mut self.result).poll(cx).map(|result| result.unwrap_or(Ok(None)))
+    }
+}
+
+impl VanitySearch {
+    /// Run the search on its own threads without blocking the runtime and
+    /// return a handle to await the first match. Publishes progress every
+    /// `stats_interval`. Must be called from within a tokio runtime.
+    pub fn spawn(self, stats_interval: Duration) -> SearchHandle {
+        let search = Arc::new(self);
+        let cancel = CancellationToken::new();
+        let (progress_tx, progress) = watch::channel(search.progress());
+        let (result_tx, result) = oneshot::channel();
+        let (done_tx, done_rx) = oneshot::channel::<()>();
+
+        {
+            let search = Arc::clone(&search);
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = cancel.cancelled() => search.stop(),
+                    _ = done_rx => {}
+                }
+            });
+        }
+
+        std::thread::spawn(move || {
+            let mut found = None;
+            let run = search.run_with(stats_interval, |event| match event {
+                Event::Progress(p) => {
+                    let _ = progress_tx.send(p);
+                    ControlFlow::Continue(())
+                }
+                Event::Match(m) => {
+                    found = Some(m);
+                    ControlFlow::Break(())
+                }
+            });
+
+            let _ = progress_tx.send(search.progress());
+            drop(done_tx);
+            let _ = result_tx.send(run.map(|()| found));
+        });
+
+        SearchHandle { result, cancel, progress }
+    }
+}
@@ -0,0 +1,102 @@
+// Search progress statistics
+//
+// Each worker owns one counter and bumps it with a relaxed add, so the hot
+// loop never takes a lock. The counters are cache-line padded to keep
+// threads from invalidating each other's lines.
+//
+// The clock only runs while the search does: time spent paused is left out
+// of the elapsed time, and with it out of every rate and timeout.
+
+use crossbeam_utils::CachePadded;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+// std's clock panics in the browser; web-time uses the JS clock there and is
+// std's clock everywhere else
+#[cfg(not(feature = "wasm"))]
+pub use std::time::Instant;
+#[cfg(feature = "wasm")]
+pub use web_time::Instant;
+
+pub struct Stats {
+    counters: Vec<CachePadded<AtomicU64>>,
+    started_at: OnceLock<Instant>,
+    // Total running time, frozen once the search returns
+    finished_after: Mutex<Option<Duration>>,
+    paused: Mutex<Paused>,
+}
+
+#[derive(Default)]
+struct Paused {
+    // Time spent in earlier pauses
+    total: Duration,
+    // Start of the current pause
+    since: Option<Instant>,
+}
+
+impl Stats {
+    pub fn new(threads: usize) -> Self {
+        Stats {
+            counters: (0..threads).map(|_| CachePadded::new(AtomicU64::new(0))).collect(),
+            started_at: OnceLock::new(),
+            finished_after: Mutex::new(None),
+            paused: Mutex::new(Paused::default()),
+        }
+    }
+
+    /// Start the clock, if it isn't running already
+    pub fn start(&self) {
+        self.started_at.get_or_init(Instant::now);
+        *self.finished_after.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Freeze the clock
+    pub fn finish(&self) {
+        *self.finished_after.lock().unwrap_or_else(PoisonError::into_inner) = Some(self.elapsed());
+    }
+
+    /// Stop the clock until `resume`
+    pub fn pause(&self) {
+        let mut paused = self.paused.lock().unwrap_or_else(PoisonError::into_inner);
+        paused.since.get_or_insert_with(Instant::now);
+    }
+
+    pub fn resume(&self) {
+        let now = Instant::now();
+        let mut paused = self.paused.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(since) = paused.since.take() {
+            paused.total += now.saturating_duration_since(self.paused_since(since));
+        }
+    }
+
+    // A pause that began before the search started only counts from the start
+    fn paused_since(&self, since: Instant) -> Instant {
+        self.started_at.get().map_or(since, |&started_at| since.max(started_at))
+    }
+
+    pub fn increment(&self, thread_id: usize, count: u64) {
+        self.counters[thread_id].fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn attempts(&self) -> u64 {
+        self.counters.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+    }
+
+    pub fn thread_attempts(&self) -> Vec<u64> {
+        self.counters.iter().map(|c| c.load(Ordering::Relaxed)).collect()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        if let Some(elapsed) = *self.finished_after.lock().unwrap_or_else(PoisonError::into_inner) {
+            return elapsed;
+        }
+        let Some(&started_at) = self.started_at.get() else {
+            return Duration::ZERO;
+        };
+        let now = Instant::now();
+        let paused = self.paused.lock().unwrap_or_else(PoisonError::into_inner);
+        let paused_for = paused.total + paused.since.map_or(Duration::ZERO, |since| now - self.paused_since(since));
+        now.saturating_duration_since(started_at).saturating_sub(paused_for)
+    }
+}
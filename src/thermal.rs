@@ -0,0 +1,164 @@
+// Backing off when the CPU runs hot
+//
+// With --max-temp, a monitor thread reads the CPU temperature every few
+// seconds. While it is above the threshold, the search's CPU limit (see
+// --max-cpu) is lowered a step at a time; once the CPU has cooled down a few
+// degrees below the threshold, it is raised again, up to the limit the search
+// started with. Every change is logged, and the stats show the current limit
+// and temperature.
+//
+// Temperatures come from Linux's sysfs, in millidegrees Celsius: the CPU
+// package's thermal zone or hwmon sensor if there is one, else the first
+// thermal zone. --temp-sensor picks any file in that format instead.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use vanity_address_rust::VanitySearch;
+
+use crate::reporter::spawn_reporter;
+
+// How often the temperature is checked
+const INTERVAL: Duration = Duration::from_secs(2);
+// Percentage points the limit moves per check
+const STEP: u8 = 10;
+// The lowest limit that throttling goes down to
+const MIN_LIMIT: u8 = 10;
+// Degrees below the threshold before the limit is raised again, so it
+// doesn't flip back and forth around the threshold
+const HYSTERESIS: f64 = 5.0;
+
+/// A file holding a temperature in millidegrees Celsius
+pub struct Sensor {
+    path: PathBuf,
+}
+
+impl Sensor {
+    pub fn at(path: &Path) -> Self {
+        Sensor { path: path.to_path_buf() }
+    }
+
+    /// The CPU's sensor, if this platform has one we know of
+    pub fn find() -> Option<Self> {
+        find_sensor().map(|path| Sensor { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The temperature in degrees Celsius
+    pub fn read(&self) -> Result<f64, String> {
+        let text = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("Failed to read {}: {}", self.path.display(), e))?;
+        let millidegrees: f64 = text
+            .trim()
+            .parse()
+            .map_err(|_| format!("{} doesn't hold a temperature: '{}'", self.path.display(), text.trim()))?;
+        Ok(millidegrees / 1000.0)
+    }
+}
+
+// Zones and hwmon chips that measure the CPU package rather than e.g. a
+// battery or a Wi-Fi card
+#[cfg(target_os = "linux")]
+const CPU_SENSORS: [&str; 6] = ["x86_pkg_temp", "cpu-thermal", "cpu_thermal", "coretemp", "k10temp", "zenpower"];
+
+#[cfg(target_os = "linux")]
+fn find_sensor() -> Option<PathBuf> {
+    let mut first_zone = None;
+    let mut zones: Vec<PathBuf> = std::fs::read_dir("/sys/class/thermal")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("thermal_zone")))
+        .collect();
+    zones.sort();
+    for zone in zones {
+        let name = std::fs::read_to_string(zone.join("type")).unwrap_or_default();
+        if CPU_SENSORS.contains(&name.trim()) {
+            return Some(zone.join("temp"));
+        }
+        first_zone.get_or_insert(zone.join("temp"));
+    }
+
+    for chip in std::fs::read_dir("/sys/class/hwmon").into_iter().flatten().flatten() {
+        let name = std::fs::read_to_string(chip.path().join("name")).unwrap_or_default();
+        if CPU_SENSORS.contains(&name.trim()) {
+            return Some(chip.path().join("temp1_input"));
+        }
+    }
+    first_zone
+}
+
+#[cfg(not(target_os = "linux"))]
+fn find_sensor() -> Option<PathBuf> {
+    None
+}
+
+/// What the monitor has seen so far, for the stats
+#[derive(Default)]
+pub struct Thermal {
+    celsius: Mutex<Option<f64>>,
+    throttled: AtomicU64,
+}
+
+impl Thermal {
+    /// The last temperature read
+    pub fn celsius(&self) -> Option<f64> {
+        *self.celsius.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// How many times the search was throttled for running hot
+    pub fn throttled(&self) -> u64 {
+        self.throttled.load(Ordering::Relaxed)
+    }
+}
+
+/// Keep the CPU of `search` below `max_temp` degrees by lowering its CPU
+/// limit, never raising it above `ceiling`
+pub fn spawn_monitor(
+    search: Arc<VanitySearch>,
+    sensor: Sensor,
+    max_temp: f64,
+    ceiling: u8,
+    stop: Arc<AtomicBool>,
+) -> (JoinHandle<()>, Arc<Thermal>) {
+    let thermal = Arc::new(Thermal::default());
+    let state = Arc::clone(&thermal);
+    let mut warned = false;
+
+    let handle = spawn_reporter(stop, INTERVAL, move || {
+        let celsius = match sensor.read() {
+            Ok(celsius) => celsius,
+            Err(e) => {
+                // Once is enough for a sensor that went away
+                if !warned {
+                    warn!("{}; not throttling until it can be read again", e);
+                    warned = true;
+                }
+                return;
+            }
+        };
+        warned = false;
+        *state.celsius.lock().unwrap_or_else(PoisonError::into_inner) = Some(celsius);
+
+        let limit = search.max_cpu();
+        if celsius > max_temp && limit > MIN_LIMIT {
+            let lowered = limit.saturating_sub(STEP).max(MIN_LIMIT);
+            warn!("CPU at {:.1}°C, above {}°C: throttling the search to {}%", celsius, max_temp, lowered);
+            let _ = search.set_max_cpu(lowered);
+            state.throttled.fetch_add(1, Ordering::Relaxed);
+        } else if celsius <= max_temp - HYSTERESIS && limit < ceiling {
+            let raised = limit.saturating_add(STEP).min(ceiling);
+            info!("CPU cooled down to {:.1}°C: raising the search to {}%", celsius, raised);
+            let _ = search.set_max_cpu(raised);
+        }
+    });
+    (handle, thermal)
+}
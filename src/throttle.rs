@@ -0,0 +1,52 @@
+// Limiting the CPU time of the search threads
+//
+// A throttled worker rests after its batches for long enough that it only
+// works the given percentage of the time: at 25%, every millisecond of work
+// is followed by three of sleep. A batch takes well under a millisecond, so
+// the rest owed is collected until it is worth a sleep of its own, which
+// keeps the number of sleeps (and the wakeup overhead) low.
+//
+// The limit can change while the search runs. Workers read it with a relaxed
+// load after every batch, so an unthrottled search pays next to nothing.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+// Shortest rest worth sleeping for
+const MIN_REST: Duration = Duration::from_millis(10);
+
+pub struct Throttle {
+    percent: AtomicU8,
+}
+
+impl Throttle {
+    /// Let workers use `percent` of their time, 1 to 100
+    pub fn new(percent: u8) -> Self {
+        Throttle {
+            percent: AtomicU8::new(percent),
+        }
+    }
+
+    pub fn set(&self, percent: u8) {
+        self.percent.store(percent, Ordering::Relaxed);
+    }
+
+    pub fn percent(&self) -> u8 {
+        self.percent.load(Ordering::Relaxed)
+    }
+
+    /// Account for `busy` time spent working, sleeping once the rest `owed`
+    /// by this worker adds up
+    pub fn rest(&self, busy: Duration, owed: &mut Duration) {
+        let percent = u32::from(self.percent());
+        if percent >= 100 {
+            *owed = Duration::ZERO;
+            return;
+        }
+        *owed += busy * (100 - percent) / percent.max(1);
+        if *owed >= MIN_REST {
+            std::thread::sleep(*owed);
+            *owed = Duration::ZERO;
+        }
+    }
+}
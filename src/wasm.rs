@@ -0,0 +1,62 @@
+// JavaScript bindings for searching in the browser
+//
+// A page can't block its thread on `run`, so it calls `step` repeatedly
+// instead, e.g. in a loop inside a Web Worker or from a timer, and every call
+// checks a bounded number of batches before handing control back.
+
+use wasm_bindgen::prelude::*;
+
+use crate::search::Worker;
+use crate::{SearchConfig, VanitySearch};
+
+/// A single-threaded search driven from JavaScript
+#[wasm_bindgen]
+pub struct WasmSearch {
+    search: VanitySearch,
+    worker: Worker,
+}
+
+/// A matching address and its hex encoded private key
+#[wasm_bindgen(getter_with_clone)]
+pub struct WasmMatch {
+    pub address: String,
+    #[wasm_bindgen(js_name = privateKey)]
+    pub private_key: String,
+}
+
+#[wasm_bindgen]
+impl WasmSearch {
+    #[wasm_bindgen(constructor)]
+    pub fn new(pattern: String, suffix: Option<String>, incremental: bool) -> Result<WasmSearch, JsError> {
+        let search = VanitySearch::new(SearchConfig {
+            pattern,
+            suffix,
+            incremental,
+            ..Default::default()
+        })
+        .map_err(|e| JsError::new(&e.to_string()))?;
+        let worker = search.worker();
+
+        Ok(WasmSearch { search, worker })
+    }
+
+    /// Check up to `batches` batches of candidates and return the first
+    /// match, or `undefined` to be called again
+    pub fn step(&mut self, batches: u32) -> Option<WasmMatch> {
+        (0..batches).find_map(|_| self.search.step(&mut self.worker)).map(|found| WasmMatch {
+            address: found.address,
+            private_key: found.private_key.to_string(),
+        })
+    }
+
+    /// Candidates checked so far
+    pub fn attempts(&self) -> f64 {
+        self.search.attempts() as f64
+    }
+
+    /// Seconds spent searching so far
+    #[wasm_bindgen(js_name = elapsedSecs)]
+    pub fn elapsed_secs(&self) -> f64 {
+        self.search.elapsed().as_secs_f64()
+    }
+}
@@ -0,0 +1,133 @@
+// Interactive wizard, for runs without any arguments
+//
+// Asks for the kind of address and the pattern, one question at a time, and
+// asks again right away for patterns that can never match, with the reason.
+// Before the search starts, it measures this machine's rate for a moment and
+// shows how long a match takes. The answers become the arguments of an
+// ordinary run, so the search behaves exactly as if they had been typed on
+// the command line, which is shown for next time.
+
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use vanity_address_rust::{benchmark, AddressType, SearchConfig, VanitySearch};
+
+use crate::estimate::format_time;
+
+// Long enough for a steady rate, short enough to go unnoticed
+const CALIBRATION: Duration = Duration::from_secs(1);
+
+// The kinds of address on offer. P2WSH needs the keys of every cosigner,
+// which is more than a wizard should ask for, so it's left to --cosigner.
+const CHOICES: &[(AddressType, &str)] = &[
+    (AddressType::P2wpkh, "native segwit address (bc1q...), what most wallets use"),
+    (AddressType::P2tr, "taproot address (bc1p...)"),
+    (AddressType::SilentPayment, "silent payment address (sp1q...)"),
+    (AddressType::NodeId, "Lightning node ID (02... or 03...)"),
+];
+
+/// Ask for a search on the terminal, and return the arguments to run it
+/// with, or `None` if it was called off. Calibrates on `threads` threads.
+pub fn ask(threads: usize) -> Result<Option<Vec<String>>, String> {
+    let mut lines = io::stdin().lock().lines();
+    let mut prompt = |question: &str| -> Result<Option<String>, String> {
+        eprint!("{} ", question);
+        let _ = io::stderr().flush();
+        match lines.next() {
+            Some(Ok(line)) => Ok(Some(line.trim().to_string())),
+            Some(Err(e)) => Err(format!("Failed to read the answer: {}", e)),
+            None => Ok(None),
+        }
+    };
+
+    eprintln!("No options given, so let's set up a search. Press Ctrl+D at any time to leave.\n");
+    eprintln!("What kind of address do you want?");
+    for (i, (_, description)) in CHOICES.iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, description);
+    }
+    let address_type = loop {
+        let Some(answer) = prompt("Choose 1-4 [1]:")? else { return Ok(None) };
+        match answer.parse::<usize>().ok().or(answer.is_empty().then_some(1)) {
+            Some(choice @ 1..=4) => break CHOICES[choice - 1].0,
+            _ => eprintln!("Please enter a number from 1 to 4."),
+        }
+    };
+    eprintln!("Addresses are for Bitcoin mainnet, the only network the search covers.\n");
+
+    let question = match address_type.prefix() {
+        "" => "Pattern at the start:".to_string(),
+        prefix => format!("Pattern after {}:", prefix),
+    };
+    // Each pattern is checked as soon as it's entered
+    let check = |pattern: &str, suffix: Option<&str>| {
+        VanitySearch::new(SearchConfig {
+            pattern: pattern.to_string(),
+            suffix: suffix.map(str::to_string),
+            address_type,
+            threads: Some(1),
+            ..Default::default()
+        })
+    };
+    let pattern = loop {
+        let Some(pattern) = prompt(&question)? else { return Ok(None) };
+        let pattern = pattern.to_lowercase();
+        match check(&pattern, None) {
+            Err(e) if !pattern.is_empty() => eprintln!("{}. Please try again.", e),
+            _ => break pattern,
+        }
+    };
+    let search = loop {
+        let question = if pattern.is_empty() { "Pattern at the end:" } else { "Pattern at the end (optional):" };
+        let Some(suffix) = prompt(question)? else { return Ok(None) };
+        let suffix = suffix.to_lowercase();
+        if pattern.is_empty() && suffix.is_empty() {
+            eprintln!("Please enter a pattern for the start, the end or both.");
+            continue;
+        }
+        match check(&pattern, (!suffix.is_empty()).then_some(suffix.as_str())) {
+            Ok(search) => break search,
+            Err(e) => eprintln!("{}. Please try again.", e),
+        }
+    };
+
+    eprintln!("\nMeasuring the speed of this machine...");
+    let config = SearchConfig {
+        address_type,
+        threads: Some(threads),
+        ..Default::default()
+    };
+    let rate = benchmark(config, CALIBRATION).map_err(|e| e.to_string())?.rate();
+    let expected = search.expected_attempts();
+    let plural = if threads == 1 { "" } else { "s" };
+    eprintln!("Expected attempts: {:.0}, at {:.0} addresses/s on {} thread{}", expected, rate, threads, plural);
+    if rate > 0.0 {
+        // Matches arrive at random: 9 in 10 within ln(10) times the average
+        eprintln!(
+            "Expected time: {} on average, {} for 9 searches in 10",
+            format_time(expected / rate),
+            format_time(expected * 10f64.ln() / rate)
+        );
+    }
+
+    let mut args = vec![
+        env!("CARGO_BIN_NAME").to_string(),
+        "--address-type".to_string(),
+        address_type.to_string(),
+    ];
+    if !search.pattern().is_empty() {
+        args.extend(["--pattern".to_string(), search.pattern().to_string()]);
+    }
+    if let Some(suffix) = search.suffix() {
+        args.extend(["--suffix".to_string(), suffix.to_string()]);
+    }
+    loop {
+        let Some(answer) = prompt("\nStart the search? [Y/n]")? else { return Ok(None) };
+        match answer.to_lowercase().as_str() {
+            "" | "y" | "yes" => break,
+            "n" | "no" => return Ok(None),
+            _ => eprintln!("Please answer y or n."),
+        }
+    }
+    eprintln!("Next time, run it right away with: {}\n", args.join(" "));
+    Ok(Some(args))
+}
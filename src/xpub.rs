@@ -0,0 +1,310 @@
+// Vanity extended public keys
+//
+// Watch-only wallets are set up from an account's extended public key, a
+// base58check string like "xpub6C...". This search looks for one that
+// contains a pattern anywhere. Every candidate is the hardened child at
+// `path/i'`: either account 0 of a fresh random master key, for a new
+// wallet, or account i of an existing wallet, with i ground from 0 up.
+//
+// Every candidate costs a few BIP32 derivations and a base58 encoding, far
+// more than an address candidate, so patterns have to be shorter. The
+// version and depth fix the first characters of every key, so matches only
+// turn up after them.
+
+use bitcoin::base58;
+use bitcoin::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+use bitcoin::secp256k1::rand::RngCore;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::Network;
+use clap::ValueEnum;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use zeroize::Zeroize;
+
+use crate::master::erase;
+use crate::rng::KeyRng;
+use crate::scheduler::{Scheduler, ThreadStart};
+use crate::{Error, SecretString};
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+// Length of an encoded extended key
+const ENCODED_LEN: usize = 111;
+// Leading characters that hardly vary: the version and the depth
+const FIXED_LEN: usize = 5;
+// Number of hardened child indices
+const INDICES: u64 = 1 << 31;
+
+/// The version an extended key is encoded with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum XpubFormat {
+    /// xpub and xprv, which BIP44 and most other wallets use
+    #[default]
+    Xpub,
+    /// zpub and zprv of BIP84 native segwit wallets
+    Zpub,
+}
+
+impl XpubFormat {
+    // Version bytes of the public and the private key
+    fn versions(self) -> ([u8; 4], [u8; 4]) {
+        match self {
+            XpubFormat::Xpub => ([0x04, 0x88, 0xb2, 0x1e], [0x04, 0x88, 0xad, 0xe4]),
+            XpubFormat::Zpub => ([0x04, 0xb2, 0x47, 0x46], [0x04, 0xb2, 0x43, 0x0c]),
+        }
+    }
+
+    /// Where wallets of this format keep their accounts: m/44'/0' or
+    /// m/84'/0'
+    pub fn default_path(self) -> &'static str {
+        match self {
+            XpubFormat::Xpub => "m/44'/0'",
+            XpubFormat::Zpub => "m/84'/0'",
+        }
+    }
+}
+
+impl std::fmt::Display for XpubFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            XpubFormat::Xpub => "xpub",
+            XpubFormat::Zpub => "zpub",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A matching extended public key and the private keys behind it
+#[derive(Clone, Debug)]
+pub struct XpubMatch {
+    /// The extended public key, in the search's format
+    pub xpub: String,
+    /// The extended private key of the same account, wiped from memory when
+    /// dropped
+    pub xprv: SecretString,
+    /// Derivation path of the account from its master key, e.g. m/84'/0'/3'
+    pub path: String,
+    /// For a search without a wallet of its own, the new wallet's master
+    /// key as an xprv, which recovers the account
+    pub master_key: Option<SecretString>,
+    /// Candidates checked before the match was found
+    pub attempts: u64,
+    /// Time spent searching
+    pub elapsed: Duration,
+}
+
+/// A search for an extended public key containing a pattern, on a thread
+/// per CPU thread of the machine unless configured otherwise
+pub struct XpubSearch {
+    pattern: String,
+    format: XpubFormat,
+    // Parent path of the ground accounts
+    path: DerivationPath,
+    // The key at `path` of an existing wallet, whose children are ground
+    parent: Option<ExtendedPrivKey>,
+    next_index: AtomicU64,
+    found: Mutex<Option<XpubMatch>>,
+    scheduler: Scheduler,
+}
+
+impl XpubSearch {
+    /// Search for a key in `format` containing `pattern`, below `path` (the
+    /// format's default path if `None`). With a wallet's `master` key, grind
+    /// its account index; without, grind new master keys.
+    pub fn new(
+        pattern: &str,
+        format: XpubFormat,
+        path: Option<&str>,
+        master: Option<ExtendedPrivKey>,
+    ) -> Result<Self, Error> {
+        if pattern.is_empty() {
+            return Err(Error::InvalidPattern("The pattern is empty".to_string()));
+        }
+        if let Some(c) = pattern.chars().find(|&c| !BASE58_ALPHABET.contains(c)) {
+            return Err(Error::InvalidPattern(format!(
+                "Invalid character '{}' in pattern '{}': extended keys only use \"{}\"",
+                c, pattern, BASE58_ALPHABET
+            )));
+        }
+        if pattern.len() > ENCODED_LEN - FIXED_LEN {
+            return Err(Error::InvalidPattern(format!(
+                "Patterns can be at most {} characters long",
+                ENCODED_LEN - FIXED_LEN
+            )));
+        }
+
+        let path = path.unwrap_or(format.default_path());
+        let path = DerivationPath::from_str(path)
+            .map_err(|e| Error::InvalidConfig(format!("Invalid derivation path '{}': {}", path, e)))?;
+        let parent = master
+            .map(|mut master| {
+                let parent = master.derive_priv(&Secp256k1::new(), &path);
+                erase(&mut master);
+                parent.map_err(|e| Error::InvalidConfig(format!("Failed to derive {}: {}", path, e)))
+            })
+            .transpose()?;
+
+        Ok(XpubSearch {
+            pattern: pattern.to_string(),
+            format,
+            path,
+            parent,
+            next_index: AtomicU64::new(0),
+            found: Mutex::new(None),
+            scheduler: Scheduler::new(Scheduler::available_threads(), None, 100),
+        })
+    }
+
+    /// Run on `threads` threads, calling `thread_start` on each as it starts
+    pub fn with_threads(mut self, threads: usize, thread_start: Option<ThreadStart>) -> Result<Self, Error> {
+        if threads == 0 {
+            return Err(Error::InvalidConfig("The number of threads must be at least 1".to_string()));
+        }
+        self.scheduler = Scheduler::new(threads, thread_start, 100);
+        Ok(self)
+    }
+
+    /// Search until a match is found or `stop` is called, or every account
+    /// index of the wallet has been tried. Returns `None` unless a match
+    /// was found, and fails if the search threads can't be started.
+    pub fn run(&self) -> Result<Option<XpubMatch>, Error> {
+        self.scheduler.run(|thread_id| {
+            let mut worker = XpubWorker::new(self, thread_id);
+            move || worker.step(self)
+        })?;
+        Ok(self.found.lock().unwrap_or_else(PoisonError::into_inner).take())
+    }
+
+    // Keep the first match and stop the other threads
+    fn record(&self, xpub: String, account: &ExtendedPrivKey, index: u64, master: Option<&ExtendedPrivKey>) {
+        let mut found = self.found.lock().unwrap_or_else(PoisonError::into_inner);
+        if found.is_none() {
+            *found = Some(XpubMatch {
+                xpub,
+                xprv: self.encode(account.encode(), true).into(),
+                path: format!("{}/{}'", self.path, index),
+                master_key: master.map(|master| master.to_string().into()),
+                attempts: self.attempts(),
+                elapsed: self.elapsed(),
+            });
+        }
+        self.scheduler.stop();
+    }
+
+    // Base58check with the search format's version
+    fn encode(&self, mut key: [u8; 78], private: bool) -> String {
+        let (public_version, private_version) = self.format.versions();
+        key[..4].copy_from_slice(if private { &private_version } else { &public_version });
+        let encoded = base58::encode_check(&key);
+        key.zeroize();
+        encoded
+    }
+
+    /// Stop the search; `run` returns once every thread has noticed
+    pub fn stop(&self) {
+        self.scheduler.stop();
+    }
+
+    /// Whether a search of a wallet has tried every account index, which
+    /// ends it
+    pub fn is_exhausted(&self) -> bool {
+        self.parent.is_some() && self.next_index.load(Ordering::Relaxed) >= INDICES
+    }
+
+    /// Candidates checked so far
+    pub fn attempts(&self) -> u64 {
+        self.scheduler.stats.attempts()
+    }
+
+    /// Time spent searching so far
+    pub fn elapsed(&self) -> Duration {
+        self.scheduler.stats.elapsed()
+    }
+
+    /// Rough number of candidates per match: every pattern character pins
+    /// down one of 58 values, at any position after the fixed ones
+    pub fn expected_attempts(&self) -> f64 {
+        let positions = ENCODED_LEN - FIXED_LEN - self.pattern.len() + 1;
+        58f64.powi(self.pattern.len() as i32) / positions as f64
+    }
+
+    /// Number of search threads
+    pub fn threads(&self) -> usize {
+        self.scheduler.workers()
+    }
+
+    /// The parent path of the ground accounts
+    pub fn path(&self) -> String {
+        self.path.to_string()
+    }
+
+    /// Whether the search grinds the account index of an existing wallet
+    pub fn has_wallet(&self) -> bool {
+        self.parent.is_some()
+    }
+}
+
+// The state of one search thread
+struct XpubWorker {
+    secp: Secp256k1<bitcoin::secp256k1::All>,
+    rng: KeyRng,
+    seed: [u8; 32],
+    // Account 0 of a new master key
+    account_path: DerivationPath,
+}
+
+impl XpubWorker {
+    fn new(search: &XpubSearch, thread_id: usize) -> Self {
+        XpubWorker {
+            secp: Secp256k1::new(),
+            rng: KeyRng::new(None, thread_id),
+            seed: [0u8; 32],
+            account_path: search.path.child(ChildNumber::Hardened { index: 0 }),
+        }
+    }
+
+    // Check one candidate, or none once the wallet's indices are used up
+    fn step(&mut self, search: &XpubSearch) -> usize {
+        // Derivations only fail for one in about 2^127 keys
+        let (mut account, index, master) = match &search.parent {
+            Some(parent) => {
+                let index = search.next_index.fetch_add(1, Ordering::Relaxed);
+                if index >= INDICES {
+                    return 0;
+                }
+                let child = ChildNumber::from_hardened_idx(index as u32).expect("index below 2^31");
+                (parent.ckd_priv(&self.secp, child).expect("valid child key"), index, None)
+            }
+            None => {
+                self.rng.fill_bytes(&mut self.seed);
+                let master = ExtendedPrivKey::new_master(Network::Bitcoin, &self.seed).expect("valid master key");
+                (master.derive_priv(&self.secp, &self.account_path).expect("valid child key"), 0, Some(master))
+            }
+        };
+
+        let xpub = search.encode(ExtendedPubKey::from_priv(&self.secp, &account).encode(), false);
+        if xpub.contains(&search.pattern) {
+            search.record(xpub, &account, index, master.as_ref());
+        }
+        erase(&mut account);
+        if let Some(mut master) = master {
+            erase(&mut master);
+        }
+        1
+    }
+}
+
+impl Drop for XpubWorker {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+impl Drop for XpubSearch {
+    fn drop(&mut self) {
+        if let Some(parent) = &mut self.parent {
+            erase(parent);
+        }
+    }
+}